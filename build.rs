@@ -1,37 +1,73 @@
+extern crate chrono;
 extern crate foreman;
 
+use chrono::TimeZone;
+use chrono::Utc;
+use std::env;
 use std::process::Command;
 use std::process::Output;
 
 fn main() {
     set_git_ver_env_var();
+    set_git_commit_env_var();
+    set_build_date_env_var();
 }
 
 fn set_git_ver_env_var() {
-    let git_ver = Command::new("git")
-        .args(&[
-            "describe",
-            "--tags",
-            "--first-parent",
-            "--always",
-            "--dirty",
-            "--broken",
-        ])
-        .output();
-
-    let git_ver = match git_ver {
+    let git_ver = run_git(&[
+        "describe",
+        "--tags",
+        "--first-parent",
+        "--always",
+        "--dirty",
+        "--broken",
+    ]);
+
+    if !git_ver.is_empty() {
+        eprintln!("Detected version from Git repository: {}", git_ver);
+    }
+
+    foreman::env_var("IRC_BOT_RS_GIT_VERSION", &git_ver);
+}
+
+fn set_git_commit_env_var() {
+    let git_commit = run_git(&["rev-parse", "HEAD"]);
+
+    foreman::env_var("IRC_BOT_RS_GIT_COMMIT", &git_commit);
+}
+
+/// Records the time of the build, or, for reproducible builds, the time given in the environment
+/// variable `SOURCE_DATE_EPOCH` (see <https://reproducible-builds.org/specs/source-date-epoch/>).
+fn set_build_date_env_var() {
+    let build_date = match env::var("SOURCE_DATE_EPOCH").ok().map(|s| s.parse()) {
+        Some(Ok(secs)) => Utc.timestamp_opt(secs, 0).single(),
+        Some(Err(e)) => {
+            foreman::warning(&format!("Error parsing `SOURCE_DATE_EPOCH`: {}", e));
+            None
+        }
+        None => Some(Utc::now()),
+    };
+
+    let build_date = build_date
+        .map(|date| date.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default();
+
+    foreman::env_var("IRC_BOT_RS_BUILD_DATE", &build_date);
+}
+
+/// Runs `git` with the given arguments, returning its output with surrounding whitespace trimmed,
+/// or the empty string if it fails.
+fn run_git(args: &[&str]) -> String {
+    match Command::new("git").args(args).output() {
         Ok(Output {
             ref status,
             ref stdout,
             ..
-        }) if status.success() => {
-            let v = String::from_utf8_lossy(stdout);
-            eprintln!("Detected version from Git repository: {}", v);
-            v
-        }
+        }) if status.success() => String::from_utf8_lossy(stdout).trim().to_owned(),
         o => {
             foreman::warning(&format!(
-                "Error running `git describe`: {}",
+                "Error running `git {}`: {}",
+                args.join(" "),
                 match o {
                     Ok(Output { ref stderr, .. }) => String::from_utf8_lossy(stderr).to_string(),
                     Err(e) => e.to_string(),
@@ -39,7 +75,5 @@ fn set_git_ver_env_var() {
             ));
             "".into()
         }
-    };
-
-    foreman::env_var("IRC_BOT_RS_GIT_VERSION", &git_ver);
+    }
 }
@@ -10,6 +10,9 @@ extern crate clap;
 extern crate log;
 
 use irc_bot::modules;
+use log::LevelFilter;
+use std::env;
+use std::process;
 
 fn main() {
     let args = clap::App::new("egbot")
@@ -19,6 +22,7 @@ fn main() {
                 .short("c")
                 .default_value("config.yaml"),
         )
+        .arg(clap::Arg::with_name("check-config").long("check-config"))
         .arg(
             clap::Arg::with_name("data-dir")
                 .long("data-dir")
@@ -34,13 +38,30 @@ fn main() {
         )
         .get_matches();
 
-    env_logger::init();
+    init_logger();
 
     let error_verbosity =
         value_t!(args, "error-verbosity", ErrorVerbosity).unwrap_or_else(|err| err.exit());
 
+    let config_path = args.value_of("config-file").expect("default missing?");
+    let config = irc_bot::Config::try_from_path(config_path);
+
+    if args.is_present("check-config") {
+        match config {
+            Ok(_) => println!("The configuration file {:?} is valid.", config_path),
+            Err(err) => {
+                match error_verbosity {
+                    ErrorVerbosity::Display => eprintln!("{}", err),
+                    ErrorVerbosity::Debug => eprintln!("{:?}", err),
+                }
+                process::exit(1)
+            }
+        }
+        return;
+    }
+
     irc_bot::run(
-        irc_bot::Config::try_from_path(args.value_of("config-file").expect("default missing?")),
+        config,
         args.value_of("data-dir").expect("default missing?"),
         move |err| {
             match error_verbosity {
@@ -53,6 +74,24 @@ fn main() {
     );
 }
 
+/// Sets up `env_logger`. If the environment variable `RUST_LOG` is not set, only errors are logged
+/// at first, as `env_logger` does by default, but the logger is left able to record messages of
+/// any level, so that the bot's `log-level` command can make the log more detailed.
+fn init_logger() {
+    let mut builder = env_logger::Builder::from_default_env();
+    let rust_log_is_set = env::var_os("RUST_LOG").is_some();
+
+    if !rust_log_is_set {
+        builder.filter_level(LevelFilter::Trace);
+    }
+
+    builder.init();
+
+    if !rust_log_is_set {
+        log::set_max_level(LevelFilter::Error);
+    }
+}
+
 arg_enum! {
     #[derive(Debug)]
     enum ErrorVerbosity {
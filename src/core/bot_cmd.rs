@@ -1,9 +1,11 @@
 use super::BotCmdHandler;
+use super::DeferredHandler;
 use super::Error;
 use super::HandlerContext;
 use super::Module;
 use super::ModuleFeatureRef;
 use super::MsgMetadata;
+use super::MsgPrefix;
 use super::Reaction;
 use super::Result;
 use super::State;
@@ -16,7 +18,10 @@ use std::borrow::Cow;
 use std::io;
 use std::num::ParseIntError;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 use util;
+use util::lock::MutexExt;
 use walkdir;
 use yaml_rust::Yaml;
 
@@ -37,10 +42,16 @@ pub struct BotCommand {
     pub(super) usage_yaml: Yaml,
 
     pub help_msg: Cow<'static, str>,
+
+    pub cooldown: Option<Duration>,
 }
 
 #[derive(Debug)]
-pub enum BotCmdAttr {}
+pub enum BotCmdAttr {
+    /// Use this attribute to keep each user (other than the bot's administrators) from invoking
+    /// the command again until the given duration has elapsed since the user last invoked it.
+    Cooldown(Duration),
+}
 
 #[derive(Debug)]
 pub enum BotCmdResult {
@@ -82,6 +93,16 @@ pub enum BotCmdResult {
     /// processing the command. The given string will be included in a reply informing the user of
     /// this.
     BotErrMsg(Cow<'static, str>),
+
+    /// The command needs more time to be processed. React immediately with the given `Reaction`,
+    /// such as a reply saying that the bot is working on the request; then run the given
+    /// [`DeferredHandler`] on a separate thread, and handle its result, when it is ready, as though
+    /// the command had returned that result in the first place.
+    ///
+    /// This is suitable for commands that do slow work, such as network requests.
+    ///
+    /// [`DeferredHandler`]: <trait.DeferredHandler.html>
+    Defer(Reaction, Box<DeferredHandler>),
 }
 
 impl From<Reaction> for BotCmdResult {
@@ -134,6 +155,13 @@ where
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum BotCmdAuthLvl {
     Public,
+
+    /// Commands at this authorization level may be used by the bot's administrators and by
+    /// operators (or users of higher status, such as channel owners) of the channel in which the
+    /// command is used. In one-to-one communication, where there is no channel, only the bot's
+    /// administrators may use such commands.
+    ChannelOp,
+
     Admin,
 }
 
@@ -144,8 +172,8 @@ pub(super) fn run(
     metadata: &MsgMetadata,
 ) -> Result<Option<BotCmdResult>> {
     let cmd_ref = match state.commands.get(cmd_name) {
-        Some(c) => c,
-        None => return Ok(None),
+        Some(c) if !state.module_is_disabled(&c.provider.name)? => c,
+        _ => return Ok(None),
     };
 
     let &BotCommand {
@@ -156,12 +184,20 @@ pub(super) fn run(
         ref usage_yaml,
         usage_str: _,
         help_msg: _,
+        cooldown,
     } = cmd_ref;
 
     let invoker_prefix = metadata.prefix;
 
     let user_authorized = match auth_lvl {
         &BotCmdAuthLvl::Public => Ok(true),
+        &BotCmdAuthLvl::ChannelOp => state.have_admin(invoker_prefix).and_then(|admin| {
+            if admin {
+                Ok(true)
+            } else {
+                state.have_channel_op(metadata.dest, invoker_prefix)
+            }
+        }),
         &BotCmdAuthLvl::Admin => state.have_admin(invoker_prefix),
     };
 
@@ -170,8 +206,25 @@ pub(super) fn run(
         Err(res) => return Ok(Some(res)),
     };
 
-    let result = match user_authorized {
-        Ok(true) => {
+    let cooldown_remaining = match (&user_authorized, cooldown) {
+        (&Ok(true), Some(cooldown)) => check_cooldown(state, cmd_ref, cooldown, invoker_prefix)?,
+        _ => None,
+    };
+
+    let result = match (user_authorized, cooldown_remaining) {
+        (Ok(true), Some(remaining)) => {
+            let remaining_secs =
+                remaining.as_secs() + if remaining.subsec_nanos() > 0 { 1 } else { 0 };
+
+            BotCmdResult::UserErrMsg(
+                format!(
+                    "Please wait {} more second(s) before using the command {:?} again.",
+                    remaining_secs, name
+                )
+                .into(),
+            )
+        }
+        (Ok(true), None) => {
             debug!(
                 "Running bot command {:?} invoked by {:?} with argument {:?}",
                 name, invoker_prefix, cmd_args
@@ -185,39 +238,121 @@ pub(super) fn run(
                 __nonexhaustive: (),
             };
 
+            *state
+                .cmd_usage
+                .lock_clean("the command usage counts")?
+                .entry(name.clone())
+                .or_insert(0) += 1;
+
+            #[cfg(feature = "metrics")]
+            state.metrics.record_cmd_run(name.clone());
+
             match util::run_handler("command", name.clone(), || handler.run(ctx, &arg)) {
                 Ok(r) => r,
                 Err(e) => BotCmdResult::LibErr(e),
             }
         }
-        Ok(false) => BotCmdResult::Unauthorized,
-        Err(e) => BotCmdResult::LibErr(e),
+        (Ok(false), _) => BotCmdResult::Unauthorized,
+        (Err(e), _) => BotCmdResult::LibErr(e),
     };
 
-    // TODO: Filter `QUIT`s in `irc_send` instead, and check `Reaction::RawMsg`s as well.
+    Ok(Some(check_quit(result, auth_lvl, name, &provider.name)))
+}
+
+/// Returns the given result of the given command, unless the result would have the bot quit but
+/// the command is not at authorization level `Admin`, in which case an error is returned instead.
+/// The result of any work that the command defers is checked likewise.
+// TODO: Filter `QUIT`s in `irc_send` instead, and check `Reaction::RawMsg`s as well.
+fn check_quit(
+    result: BotCmdResult,
+    auth_lvl: &BotCmdAuthLvl,
+    cmd_name: &str,
+    provider_name: &str,
+) -> BotCmdResult {
+    if *auth_lvl == BotCmdAuthLvl::Admin {
+        return result;
+    }
+
+    let quit_msg = match result {
+        BotCmdResult::Ok(ref r) | BotCmdResult::Defer(ref r, _) => r.quit_msg(),
+        _ => None,
+    };
+
+    if let Some(quit_msg) = quit_msg {
+        return BotCmdResult::BotErrMsg(
+            format!(
+                "Only commands at authorization level {auth_lvl_owner:?} \
+                 may tell the bot to quit, but the command {cmd_name:?} \
+                 from module {provider_name:?}, at authorization level \
+                 {cmd_auth_lvl:?}, has told the bot to quit with quit \
+                 message {quit_msg:?}.",
+                auth_lvl_owner = BotCmdAuthLvl::Admin,
+                cmd_name = cmd_name,
+                provider_name = provider_name,
+                cmd_auth_lvl = auth_lvl,
+                quit_msg = quit_msg.as_ref()
+            )
+            .into(),
+        );
+    }
+
     match result {
-        BotCmdResult::Ok(Reaction::Quit(ref s)) if *auth_lvl != BotCmdAuthLvl::Admin => {
-            Ok(Some(BotCmdResult::BotErrMsg(
-                format!(
-                    "Only commands at authorization level {auth_lvl_owner:?} \
-                     may tell the bot to quit, but the command {cmd_name:?} \
-                     from module {provider_name:?}, at authorization level \
-                     {cmd_auth_lvl:?}, has told the bot to quit with quit \
-                     message {quit_msg:?}.",
-                    auth_lvl_owner = BotCmdAuthLvl::Admin,
-                    cmd_name = name,
-                    provider_name = provider.name,
-                    cmd_auth_lvl = auth_lvl,
-                    quit_msg = s
-                )
-                .into(),
-            )))
+        BotCmdResult::Defer(ack, work) => {
+            let auth_lvl = auth_lvl.clone();
+            let cmd_name = cmd_name.to_owned();
+            let provider_name = provider_name.to_owned();
+
+            BotCmdResult::Defer(
+                ack,
+                Box::new(move |state: &State| {
+                    check_quit(work.run(state), &auth_lvl, &cmd_name, &provider_name)
+                }),
+            )
         }
-        r => Ok(Some(r)),
+        r => r,
     }
 }
 
-fn parse_arg<'s>(syntax: &'s Yaml, arg_str: &str) -> std::result::Result<Yaml, BotCmdResult> {
+/// Checks whether the given user must wait before invoking the given command again, returning how
+/// much longer the user must wait, if at all. If the user need not wait, the cooldown is restarted
+/// for the user.
+///
+/// Users are distinguished by their full message prefixes, in which nicknames are compared per
+/// IRC's case-folding rules and hostnames are compared case-insensitively. The bot's
+/// administrators are exempt from cooldowns.
+fn check_cooldown(
+    state: &State,
+    cmd: &BotCommand,
+    cooldown: Duration,
+    invoker_prefix: MsgPrefix,
+) -> Result<Option<Duration>> {
+    if state.have_admin(invoker_prefix)? {
+        return Ok(None);
+    }
+
+    let user_key = format!(
+        "{}!{}@{}",
+        util::irc::irc_to_lowercase(invoker_prefix.nick.unwrap_or("")),
+        invoker_prefix.user.unwrap_or(""),
+        invoker_prefix.host.unwrap_or("").to_ascii_lowercase()
+    );
+    let now = Instant::now();
+
+    let mut cooldowns = state.cmd_cooldowns.lock_clean("the command cooldowns")?;
+
+    if let Some(&expiry) = cooldowns.get(&(cmd.name.clone(), user_key.clone())) {
+        if expiry > now {
+            return Ok(Some(expiry - now));
+        }
+    }
+
+    cooldowns.retain(|_, &mut expiry| expiry > now);
+    cooldowns.insert((cmd.name.clone(), user_key), now + cooldown);
+
+    Ok(None)
+}
+
+fn parse_arg(syntax: &Yaml, arg_str: &str) -> std::result::Result<Yaml, BotCmdResult> {
     use util::yaml as uy;
 
     match uy::parse_and_check_node(arg_str, syntax, "<argument>", || {
@@ -232,6 +367,8 @@ fn parse_arg<'s>(syntax: &'s Yaml, arg_str: &str) -> std::result::Result<Yaml, B
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::test_harness::TestBot;
+    use modules;
     use util::yaml::mk_str as s;
 
     fn pa(syntax_str: &str, arg_str: &str) -> std::result::Result<Yaml, String> {
@@ -310,4 +447,89 @@ mod tests {
             Ok(map(&[(s("k"), map(&[(s("j"), Yaml::Integer(123))]))]))
         );
     }
+
+    #[test]
+    fn parse_arg_aliases() {
+        assert_eq!(
+            pa("{k: [a], j: [a]}", "{k: &x [b], j: *x}"),
+            Ok(map(&[(s("k"), seq(&[s("b")])), (s("j"), seq(&[s("b")]))]))
+        );
+        assert_eq!(
+            pa("{k: {i: v}, j: ...}", "{k: &x {i: y}, j: [*x]}"),
+            Ok(map(&[
+                (s("k"), map(&[(s("i"), s("y"))])),
+                (s("j"), seq(&[map(&[(s("i"), s("y"))])]))
+            ]))
+        );
+        assert!(pa("{k: ...}", "k: *x").is_err());
+
+        match parse_arg(
+            &util::yaml::parse_node("{k: ...}").unwrap().unwrap(),
+            "k: &x [a, *x]",
+        ) {
+            Err(BotCmdResult::LibErr(e)) => assert!(
+                e.to_string().contains("<argument>.k.1 is invalid"),
+                "unexpected error: {}",
+                e
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_arg_error_messages() {
+        let err_msg = |syntax_str: &str, arg_str: &str| match parse_arg(
+            &util::yaml::parse_node(syntax_str).unwrap().unwrap(),
+            arg_str,
+        ) {
+            Err(BotCmdResult::LibErr(e)) => e.to_string(),
+            other => panic!("unexpected result: {:?}", other),
+        };
+
+        assert_eq!(
+            err_msg("{k: v}", "k: {j: [a, {i: b}]}"),
+            "While handling YAML: Expected <argument>.k to be of type Scalar, but it is of type \
+             Mapping:\n\
+             j:\n  \
+             - a\n  \
+             -\n    \
+             i: b"
+        );
+        assert_eq!(
+            err_msg("{k: {j: v}}", "k: {i: [a]}"),
+            "While handling YAML: An object is missing the required field \"j\":\n\
+             i:\n  \
+             - a"
+        );
+    }
+
+    #[test]
+    fn cooldowns() {
+        let bot = TestBot::new(
+            "{nickname: test-bot, admins: [{nick: boss}], servers: [{name: test, host: \
+             irc.example.net, port: 6697}]}",
+            vec![modules::default(), modules::quote()],
+        );
+
+        // The test bot has no connection from which to list the channel's users, so `quote`
+        // itself fails, but its cooldown applies all the same.
+        let told_to_wait = |prefix: &str| {
+            let output = bot.feed(&format!(":{} PRIVMSG #test :test-bot: quote", prefix));
+            assert_eq!(output.len(), 1);
+            output[0]
+                == "PRIVMSG #test :User error: Please wait 5 more second(s) before using the \
+                    command \"quote\" again."
+        };
+
+        assert!(!told_to_wait("grace[m]!grace@example.org"));
+        assert!(told_to_wait("grace[m]!grace@example.org"));
+        assert!(told_to_wait("GRACE{M}!grace@EXAMPLE.ORG"));
+
+        // Other users aren't held up by Grace's cooldown.
+        assert!(!told_to_wait("heidi!heidi@example.org"));
+
+        // Administrators have no cooldowns.
+        assert!(!told_to_wait("boss!boss@example.org"));
+        assert!(!told_to_wait("boss!boss@example.org"));
+    }
 }
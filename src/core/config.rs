@@ -3,20 +3,38 @@ use super::pkg_info;
 use super::ErrorKind;
 use super::Result;
 use super::ServerConfigIndex;
+use serde::Serializer;
+use serde_json;
 use serde_yaml;
 use smallvec::SmallVec;
 use std::convert::TryInto;
+use std::env;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::iter;
 use std::path::Path;
+use std::path::PathBuf;
+use std::result::Result as StdResult;
 use std::sync::Arc;
 use std::time::Duration;
 use util::irc::ChannelName;
+use util::lock::ReadLockExt;
 use util::lock::RoLock;
 use util::regex::config as rx_cfg;
 use util::regex::Regex;
 
+/// The default number of seconds between requests for the bot's message prefix
+const DEFAULT_PREFIX_UPDATE_INTERVAL_SECS: u16 = 30 * 60;
+
+/// The least nonzero number of seconds allowed between requests for the bot's message prefix, so
+/// that the bot doesn't spam the server
+const MIN_PREFIX_UPDATE_INTERVAL_SECS: u16 = 60;
+
+/// The default greatest number of IRC messages that the bot sends in response to a single reaction
+const DEFAULT_MAX_MSGS_PER_REACTION: u16 = 30;
+
 mod inner {
     use smallvec::SmallVec;
 
@@ -27,6 +45,9 @@ mod inner {
     pub(super) struct Config {
         pub(super) nickname: String,
 
+        #[serde(default, rename = "alt nicks")]
+        pub(super) alt_nicks: Option<Vec<String>>,
+
         #[serde(default)]
         pub(super) username: String,
 
@@ -36,6 +57,39 @@ mod inner {
         #[serde(default, rename = "join delay")]
         pub(super) join_delay: u16,
 
+        #[serde(default, rename = "command prefix")]
+        pub(super) command_prefix: Option<String>,
+
+        #[serde(default, rename = "require command prefix in private messages")]
+        pub(super) require_cmd_prefix_in_pms: bool,
+
+        #[serde(default, rename = "addressee suffix")]
+        pub(super) addressee_suffix: Option<String>,
+
+        #[serde(default, rename = "accept invites")]
+        pub(super) accept_invites: bool,
+
+        #[serde(default, rename = "accept invites only from admins")]
+        pub(super) accept_invites_only_from_admins: bool,
+
+        #[serde(default = "super::mk_true", rename = "learn prefix by self-message")]
+        pub(super) learn_prefix_by_self_msg: bool,
+
+        #[serde(
+            default = "super::mk_prefix_update_interval",
+            rename = "prefix update interval"
+        )]
+        pub(super) prefix_update_interval: u16,
+
+        #[serde(
+            default = "super::mk_max_msgs_per_reaction",
+            rename = "max messages per reaction"
+        )]
+        pub(super) max_msgs_per_reaction: u16,
+
+        #[serde(default, rename = "metrics address")]
+        pub(super) metrics_address: Option<String>,
+
         // TODO: admins should be per-server.
         #[serde(default)]
         pub(super) admins: SmallVec<[super::Admin; 8]>,
@@ -52,7 +106,22 @@ mod inner {
 /// # Configuring a bot in YAML
 ///
 /// To configure the bot using a [YAML] configuration file, create such a file and then use
-/// [`Config::try_from_path`] to read and parse it into a [`Config`] structure.
+/// [`Config::try_from_path`] to read and parse it into a [`Config`] structure. The configuration
+/// file instead may be written in [JSON], in which case its name should end in `.json`; the
+/// structure of the configuration is the same in either format.
+///
+/// Any string value in the configuration file may refer to environment variables, written as
+/// `${NAME}`, which will be replaced with the variables' values when the file is read, e.g., so
+/// that passwords need not be stored in the file itself. It is an error to refer to an environment
+/// variable that is not set. To write a literal `${`, write `$${`.
+///
+/// A configuration may be split across multiple files with the top-level field `include`, whose
+/// value should be a sequence of paths of other configuration files, in YAML or JSON, relative to
+/// the directory of the file that lists them. The included files are merged in the order in which
+/// they are listed, and then the including file is merged over them, such that a field given in a
+/// later file overrides the same field given in an earlier one. Mappings are merged field by field;
+/// other values, including sequences such as `servers`, are replaced whole. Included files may
+/// include other files, but a file may not include itself, directly or indirectly.
 ///
 /// The text of the configuration file should constitute a YAML mapping with the key-value pairs
 /// (hereinafter termed _fields_) that follow, listed by their keys:
@@ -60,6 +129,11 @@ mod inner {
 /// - `nickname` — The value of this field should be a string, which is to be used as the bot's
 /// default IRC nickname.
 ///
+/// - `alt nicks` — The value of this field, if specified, should be a sequence of strings, which
+/// are to be tried in order as the bot's IRC nickname if the nickname given in `nickname` is
+/// unavailable. This field is optional; its value defaults to a sequence of the `nickname` with
+/// one and with two underscores (`_`) appended. This can be overridden per-server (see below).
+///
 /// - `username` — The value of this field, if specified, should be a string, which is to be used
 /// as the bot's IRC username (which has little effect in most cases). This field is optional; its
 /// value defaults to the given `nickname`.
@@ -75,6 +149,66 @@ mod inner {
 /// field is optional; its value defaults to zero seconds. TODO: This should be overridable
 /// per-server, or even per-channel.
 ///
+/// - `command prefix` — The value of this field, if specified, should be a non-empty string
+/// without whitespace, such as `"!"`. A message sent to a channel that begins with this string
+/// will be treated as a bot command, as if it had been addressed to the bot by nickname; e.g.,
+/// with a `command prefix` of `"!"`, the message `!ping` will be treated as the bot command
+/// `ping`. Addressing the bot by nickname continues to work regardless. This field is optional;
+/// by default, bot commands must be addressed to the bot by nickname.
+///
+/// - `require command prefix in private messages` — The value of this field, if specified, should
+/// be `true` or `false`, specifying whether private messages to the bot, like channel messages,
+/// must begin with the `command prefix` (or be addressed to the bot by nickname) to be treated as
+/// bot commands. If `false`, every private message to the bot is treated as a bot command, without
+/// any prefix. If `true`, `command prefix` must be specified. This field is optional; its value
+/// defaults to `false`.
+///
+/// - `addressee suffix` — The value of this field, if specified, should be a string, which is to
+/// be placed between the nickname of the user to whom the bot is replying and the text of the
+/// reply; e.g., with an `addressee suffix` of `", "`, the bot's replies will read like `nick,
+/// message`. The string may be empty, but it may not contain line breaks or NUL characters. This
+/// field is optional; its value defaults to `": "`.
+///
+/// - `accept invites` — The value of this field, if specified, should be `true` or `false`,
+/// specifying whether the bot should join channels to which it is invited with the IRC command
+/// `INVITE`. To prevent invitation loops, an invitation to a channel that the bot already is in, or
+/// that the bot joined in response to an invitation within the last minute, is ignored. This field
+/// is optional; its value defaults to `false`.
+///
+/// - `accept invites only from admins` — The value of this field, if specified, should be `true`
+/// or `false`, specifying whether the bot should accept invitations only from its administrators,
+/// if `accept invites` is `true`. This field is optional; its value defaults to `false`.
+///
+/// - `learn prefix by self-message` — The value of this field, if specified, should be `true` or
+/// `false`, specifying how the bot should learn the message prefix (its nickname, username, and
+/// hostname) that each server prepends to the messages that it sends, which the bot needs to know
+/// in order to split long messages correctly. If `true`, the bot sends a message to itself upon
+/// connecting and reads the prefix from that message when the server relays it back. If `false`,
+/// the bot instead sends a `WHOIS` query about itself, which avoids the self-message on networks
+/// where it is visible or filtered. This field is optional; its value defaults to `true`.
+///
+/// - `prefix update interval` — The value of this field, if specified, should be a non-negative
+/// integer, which is to be used as a number of seconds to wait between repetitions of the request
+/// described under `learn prefix by self-message`, so that the bot notices if its message prefix
+/// changes while it is connected, e.g., when a hostname cloak is applied after it identifies to
+/// services. The value must be zero, which disables the repetition, or at least 60. This field is
+/// optional; its value defaults to 1800 seconds (30 minutes).
+///
+/// - `max messages per reaction` — The value of this field, if specified, should be a positive
+/// integer, which is to be used as the greatest number of IRC messages that the bot should send
+/// in response to a single reaction returned by a module, counting each line of a reply that is
+/// split across several lines. If a reaction would produce more messages than this, the excess
+/// messages are discarded, and the last message sent is replaced with the text `(output
+/// truncated)`, so that a misbehaving module cannot flood a channel or have the bot disconnected
+/// for flooding. This field is optional; its value defaults to 30.
+///
+/// - `metrics address` — The value of this field, if specified, should be a string giving a
+/// network address and port, such as `"127.0.0.1:9184"`, at which the bot should serve counters
+/// of its activity (messages received and sent, bot commands run, errors, and which servers it is
+/// connected to) over HTTP, at the path `/metrics`, in the text format read by the Prometheus
+/// monitoring system. This field has effect only if the bot was built with the Cargo feature
+/// `metrics`. This field is optional; by default, no metrics are served.
+///
 /// - `servers` — The value of this field should be a sequence of mappings, which specify IRC
 /// servers to which the bot should attempt to connect. The fields of these mappings are termed
 /// _per-server settings_ and are documented below.
@@ -103,6 +237,10 @@ mod inner {
 ///   - `port` — The value of this field should be a non-negative integer specifying the number of
 ///   the TCP port at which the server serves IRC, such as `6697`.
 ///
+///   - `alt nicks` — The value of this field, if specified, should be a sequence of strings, which
+///   is to be used instead of the value of the top-level `alt nicks` field for this server. This
+///   field is optional.
+///
 ///   - `nick password` — The value of this field, if specified, should be a string specifying a
 ///   password to be used to verify that the bot is authorized to use the nickname that has been
 ///   specified, e.g., a NickServ password. This field is optional.
@@ -111,6 +249,13 @@ mod inner {
 ///   password to be used to verify that the bot is authorized to connect to the server, i.e., a
 ///   password to be sent with the IRC protocol command `PASS` at the start of the IRC session.
 ///
+///   - `SASL` — The value of this field, if specified, should be a mapping with the fields
+///   `username` and `password`, whose values should be strings specifying credentials with which
+///   the bot should authenticate to the server via the Simple Authentication and Security Layer
+///   (SASL) during connection registration, and optionally the field `mechanism`, specifying the
+///   SASL mechanism to be used, of which only `PLAIN` (the default) is currently supported. This
+///   field is optional.
+///
 ///   - `TLS` — The value of this field, if specified, should be `true` or `false`, specifying
 ///   whether the bot should attempt to connect to the server using Transport Layer Security (TLS).
 ///   This field is optional; its value defaults to `true`.
@@ -206,44 +351,100 @@ mod inner {
 ///     setting with the key `can see`. All channels whose identifiers match this regular
 ///     expression will be able to see the channel `C`.
 ///
+///     - `greeting` — The value of this per-channel setting, if specified, should be a string,
+///     which the bot will send as a message to the channel `C` each time it joins `C`, e.g., to
+///     introduce itself or to explain how to use it. This field is optional.
 ///
+///     The settings `can see` and `seen by` can be changed while the bot is running, by editing
+///     the configuration file and then using the bot command `config-reload`, which is provided
+///     by the default module. Other settings take effect only when the bot is started.
+///
+/// # Writing a configuration
+///
+/// A [`Config`] can be serialized with Serde, or converted to a YAML string with
+/// [`Config::to_yaml_string`], producing a configuration in the format described above that can
+/// be read back into an equivalent [`Config`]. Default values that were filled in when the
+/// configuration was read are written out explicitly.
+///
+/// [JSON]: <https://www.json.org/>
 /// [YAML]: <https://en.wikipedia.org/wiki/YAML>
+/// [`Config::to_yaml_string`]: <struct.Config.html#method.to_yaml_string>
 /// [`Config::try_from_path`]: <struct.Config.html#method.try_from_path>
 /// [`Config`]: <struct.Config.html>
 /// [`Duration`]: <https://doc.rust-lang.org/std/time/struct.Duration.html>
 /// [`regex` flag]: <https://docs.rs/regex/*/regex/#grouping-and-flags>
 /// [`regex` syntax]: <https://docs.rs/regex/*/regex/#syntax>
 /// [`regex`]: <https://docs.rs/regex/*/regex/>
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Config {
     pub(super) nickname: String,
 
+    #[serde(rename = "alt nicks")]
+    pub(super) alt_nicks: Vec<String>,
+
     pub(super) username: String,
 
     pub(super) realname: String,
 
+    #[serde(rename = "join delay", serialize_with = "serialize_duration_as_secs")]
+    pub(super) join_delay: Duration,
+
+    #[serde(rename = "command prefix", skip_serializing_if = "Option::is_none")]
+    pub(super) command_prefix: Option<String>,
+
+    #[serde(rename = "require command prefix in private messages")]
+    pub(super) require_cmd_prefix_in_pms: bool,
+
+    #[serde(rename = "addressee suffix", skip_serializing_if = "Option::is_none")]
+    pub(super) addressee_suffix: Option<String>,
+
+    #[serde(rename = "accept invites")]
+    pub(super) accept_invites: bool,
+
+    #[serde(rename = "accept invites only from admins")]
+    pub(super) accept_invites_only_from_admins: bool,
+
+    #[serde(rename = "learn prefix by self-message")]
+    pub(super) learn_prefix_by_self_msg: bool,
+
+    #[serde(
+        rename = "prefix update interval",
+        serialize_with = "serialize_duration_as_secs"
+    )]
+    pub(super) prefix_update_interval: Duration,
+
+    #[serde(rename = "max messages per reaction")]
+    pub(super) max_msgs_per_reaction: u16,
+
+    #[serde(rename = "metrics address", skip_serializing_if = "Option::is_none")]
+    pub(super) metrics_address: Option<String>,
+
     pub(super) admins: SmallVec<[Admin; 8]>,
 
     pub(super) servers: SmallVec<[Server; 8]>,
 
+    #[serde(skip)]
     pub(super) aatxe_configs: SmallVec<[(ServerConfigIndex, Arc<aatxe::Config>); 8]>,
 
-    pub(super) join_delay: Duration,
+    /// The path of the file from which the configuration was read, if any, from which it can be
+    /// reloaded
+    #[serde(skip)]
+    pub(super) source_path: Option<PathBuf>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub(super) struct Admin {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub nick: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub host: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(super) struct Server {
     // TODO: Use a `ServerName` newtype that checks that the string is a valid identifier.
     pub name: String,
@@ -252,10 +453,13 @@ pub(super) struct Server {
 
     pub port: u16,
 
-    #[serde(rename = "nick password")]
+    #[serde(default, rename = "alt nicks", skip_serializing_if = "Option::is_none")]
+    pub(super) alt_nicks: Option<Vec<String>>,
+
+    #[serde(rename = "nick password", skip_serializing_if = "Option::is_none")]
     pub(super) nick_password: Option<String>,
 
-    #[serde(rename = "server password")]
+    #[serde(rename = "server password", skip_serializing_if = "Option::is_none")]
     pub(super) server_password: Option<String>,
 
     #[serde(default = "mk_true", rename = "TLS")]
@@ -264,19 +468,55 @@ pub(super) struct Server {
     #[serde(default)]
     pub channels: SmallVec<[Channel; 24]>,
 
-    #[serde(default, rename = "await registration mode")]
+    #[serde(
+        default,
+        rename = "await registration mode",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub(super) await_registration_mode: Option<char>,
+
+    #[serde(default, rename = "SASL", skip_serializing_if = "Option::is_none")]
+    pub(super) sasl: Option<Sasl>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct Sasl {
+    #[serde(default = "mk_sasl_plain")]
+    pub(super) mechanism: SaslMechanism,
+
+    pub(super) username: String,
+
+    pub(super) password: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) enum SaslMechanism {
+    #[serde(rename = "PLAIN")]
+    Plain,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub(super) struct Channel {
     pub name: ChannelName,
 
-    #[serde(rename = "can see")]
-    pub can_see: Option<RoLock<Regex<rx_cfg::Anchored>>>,
+    #[serde(default, rename = "can see", skip_serializing_if = "regex_is_unset")]
+    pub can_see: RoLock<Option<Regex<rx_cfg::Anchored>>>,
+
+    #[serde(default, rename = "seen by", skip_serializing_if = "regex_is_unset")]
+    pub seen_by: RoLock<Option<Regex<rx_cfg::Anchored>>>,
 
-    #[serde(rename = "seen by")]
-    pub seen_by: Option<RoLock<Regex<rx_cfg::Anchored>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub greeting: Option<String>,
+}
+
+/// The formats in which a configuration can be written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigFormat {
+    /// [JSON](https://www.json.org/)
+    Json,
+
+    /// [YAML](http://yaml.org/), the default
+    Yaml,
 }
 
 #[derive(Debug)]
@@ -290,16 +530,76 @@ impl Config {
         input.into_config()
     }
 
+    /// Reads and parses a configuration file.
+    ///
+    /// The file is parsed as JSON if its name ends in `.json`, and as YAML otherwise.
     pub fn try_from_path<P>(path: P) -> Result<Config>
     where
         P: AsRef<Path>,
     {
-        Self::try_from(File::open(path)?)
+        let path = path.as_ref();
+
+        let mut text = String::new();
+        BufReader::new(File::open(path)?).read_to_string(&mut text)?;
+
+        let mut config = read_config(&text, format_of_path(path), Some(path))?;
+        config.source_path = Some(path.to_owned());
+        Ok(config)
+    }
+
+    /// Parses a configuration from a string in the given format.
+    ///
+    /// `Config::try_from` also can parse a configuration from a string, but it assumes that the
+    /// string is YAML.
+    pub fn try_from_str_with_format(input: &str, format: ConfigFormat) -> Result<Config> {
+        read_config(input, format, None)
     }
 
     pub fn build() -> ConfigBuilder {
         ConfigBuilder(Ok(Default::default()))
     }
+
+    /// Updates the per-channel settings `can see` and `seen by` of each channel in this
+    /// configuration to match those of the same channel on the same server in `new`, returning
+    /// the number of channels whose settings changed.
+    ///
+    /// Channels that are not listed in this configuration are ignored, as are all other settings,
+    /// since those take effect only when the bot starts.
+    pub(super) fn update_channel_visibility(&self, new: &Config) -> Result<usize> {
+        let mut changed = 0;
+
+        for server in &self.servers {
+            let new_server = match new.servers.iter().find(|s| s.name == server.name) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            for channel in &server.channels {
+                let new_channel = match new_server.channels.iter().find(|c| c.name == channel.name)
+                {
+                    Some(c) => c,
+                    None => continue,
+                };
+
+                let can_see_changed =
+                    update_regex(&channel.can_see, &new_channel.can_see, "a `can see` regex")?;
+                let seen_by_changed =
+                    update_regex(&channel.seen_by, &new_channel.seen_by, "a `seen by` regex")?;
+
+                if can_see_changed || seen_by_changed {
+                    changed += 1;
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Writes the configuration as a YAML document, which can be parsed back into an equivalent
+    /// `Config`, e.g., with `Config::try_from`.
+    pub fn to_yaml_string(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(Into::into)
+    }
 }
 
 impl ConfigBuilder {
@@ -318,6 +618,17 @@ impl ConfigBuilder {
         ConfigBuilder(self.0.map(|cfg| inner::Config { nickname, ..cfg }))
     }
 
+    pub fn alt_nicks<I, S>(self, alt_nicks: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ConfigBuilder(self.0.map(|cfg| inner::Config {
+            alt_nicks: Some(alt_nicks.into_iter().map(Into::into).collect()),
+            ..cfg
+        }))
+    }
+
     pub fn username<S>(self, username: S) -> Self
     where
         S: Into<String>,
@@ -337,6 +648,47 @@ impl ConfigBuilder {
             ..cfg
         }))
     }
+
+    pub fn addressee_suffix<S>(self, addressee_suffix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        ConfigBuilder(self.0.map(|cfg| inner::Config {
+            addressee_suffix: Some(addressee_suffix.into()),
+            ..cfg
+        }))
+    }
+
+    pub fn accept_invites(self, accept_invites: bool) -> Self {
+        ConfigBuilder(self.0.map(|cfg| inner::Config {
+            accept_invites,
+            ..cfg
+        }))
+    }
+
+    pub fn accept_invites_only_from_admins(self, accept_invites_only_from_admins: bool) -> Self {
+        ConfigBuilder(self.0.map(|cfg| inner::Config {
+            accept_invites_only_from_admins,
+            ..cfg
+        }))
+    }
+
+    pub fn command_prefix<S>(self, command_prefix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        ConfigBuilder(self.0.map(|cfg| inner::Config {
+            command_prefix: Some(command_prefix.into()),
+            ..cfg
+        }))
+    }
+
+    pub fn require_cmd_prefix_in_pms(self, require_cmd_prefix_in_pms: bool) -> Self {
+        ConfigBuilder(self.0.map(|cfg| inner::Config {
+            require_cmd_prefix_in_pms,
+            ..cfg
+        }))
+    }
 }
 
 // TODO: Switch to `TryFrom` once rustc 1.18 is stable.
@@ -362,15 +714,15 @@ impl IntoConfig for ConfigBuilder {
     }
 }
 
-impl<'a> IntoConfig for &'a str {
+impl IntoConfig for &str {
     fn into_config(self) -> Result<Config> {
-        read_config(self)
+        read_config(self, ConfigFormat::Yaml, None)
     }
 }
 
 impl IntoConfig for String {
     fn into_config(self) -> Result<Config> {
-        read_config(&self)
+        read_config(&self, ConfigFormat::Yaml, None)
     }
 }
 
@@ -391,12 +743,258 @@ impl IntoConfig for File {
     }
 }
 
-fn read_config(input: &str) -> Result<Config> {
-    serde_yaml::from_str(input)
+/// Parses a configuration from a string in the given format, which was read from the file at
+/// `source_path`, if any, relative to whose directory the paths of included files are resolved.
+fn read_config(input: &str, format: ConfigFormat, source_path: Option<&Path>) -> Result<Config> {
+    let mut including = Vec::new();
+
+    if let Some(path) = source_path {
+        including.push(path.canonicalize()?);
+    }
+
+    let base_dir = source_path
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new(""));
+
+    let value = read_config_value(input, format, base_dir, &mut including)?;
+
+    check_channel_names(&value)?;
+
+    serde_yaml::from_value(value)
         .map_err(Into::into)
         .and_then(cook_config)
 }
 
+/// Returns the format in which the configuration file at the given path is presumed to be written.
+fn format_of_path(path: &Path) -> ConfigFormat {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+        _ => ConfigFormat::Yaml,
+    }
+}
+
+/// Parses a configuration document into a YAML node, expanding references to environment variables
+/// and merging in the files listed in its `include` field, if any, whose paths are resolved
+/// relative to `base_dir`.
+///
+/// `including` holds the canonical paths of the files that are being read, outermost first, so
+/// that a file that includes itself, directly or indirectly, can be detected.
+fn read_config_value(
+    input: &str,
+    format: ConfigFormat,
+    base_dir: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<serde_yaml::Value> {
+    let mut value = match format {
+        ConfigFormat::Json => serde_json::from_str(input)?,
+        ConfigFormat::Yaml => serde_yaml::from_str(input)?,
+    };
+
+    expand_env_vars(&mut value, "<top level>")?;
+
+    let include = match value {
+        serde_yaml::Value::Mapping(ref mut map) => map.remove(&"include".into()),
+        _ => None,
+    };
+
+    let include_paths = match include {
+        Some(serde_yaml::Value::Sequence(seq)) => seq,
+        Some(_) => bail!(ErrorKind::Config(
+            "include".into(),
+            "is not a sequence of file paths".into(),
+        )),
+        None => return Ok(value),
+    };
+
+    let mut merged = serde_yaml::Value::Mapping(Default::default());
+
+    for include_path in include_paths {
+        let path = match include_path.as_str() {
+            Some(s) => base_dir.join(s),
+            None => bail!(ErrorKind::Config(
+                "include".into(),
+                format!("contains a non-string entry: {:?}", include_path),
+            )),
+        };
+
+        let unreadable = |e: io::Error| {
+            ErrorKind::Config(
+                "include".into(),
+                format!("lists {:?}, which could not be read: {}", path, e),
+            )
+        };
+
+        let canonical_path = path.canonicalize().map_err(&unreadable)?;
+
+        ensure!(
+            !including.contains(&canonical_path),
+            ErrorKind::Config(
+                "include".into(),
+                format!(
+                    "lists {:?}, which includes itself, directly or indirectly",
+                    path
+                ),
+            )
+        );
+
+        let mut text = String::new();
+        File::open(&canonical_path)
+            .and_then(|file| BufReader::new(file).read_to_string(&mut text))
+            .map_err(&unreadable)?;
+
+        including.push(canonical_path);
+
+        let included = read_config_value(
+            &text,
+            format_of_path(&path),
+            path.parent().unwrap_or_else(|| Path::new("")),
+            including,
+        )?;
+
+        including.pop();
+
+        merge_yaml(&mut merged, included);
+    }
+
+    merge_yaml(&mut merged, value);
+
+    Ok(merged)
+}
+
+/// Merges `overlay` into `base`. Mappings are merged key by key, recursively; any other value in
+/// `overlay`, including a sequence, replaces the corresponding value in `base`.
+fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (
+            &mut serde_yaml::Value::Mapping(ref mut base_map),
+            serde_yaml::Value::Mapping(overlay_map),
+        ) => {
+            for (key, value) in overlay_map {
+                if let Some(base_value) = base_map.get_mut(&key) {
+                    merge_yaml(base_value, value);
+                    continue;
+                }
+
+                base_map.insert(key, value);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Replaces each reference to an environment variable, written as `${NAME}`, in each string value
+/// in the given YAML node with the value of that environment variable. `$${` is replaced with a
+/// literal `${`. Mapping keys are left as they are.
+///
+/// `key` is the mapping key under which the node was found, for use in error messages.
+fn expand_env_vars(value: &mut serde_yaml::Value, key: &str) -> Result<()> {
+    match *value {
+        serde_yaml::Value::String(ref mut s) if s.contains('$') => {
+            *s = expand_env_vars_in_str(s)
+                .map_err(|problem| ErrorKind::Config(key.to_owned(), problem))?;
+        }
+        serde_yaml::Value::Sequence(ref mut seq) => {
+            for item in seq {
+                expand_env_vars(item, key)?;
+            }
+        }
+        serde_yaml::Value::Mapping(ref mut map) => {
+            for (k, v) in map.iter_mut() {
+                expand_env_vars(v, k.as_str().unwrap_or(key))?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Checks that the name of each channel listed in the given configuration is a valid IRC channel
+/// name, so that an invalid name can be reported along with the server and the position in that
+/// server's channel list at which it was found, which Serde's own error would not mention.
+///
+/// Names that are not strings at all are left for Serde to report.
+fn check_channel_names(cfg: &serde_yaml::Value) -> Result<()> {
+    let servers = match cfg.get("servers").and_then(serde_yaml::Value::as_sequence) {
+        Some(servers) => servers,
+        None => return Ok(()),
+    };
+
+    for server in servers {
+        let channels = match server
+            .get("channels")
+            .and_then(serde_yaml::Value::as_sequence)
+        {
+            Some(channels) => channels,
+            None => continue,
+        };
+
+        for (idx, channel) in channels.iter().enumerate() {
+            let name = match channel.get("name").and_then(serde_yaml::Value::as_str) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            if let Err(e) = ChannelName::new(name) {
+                let server_name = server
+                    .get("name")
+                    .and_then(serde_yaml::Value::as_str)
+                    .unwrap_or("<unnamed>");
+
+                bail!(ErrorKind::Config(
+                    "channels".into(),
+                    format!(
+                        "of server {:?} has an invalid name at index {}: {}",
+                        server_name, idx, e
+                    ),
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn expand_env_vars_in_str(input: &str) -> StdResult<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(idx) = rest.find('$') {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if rest.starts_with("$${") {
+            output.push_str("${");
+            rest = &rest["$${".len()..];
+        } else if rest.starts_with("${") {
+            let end = match rest.find('}') {
+                Some(end) => end,
+                None => return Err(format!("has an unterminated `${{` in {:?}", input)),
+            };
+            let name = &rest["${".len()..end];
+
+            match env::var(name) {
+                Ok(val) => output.push_str(&val),
+                Err(e) => {
+                    return Err(format!(
+                        "refers to the environment variable {:?}, which could not be read: {}",
+                        name, e
+                    ))
+                }
+            }
+
+            rest = &rest[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
+}
+
 fn cook_config(mut cfg: inner::Config) -> Result<Config> {
     validate_config(&cfg)?;
 
@@ -404,15 +1002,27 @@ fn cook_config(mut cfg: inner::Config) -> Result<Config> {
 
     let inner::Config {
         nickname,
+        alt_nicks,
         username,
         realname,
         admins,
         servers,
         join_delay,
+        command_prefix,
+        require_cmd_prefix_in_pms,
+        addressee_suffix,
+        accept_invites,
+        accept_invites_only_from_admins,
+        learn_prefix_by_self_msg,
+        prefix_update_interval,
+        max_msgs_per_reaction,
+        metrics_address,
     } = cfg;
 
     let join_delay = Duration::from_secs(join_delay.into());
 
+    let prefix_update_interval = Duration::from_secs(prefix_update_interval.into());
+
     let aatxe_configs = servers
         .iter()
         .enumerate()
@@ -422,10 +1032,12 @@ fn cook_config(mut cfg: inner::Config) -> Result<Config> {
                 ref host,
                 port,
                 tls,
+                alt_nicks: ref server_alt_nicks,
                 ref nick_password,
                 ref server_password,
                 channels: _,
                 await_registration_mode: _,
+                sasl: _,
             } = server_cfg;
 
             let server_cfg_idx = i.try_into()?;
@@ -433,6 +1045,7 @@ fn cook_config(mut cfg: inner::Config) -> Result<Config> {
             let aatxe_config = Arc::new(aatxe::Config {
                 // TODO: Allow nickname etc. to be configured per-server.
                 nickname: Some(nickname.clone()),
+                alt_nicks: server_alt_nicks.clone().or_else(|| alt_nicks.clone()),
                 nick_password: nick_password.clone(),
                 password: server_password.clone(),
                 username: Some(username.clone()),
@@ -449,21 +1062,94 @@ fn cook_config(mut cfg: inner::Config) -> Result<Config> {
 
     Ok(Config {
         nickname,
+        alt_nicks: alt_nicks.unwrap_or_default(),
         username,
         realname,
         admins,
         servers,
         aatxe_configs,
         join_delay,
+        command_prefix,
+        require_cmd_prefix_in_pms,
+        addressee_suffix,
+        accept_invites,
+        accept_invites_only_from_admins,
+        learn_prefix_by_self_msg,
+        prefix_update_interval,
+        max_msgs_per_reaction,
+        metrics_address,
+        source_path: None,
     })
 }
 
+/// Returns alternate nicknames to be tried, in order, if the given nickname is unavailable.
+fn default_alt_nicks(nickname: &str) -> Vec<String> {
+    vec![format!("{}_", nickname), format!("{}__", nickname)]
+}
+
 fn validate_config(cfg: &inner::Config) -> Result<()> {
     ensure!(
         !cfg.nickname.is_empty(),
         ErrorKind::Config("nickname".into(), "is empty".into())
     );
 
+    for alt_nicks in iter::once(&cfg.alt_nicks).chain(cfg.servers.iter().map(|s| &s.alt_nicks)) {
+        for alt_nick in alt_nicks.iter().flatten() {
+            ensure!(
+                !alt_nick.is_empty() && !alt_nick.contains(char::is_whitespace),
+                ErrorKind::Config(
+                    "alt nicks".into(),
+                    format!("contains an invalid nickname: {:?}", alt_nick),
+                )
+            );
+        }
+    }
+
+    if let Some(ref prefix) = cfg.command_prefix {
+        ensure!(
+            !prefix.is_empty() && !prefix.contains(char::is_whitespace),
+            ErrorKind::Config(
+                "command prefix".into(),
+                "is empty or contains whitespace".into(),
+            )
+        );
+    }
+
+    ensure!(
+        !cfg.require_cmd_prefix_in_pms || cfg.command_prefix.is_some(),
+        ErrorKind::Config(
+            "require command prefix in private messages".into(),
+            "is true, but no `command prefix` is specified".into(),
+        )
+    );
+
+    if let Some(ref suffix) = cfg.addressee_suffix {
+        ensure!(
+            !suffix.contains(&['\r', '\n', '\0'][..]),
+            ErrorKind::Config(
+                "addressee suffix".into(),
+                "contains a line break or NUL character".into(),
+            )
+        );
+    }
+
+    ensure!(
+        cfg.prefix_update_interval == 0
+            || cfg.prefix_update_interval >= MIN_PREFIX_UPDATE_INTERVAL_SECS,
+        ErrorKind::Config(
+            "prefix update interval".into(),
+            format!(
+                "is neither zero nor at least {} seconds",
+                MIN_PREFIX_UPDATE_INTERVAL_SECS
+            ),
+        )
+    );
+
+    ensure!(
+        cfg.max_msgs_per_reaction > 0,
+        ErrorKind::Config("max messages per reaction".into(), "is zero".into())
+    );
+
     ensure!(
         !cfg.servers.is_empty(),
         ErrorKind::Config("servers".into(), "is empty".into())
@@ -481,6 +1167,10 @@ fn validate_config(cfg: &inner::Config) -> Result<()> {
 }
 
 fn fill_in_config_defaults(cfg: &mut inner::Config) -> Result<()> {
+    if cfg.alt_nicks.is_none() {
+        cfg.alt_nicks = Some(default_alt_nicks(&cfg.nickname));
+    }
+
     if cfg.username.is_empty() {
         cfg.username = cfg.nickname.clone();
     }
@@ -492,6 +1182,448 @@ fn fill_in_config_defaults(cfg: &mut inner::Config) -> Result<()> {
     Ok(())
 }
 
+/// Replaces the regex in `old` with the one in `new`, returning whether they differed.
+fn update_regex(
+    old: &RoLock<Option<Regex<rx_cfg::Anchored>>>,
+    new: &RoLock<Option<Regex<rx_cfg::Anchored>>>,
+    description: &'static str,
+) -> Result<bool> {
+    let new = new.read_clean(description)?.clone();
+
+    let unchanged = {
+        let old = old.read_clean(description)?;
+        old.as_ref().map(|rx| rx.as_str()) == new.as_ref().map(|rx| rx.as_str())
+    };
+
+    if unchanged {
+        return Ok(false);
+    }
+
+    old.replace(new, description)?;
+
+    Ok(true)
+}
+
+fn regex_is_unset(regex: &RoLock<Option<Regex<rx_cfg::Anchored>>>) -> bool {
+    regex.read().map(|rx| rx.is_none()).unwrap_or(false)
+}
+
+fn serialize_duration_as_secs<S>(duration: &Duration, serializer: S) -> StdResult<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u64(duration.as_secs())
+}
+
 fn mk_true() -> bool {
     true
 }
+
+fn mk_prefix_update_interval() -> u16 {
+    DEFAULT_PREFIX_UPDATE_INTERVAL_SECS
+}
+
+fn mk_max_msgs_per_reaction() -> u16 {
+    DEFAULT_MAX_MSGS_PER_REACTION
+}
+
+fn mk_sasl_plain() -> SaslMechanism {
+    SaslMechanism::Plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn yaml_round_trip() {
+        let yaml = r#"
+            nickname: test-bot
+            command prefix: "!"
+            admins:
+              - nick: admin
+            servers:
+              - name: test
+                host: irc.example.net
+                port: 6697
+                SASL:
+                  username: test-bot
+                  password: hunter2
+                channels:
+                  - name: '#test'
+                    can see: 'test/#test-.*|other/#(a|b)'
+        "#;
+
+        let cfg = Config::try_from(yaml).unwrap();
+        let written = cfg.to_yaml_string().unwrap();
+        let reread = Config::try_from(written.as_str()).unwrap();
+
+        assert_eq!(reread.to_yaml_string().unwrap(), written);
+        assert_eq!(
+            reread.servers[0].channels[0]
+                .can_see
+                .read()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .as_str(),
+            r"\A(?:test/#test-.*|other/#(a|b))\z"
+        );
+    }
+
+    #[test]
+    fn documented_fields_parse() {
+        let yaml = r#"
+            nickname: test-bot
+            alt nicks: [test-bot-alt]
+            username: tester
+            realname: A test bot
+            join delay: 5
+            command prefix: "!"
+            require command prefix in private messages: true
+            addressee suffix: ", "
+            accept invites: true
+            accept invites only from admins: true
+            learn prefix by self-message: false
+            prefix update interval: 600
+            max messages per reaction: 5
+            metrics address: "127.0.0.1:9184"
+            admins:
+              - nick: admin
+                user: adm
+                host: example.net
+            servers:
+              - name: test
+                host: irc.example.net
+                port: 6697
+                alt nicks: [test-bot-server-alt]
+                nick password: hunter2
+                server password: swordfish
+                SASL:
+                  mechanism: PLAIN
+                  username: test-bot
+                  password: hunter3
+                TLS: false
+                await registration mode: r
+                channels:
+                  - name: '#Test'
+                    autojoin: true
+                    can see: 'test/#test-.*'
+                    seen by: 'other/#.*'
+                    greeting: Hello.
+                  - name: '#test-other'
+        "#;
+
+        let cfg = Config::try_from(yaml).unwrap();
+
+        assert_eq!(cfg.nickname, "test-bot");
+        assert_eq!(cfg.alt_nicks, ["test-bot-alt"]);
+        assert_eq!(cfg.username, "tester");
+        assert_eq!(cfg.realname, "A test bot");
+        assert_eq!(cfg.join_delay, Duration::from_secs(5));
+        assert_eq!(cfg.command_prefix.as_ref().unwrap(), "!");
+        assert!(cfg.require_cmd_prefix_in_pms);
+        assert_eq!(cfg.addressee_suffix.as_ref().unwrap(), ", ");
+        assert!(cfg.accept_invites);
+        assert!(cfg.accept_invites_only_from_admins);
+        assert!(!cfg.learn_prefix_by_self_msg);
+        assert_eq!(cfg.prefix_update_interval, Duration::from_secs(600));
+        assert_eq!(cfg.max_msgs_per_reaction, 5);
+        assert_eq!(cfg.metrics_address.as_ref().unwrap(), "127.0.0.1:9184");
+
+        assert_eq!(cfg.admins.len(), 1);
+        assert_eq!(cfg.admins[0].nick.as_ref().unwrap(), "admin");
+        assert_eq!(cfg.admins[0].user.as_ref().unwrap(), "adm");
+        assert_eq!(cfg.admins[0].host.as_ref().unwrap(), "example.net");
+
+        assert_eq!(cfg.servers.len(), 1);
+        let server = &cfg.servers[0];
+        assert_eq!(server.name, "test");
+        assert_eq!(server.host, "irc.example.net");
+        assert_eq!(server.port, 6697);
+        assert_eq!(server.alt_nicks.as_ref().unwrap(), &["test-bot-server-alt"]);
+        assert_eq!(server.nick_password.as_ref().unwrap(), "hunter2");
+        assert_eq!(server.server_password.as_ref().unwrap(), "swordfish");
+        assert!(!server.tls);
+        assert_eq!(server.await_registration_mode, Some('r'));
+
+        let sasl = server.sasl.as_ref().unwrap();
+        match sasl.mechanism {
+            SaslMechanism::Plain => {}
+        }
+        assert_eq!(sasl.username, "test-bot");
+        assert_eq!(sasl.password, "hunter3");
+
+        assert_eq!(server.channels.len(), 2);
+        let channel = &server.channels[0];
+        assert_eq!(channel.name, ChannelName::new("#test").unwrap());
+        assert_eq!(&*channel.name, "#Test");
+        assert_eq!(channel.greeting.as_ref().unwrap(), "Hello.");
+
+        let can_see = channel.can_see.read().unwrap();
+        let can_see = can_see.as_ref().unwrap();
+        assert!(can_see.is_match("test/#test-1"));
+        assert!(!can_see.is_match("xtest/#test-1"));
+
+        let seen_by = channel.seen_by.read().unwrap();
+        let seen_by = seen_by.as_ref().unwrap();
+        assert!(seen_by.is_match("other/#a"));
+        assert!(!seen_by.is_match("another/#a"));
+
+        let channel = &server.channels[1];
+        assert_eq!(&*channel.name, "#test-other");
+        assert!(channel.can_see.read().unwrap().is_none());
+        assert!(channel.seen_by.read().unwrap().is_none());
+        assert!(channel.greeting.is_none());
+
+        let aatxe_config = &cfg.aatxe_configs[0].1;
+        assert_eq!(aatxe_config.nickname.as_ref().unwrap(), "test-bot");
+        assert_eq!(
+            aatxe_config.alt_nicks.as_ref().unwrap(),
+            &["test-bot-server-alt"]
+        );
+        assert_eq!(aatxe_config.nick_password.as_ref().unwrap(), "hunter2");
+        assert_eq!(aatxe_config.password.as_ref().unwrap(), "swordfish");
+        assert_eq!(aatxe_config.use_ssl, Some(false));
+    }
+
+    #[test]
+    fn documented_defaults() {
+        let cfg = Config::try_from(
+            "{nickname: test-bot, servers: [{name: test, host: h, port: 1, \
+             channels: [{name: '#test'}]}]}",
+        )
+        .unwrap();
+
+        assert_eq!(cfg.alt_nicks, ["test-bot_", "test-bot__"]);
+        assert_eq!(cfg.username, "test-bot");
+        assert_eq!(cfg.realname, *pkg_info::BRIEF_CREDITS_STRING);
+        assert_eq!(cfg.join_delay, Duration::from_secs(0));
+        assert!(cfg.command_prefix.is_none());
+        assert!(!cfg.require_cmd_prefix_in_pms);
+        assert!(cfg.addressee_suffix.is_none());
+        assert!(!cfg.accept_invites);
+        assert!(!cfg.accept_invites_only_from_admins);
+        assert!(cfg.learn_prefix_by_self_msg);
+        assert_eq!(
+            cfg.prefix_update_interval,
+            Duration::from_secs(DEFAULT_PREFIX_UPDATE_INTERVAL_SECS.into())
+        );
+        assert_eq!(cfg.max_msgs_per_reaction, DEFAULT_MAX_MSGS_PER_REACTION);
+        assert!(cfg.metrics_address.is_none());
+        assert!(cfg.admins.is_empty());
+
+        let server = &cfg.servers[0];
+        assert!(server.alt_nicks.is_none());
+        assert!(server.nick_password.is_none());
+        assert!(server.server_password.is_none());
+        assert!(server.sasl.is_none());
+        assert!(server.tls);
+        assert!(server.await_registration_mode.is_none());
+    }
+
+    #[test]
+    fn includes() {
+        let dir = env::temp_dir().join(format!("irc-bot-config-test-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+
+        let write = |name: &str, text: &str| fs::write(dir.join(name), text).unwrap();
+
+        write(
+            "sub/servers.yaml",
+            "{nickname: shared-bot, join delay: 5, servers: [{name: test, host: h, port: 1}]}",
+        );
+        write("sub/admins.json", r#"{"admins": [{"nick": "admin"}]}"#);
+        write(
+            "main.yaml",
+            "{include: [sub/servers.yaml, sub/admins.json], nickname: test-bot}",
+        );
+        write(
+            "cycle-a.yaml",
+            "{include: [cycle-b.yaml], nickname: test-bot}",
+        );
+        write("cycle-b.yaml", "{include: [cycle-a.yaml]}");
+
+        let cfg = Config::try_from_path(dir.join("main.yaml"));
+        let cycle = Config::try_from_path(dir.join("cycle-a.yaml"));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let cfg = cfg.unwrap();
+        assert_eq!(cfg.nickname, "test-bot");
+        assert_eq!(cfg.join_delay, Duration::from_secs(5));
+        assert_eq!(cfg.servers[0].name, "test");
+        assert_eq!(cfg.admins[0].nick.as_ref().unwrap(), "admin");
+
+        match *cycle.unwrap_err().kind() {
+            ErrorKind::Config(ref key, _) => assert_eq!(key, "include"),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn env_var_expansion() {
+        env::set_var("IRC_BOT_CONFIG_TEST_VAR", "hunter2");
+        env::remove_var("IRC_BOT_CONFIG_TEST_UNSET_VAR");
+
+        let expand = |yaml: &str| {
+            let mut value = serde_yaml::from_str::<serde_yaml::Value>(yaml).unwrap();
+            expand_env_vars(&mut value, "<top level>").map(|()| value)
+        };
+
+        assert_eq!(
+            expand(
+                "{password: '${IRC_BOT_CONFIG_TEST_VAR}', list: ['a${IRC_BOT_CONFIG_TEST_VAR}']}"
+            )
+            .unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>("{password: hunter2, list: [ahunter2]}")
+                .unwrap()
+        );
+        assert_eq!(
+            expand("{text: 'costs $5, not $${IRC_BOT_CONFIG_TEST_VAR}'}").unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>(
+                "{text: 'costs $5, not ${IRC_BOT_CONFIG_TEST_VAR}'}"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            expand("{port: 6697, tls: true, '${IRC_BOT_CONFIG_TEST_VAR}': ~}").unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>(
+                "{port: 6697, tls: true, '${IRC_BOT_CONFIG_TEST_VAR}': ~}"
+            )
+            .unwrap()
+        );
+
+        match *expand("{servers: [{password: '${IRC_BOT_CONFIG_TEST_UNSET_VAR}'}]}")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::Config(ref key, ref problem) => {
+                assert_eq!(key, "password");
+                assert!(
+                    problem.contains("IRC_BOT_CONFIG_TEST_UNSET_VAR"),
+                    "{}",
+                    problem
+                );
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+
+        match *expand("{password: 'a${IRC_BOT_CONFIG_TEST_VAR'}")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::Config(ref key, ref problem) => {
+                assert_eq!(key, "password");
+                assert!(problem.contains("unterminated"), "{}", problem);
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn yaml_merging() {
+        let mut base = serde_yaml::from_str("{a: 1, b: {c: 2, d: 3}, e: [4, 5]}").unwrap();
+        let overlay = serde_yaml::from_str("{b: {d: 6, f: 7}, e: [8]}").unwrap();
+
+        merge_yaml(&mut base, overlay);
+
+        assert_eq!(
+            base,
+            serde_yaml::from_str::<serde_yaml::Value>("{a: 1, b: {c: 2, d: 6, f: 7}, e: [8]}")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn prefix_update_interval_bounds() {
+        let mk = |interval: u16| {
+            Config::try_from(format!(
+                "{{nickname: test-bot, prefix update interval: {}, \
+                 servers: [{{name: test, host: h, port: 1}}]}}",
+                interval
+            ))
+        };
+
+        assert_eq!(
+            mk(0).unwrap().prefix_update_interval,
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            mk(60).unwrap().prefix_update_interval,
+            Duration::from_secs(60)
+        );
+        assert!(mk(59).is_err());
+    }
+
+    #[test]
+    fn pm_cmd_prefix_requires_cmd_prefix() {
+        let mk = |extra: &str| {
+            Config::try_from(format!(
+                "{{nickname: test-bot, require command prefix in private messages: true, {} \
+                 servers: [{{name: test, host: h, port: 1}}]}}",
+                extra
+            ))
+        };
+
+        assert!(mk("command prefix: '!',").unwrap().require_cmd_prefix_in_pms);
+
+        match *mk("").unwrap_err().kind() {
+            ErrorKind::Config(ref key, _) => {
+                assert_eq!(key, "require command prefix in private messages")
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn invalid_channel_name() {
+        let result = Config::try_from(
+            "{nickname: test-bot, servers: [\
+             {name: first, host: h, port: 1, channels: [{name: '#a'}, {name: '#b'}]}, \
+             {name: second, host: h, port: 2, channels: [{name: '#c'}, {name: 'd'}]}]}",
+        );
+
+        match *result.unwrap_err().kind() {
+            ErrorKind::Config(ref key, ref problem) => {
+                assert_eq!(key, "channels");
+                assert_eq!(
+                    problem,
+                    "of server \"second\" has an invalid name at index 1: \
+                     Invalid IRC channel name: \"d\""
+                );
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn update_channel_visibility() {
+        let mk = |can_see: &str| {
+            Config::try_from(format!(
+                "{{nickname: test-bot, servers: [{{name: test, host: h, port: 1, channels: [\
+                 {{name: '#a', can see: '{}'}}, {{name: '#b'}}]}}]}}",
+                can_see
+            ))
+            .unwrap()
+        };
+
+        let cfg = mk("test/#b");
+        let is_match = |cfg: &Config| {
+            cfg.servers[0].channels[0]
+                .can_see
+                .read()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .is_match("test/#c")
+        };
+
+        assert!(!is_match(&cfg));
+        assert_eq!(cfg.update_channel_visibility(&mk("test/#b")).unwrap(), 0);
+        assert_eq!(cfg.update_channel_visibility(&mk("test/#c")).unwrap(), 1);
+        assert!(is_match(&cfg));
+    }
+}
@@ -4,6 +4,7 @@ use super::ServerId;
 use irc;
 use rand;
 use regex;
+use serde_json;
 use serde_yaml;
 use std::any::Any;
 use std::borrow::Cow;
@@ -25,12 +26,16 @@ error_chain! {
 
         Regex(regex::Error);
 
+        SerdeJson(serde_json::Error);
+
         SerdeYaml(serde_yaml::Error);
 
         WalkDir(walkdir::Error);
     }
 
     links {
+        IrcUtil(util::irc::Error, util::irc::ErrorKind);
+
         YamlUtil(util::yaml::Error, util::yaml::ErrorKind);
     }
 
@@ -75,6 +80,11 @@ error_chain! {
             display("Configuration error: Key {:?} {}.", key, problem)
         }
 
+        ConfigNotReloadable {
+            description("configuration not reloadable")
+            display("The configuration cannot be reloaded, because it was not read from a file.")
+        }
+
         ThreadSpawnFailure(io_err: io::Error) {
             description("failed to spawn thread")
             display("Failed to spawn thread: {}", io_err)
@@ -83,13 +93,30 @@ error_chain! {
         HandlerPanic(
             feature_kind: Cow<'static, str>,
             feature_name: Cow<'static, str>,
-            payload: Box<Any + Send + 'static>
+            panic_msg: Cow<'static, str>
         ) {
             description("panic in module feature handler function")
             display("The handler function for {} {:?} panicked with the following message: {}",
                     feature_kind,
                     feature_name,
-                    util::fmt::FmtAny(payload.as_ref()))
+                    panic_msg)
+        }
+
+        RawMsgHasLineBreak(msg: Cow<'static, str>) {
+            description("raw IRC message contains a line break or NUL character")
+            display("Refusing to send a raw IRC message that contains a line break or NUL \
+                     character, as it could smuggle in additional IRC commands: {:?}",
+                    msg)
+        }
+
+        MsgParamInvalid(desc: Cow<'static, str>, param: Cow<'static, str>) {
+            description("IRC message parameter contains a control character or misplaced \
+                         whitespace")
+            display("Refusing to send an IRC message whose {} contains a control character or \
+                     misplaced whitespace, as it could smuggle in additional IRC commands or \
+                     parameters: {:?}",
+                    desc,
+                    param)
         }
 
         NicknameUnknown {
@@ -112,6 +139,11 @@ error_chain! {
                     server_id = server_id)
         }
 
+        UnknownModule(name: String) {
+            description("module name not recognized")
+            display("No module named {:?} is loaded.", name)
+        }
+
         LockPoisoned(lock_contents_desc: Cow<'static, str>) {
             description("lock poisoned")
             display("A thread panicked, poisoning a lock around {}.", lock_contents_desc)
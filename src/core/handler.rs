@@ -2,15 +2,22 @@ use super::BotCmdResult;
 use super::BotCommand;
 use super::Error;
 use super::ErrorReaction;
+use super::Module;
+use super::ModuleStore;
 use super::MsgDest;
 use super::MsgMetadata;
 use super::MsgPrefix;
+use super::Reaction;
 use super::Result;
+use super::ServerId;
 use super::State;
 use super::Trigger;
+use irc::proto::Message;
 use regex::Captures;
+use std::fmt;
 use std::panic::RefUnwindSafe;
 use std::panic::UnwindSafe;
+use std::sync::Arc;
 use yaml_rust::Yaml;
 
 pub trait ErrorHandler: Send + Sync + UnwindSafe + RefUnwindSafe + 'static {
@@ -30,6 +37,14 @@ where
     }
 }
 
+/// A handler function for a bot command.
+///
+/// This trait is implemented for all suitable functions whose return type implements
+/// `Into<BotCmdResult>`, so a handler function may return whichever of, e.g., `Reaction`,
+/// `BotCmdResult`, `Result<Reaction>`, or `Result<BotCmdResult>` is most convenient, without
+/// needing to convert its return value into a `BotCmdResult` itself. In a `Result`, the error type
+/// may be any type that can be converted into a `BotCmdResult`, including this library's `Error`
+/// and `BotCmdResult` itself.
 pub trait BotCmdHandler: Send + Sync + UnwindSafe + RefUnwindSafe {
     fn run(&self, HandlerContext, &Yaml) -> BotCmdResult;
 }
@@ -44,6 +59,12 @@ where
     }
 }
 
+/// A handler function for a trigger.
+///
+/// As with [`BotCmdHandler`], this trait is implemented for all suitable functions whose return
+/// type implements `Into<BotCmdResult>`.
+///
+/// [`BotCmdHandler`]: <trait.BotCmdHandler.html>
 pub trait TriggerHandler: Send + Sync + UnwindSafe + RefUnwindSafe {
     fn run(&self, HandlerContext, Captures) -> BotCmdResult;
 }
@@ -58,6 +79,57 @@ where
     }
 }
 
+/// Work that a bot command or trigger handler has deferred by returning [`BotCmdResult::Defer`].
+///
+/// This trait is implemented for all suitable one-shot functions that take the bot state and
+/// whose return type implements `Into<BotCmdResult>`, as with [`BotCmdHandler`].
+///
+/// [`BotCmdHandler`]: <trait.BotCmdHandler.html>
+/// [`BotCmdResult::Defer`]: <enum.BotCmdResult.html#variant.Defer>
+pub trait DeferredHandler: Send + UnwindSafe + 'static {
+    fn run(self: Box<Self>, &State) -> BotCmdResult;
+}
+
+impl<F, R> DeferredHandler for F
+where
+    F: FnOnce(&State) -> R + Send + UnwindSafe + 'static,
+    R: Into<BotCmdResult>,
+{
+    fn run(self: Box<Self>, state: &State) -> BotCmdResult {
+        (*self)(state).into()
+    }
+}
+
+impl fmt::Debug for DeferredHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DeferredHandler")
+    }
+}
+
+/// A handler function for raw IRC messages.
+///
+/// A raw message handler is run on every message that the bot receives from any server, of
+/// whatever type, before the message is otherwise handled. The handler is given the ID of the
+/// server from which the message came and the message itself.
+///
+/// Raw message handlers are run on the thread that receives messages from the server, so they
+/// should not block for long. The reaction that a handler returns is addressed as though it were
+/// a reaction to a bot command sent in the channel or by the user to which the message pertains;
+/// if the message pertains to no channel or user, the handler should return `Reaction::None`.
+pub trait RawMsgHandler: Send + Sync + UnwindSafe + RefUnwindSafe {
+    fn run(&self, &State, ServerId, &Message) -> Result<Reaction>;
+}
+
+impl<F, R> RawMsgHandler for F
+where
+    F: Fn(&State, ServerId, &Message) -> R + Send + Sync + UnwindSafe + RefUnwindSafe,
+    R: Into<Result<Reaction>>,
+{
+    fn run(&self, state: &State, server_id: ServerId, msg: &Message) -> Result<Reaction> {
+        self(state, server_id, msg).into()
+    }
+}
+
 pub trait ModuleLoadHandler: Send + Sync + UnwindSafe + RefUnwindSafe + 'static {
     fn run(&self, &State) -> Result<()>;
 }
@@ -98,6 +170,16 @@ pub enum ModuleFeatureRef<'s> {
     Trigger(&'s Trigger),
 }
 
+impl<'s> ModuleFeatureRef<'s> {
+    /// Returns the module that provides this feature.
+    pub fn provider(&self) -> &'s Arc<Module> {
+        match *self {
+            ModuleFeatureRef::Command(cmd) => &cmd.provider,
+            ModuleFeatureRef::Trigger(trigger) => &trigger.provider,
+        }
+    }
+}
+
 impl<'s, 'm> HandlerContext<'s, 'm> {
     /// Returns the `MsgMetadata` for the message that caused this handler to be run.
     pub fn request_metadata(&self) -> MsgMetadata<'m> {
@@ -116,9 +198,9 @@ impl<'s, 'm> HandlerContext<'s, 'm> {
         self.state.guess_reply_dest(&self.request_metadata())
     }
 
-    // TODO
-    // pub fn module_data(&self) -> Result<...> {
-    //     let module_id = &self.this_feature.provider().(...);
-    //     self.state.(...)
-    // }
+    /// Returns a handle to the persistent key–value store of the module that provides the feature
+    /// for which this handler is running.
+    pub fn module_store(&self) -> ModuleStore<'s> {
+        ModuleStore::new(self.state, &self.this_feature.provider().name)
+    }
 }
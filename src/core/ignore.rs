@@ -0,0 +1,180 @@
+use super::MsgPrefix;
+use super::Result;
+use super::State;
+use std::cmp::Ordering;
+use std::fmt;
+use util::irc::case_insensitive_str_cmp;
+use util::lock::ReadLockExt;
+use util::lock::WriteLockExt;
+
+/// A pattern identifying IRC users whose messages the bot should ignore.
+///
+/// Each of the fields `nick`, `user`, and `host` that is set must match the corresponding part of
+/// a message's prefix, case-insensitively under IRC's rules, for the message to be ignored; fields
+/// that are not set match anything.
+#[derive(Clone, Debug)]
+pub struct IgnoreMask {
+    pub nick: Option<String>,
+    pub user: Option<String>,
+    pub host: Option<String>,
+}
+
+impl IgnoreMask {
+    pub fn matches(&self, prefix: MsgPrefix) -> bool {
+        field_matches(prefix.nick, &self.nick)
+            && field_matches(prefix.user, &self.user)
+            && field_matches(prefix.host, &self.host)
+    }
+
+    /// Returns whether none of this mask's fields are set.
+    pub fn is_empty(&self) -> bool {
+        self.nick.is_none() && self.user.is_none() && self.host.is_none()
+    }
+}
+
+fn field_matches(candidate: Option<&str>, control: &Option<String>) -> bool {
+    match (candidate, control) {
+        (Some(cdt), &Some(ref ctl)) => {
+            case_insensitive_str_cmp(cdt, ctl.as_str()) == Ordering::Equal
+        }
+        (_, &None) => true,
+        (None, &Some(_)) => false,
+    }
+}
+
+fn field_eq(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (&Some(ref a), &Some(ref b)) => {
+            case_insensitive_str_cmp(a.as_str(), b.as_str()) == Ordering::Equal
+        }
+        (&None, &None) => true,
+        _ => false,
+    }
+}
+
+impl PartialEq for IgnoreMask {
+    fn eq(&self, other: &Self) -> bool {
+        field_eq(&self.nick, &other.nick)
+            && field_eq(&self.user, &other.user)
+            && field_eq(&self.host, &other.host)
+    }
+}
+
+impl Eq for IgnoreMask {}
+
+impl fmt::Display for IgnoreMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn or_wildcard(field: &Option<String>) -> &str {
+            match field {
+                Some(s) => s,
+                None => "*",
+            }
+        }
+
+        write!(
+            f,
+            "{}!{}@{}",
+            or_wildcard(&self.nick),
+            or_wildcard(&self.user),
+            or_wildcard(&self.host),
+        )
+    }
+}
+
+impl State {
+    /// Adds the given mask to the bot's ignore list, returning whether it was not already present.
+    ///
+    /// A mask that sets none of its fields would match everyone, and is rejected as though it
+    /// were already present.
+    pub fn ignore(&self, mask: IgnoreMask) -> Result<bool> {
+        if mask.is_empty() {
+            return Ok(false);
+        }
+
+        let mut list = self.ignore_list.write_clean("the ignore list")?;
+
+        if list.contains(&mask) {
+            Ok(false)
+        } else {
+            list.push(mask);
+            Ok(true)
+        }
+    }
+
+    /// Removes the given mask from the bot's ignore list, returning whether it was present.
+    pub fn unignore(&self, mask: &IgnoreMask) -> Result<bool> {
+        let mut list = self.ignore_list.write_clean("the ignore list")?;
+        let len_before = list.len();
+
+        list.retain(|m| m != mask);
+
+        Ok(list.len() != len_before)
+    }
+
+    pub fn ignore_list(&self) -> Result<Vec<IgnoreMask>> {
+        Ok(self.ignore_list.read_clean("the ignore list")?.clone())
+    }
+
+    /// Returns whether messages with the given prefix should be ignored. Messages from the bot's
+    /// administrators are never ignored.
+    pub fn is_ignored(&self, prefix: MsgPrefix) -> Result<bool> {
+        if self.have_admin(prefix)? {
+            return Ok(false);
+        }
+
+        Ok(self
+            .ignore_list
+            .read_clean("the ignore list")?
+            .iter()
+            .any(|mask| mask.matches(prefix)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(nick: Option<&str>, user: Option<&str>, host: Option<&str>) -> IgnoreMask {
+        IgnoreMask {
+            nick: nick.map(ToOwned::to_owned),
+            user: user.map(ToOwned::to_owned),
+            host: host.map(ToOwned::to_owned),
+        }
+    }
+
+    fn prefix<'a>(nick: &'a str, user: &'a str, host: &'a str) -> MsgPrefix<'a> {
+        MsgPrefix {
+            nick: Some(nick),
+            user: Some(user),
+            host: Some(host),
+        }
+    }
+
+    #[test]
+    fn ignore_mask_matching_examples() {
+        let p = prefix("Spammer[1]", "~spam", "example.com");
+
+        assert!(mask(Some("spammer{1}"), None, None).matches(p));
+        assert!(mask(None, None, Some("EXAMPLE.COM")).matches(p));
+        assert!(mask(Some("SPAMMER[1]"), Some("~spam"), Some("example.com")).matches(p));
+        assert!(!mask(Some("spammer"), None, None).matches(p));
+        assert!(!mask(Some("spammer[1]"), Some("~other"), None).matches(p));
+        assert!(!mask(Some("x"), None, None).matches(MsgPrefix {
+            nick: None,
+            user: None,
+            host: None,
+        }));
+    }
+
+    #[test]
+    fn ignore_mask_eq_examples() {
+        assert_eq!(
+            mask(Some("Nick"), None, None),
+            mask(Some("nICK"), None, None)
+        );
+        assert_ne!(
+            mask(Some("nick"), None, None),
+            mask(Some("nick"), Some("u"), None)
+        );
+    }
+}
@@ -1,13 +1,18 @@
 use super::bot_cmd;
 use super::irc_msgs::is_msg_to_nick;
+use super::irc_msgs::parse_prefixed_cmd;
 use super::irc_msgs::OwningMsgPrefix;
 use super::irc_send::push_to_outbox;
 use super::irc_send::OutboxPort;
 use super::parse_msg_to_nick;
 use super::pkg_info;
+use super::raw_handler;
 use super::reaction::LibReaction;
+use super::sasl;
 use super::trigger;
+use super::whois;
 use super::BotCmdResult;
+use super::DeferredHandler;
 use super::ErrorKind;
 use super::MsgDest;
 use super::MsgMetadata;
@@ -17,23 +22,47 @@ use super::Result;
 use super::Server;
 use super::ServerId;
 use super::State;
+use chrono::Local;
 use irc::client::prelude as aatxe;
+use irc::proto::CapSubCommand;
 use irc::proto::Message;
 use itertools::Itertools;
 use smallvec::SmallVec;
 use std::borrow::Borrow;
 use std::borrow::Cow;
-use std::cmp;
+use std::cmp::Ordering;
 use std::fmt::Display;
 use std::iter;
 use std::sync::Arc;
 use std::sync::RwLockWriteGuard;
 use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use util;
+use util::irc::case_insensitive_str_cmp;
+use util::irc::ChannelMap;
+use util::irc::ChannelName;
+use util::irc::IRC_LINE_TERMINATOR_LEN;
+use util::irc::IRC_MESSAGE_MAX_LEN;
+use util::irc::IRC_WORST_CASE_HOST_LEN;
+use util::irc::NickName;
+use util::lock::MutexExt;
 
 const UPDATE_MSG_PREFIX_STR: &'static str = "!!! UPDATE MESSAGE PREFIX !!!";
 
+/// How long after joining a channel in response to an invitation the bot ignores further
+/// invitations to that channel, so that it cannot be made to join and leave a channel repeatedly.
+pub(super) const INVITE_REJOIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The character that delimits Client-to-Client Protocol (CTCP) messages.
+const CTCP_DELIM: char = '\x01';
+
+/// The message that takes the place of the last message of output that had to be truncated to
+/// the configured `max messages per reaction`.
+const OUTPUT_TRUNCATED_STR: &str = "(output truncated)";
+
 impl State {
-    fn compose_msg<S1, S2>(
+    pub(super) fn compose_msg<S1, S2>(
         &self,
         dest: MsgDest,
         addressee: S1,
@@ -54,7 +83,11 @@ impl State {
             msg,
         );
 
-        info!("Sending message to {:?}: {:?}", dest, final_msg);
+        info!(
+            "Sending message to {}: {:?}",
+            self.msg_dest_dbg_string(dest),
+            final_msg
+        );
 
         let mut wrapped_msg = SmallVec::<[_; 1]>::new();
 
@@ -74,6 +107,24 @@ impl State {
         }
     }
 
+    /// Queues the given raw IRC message, such as `"WHOIS someone"`, to be sent to the server with
+    /// the given `ServerId`, as if a handler had returned it as a `Reaction::RawMsg`. This allows
+    /// modules to send arbitrary IRC commands from outside the usual reply path, e.g., from a thread
+    /// of their own.
+    ///
+    /// An error is returned if the message contains a line break or NUL character or cannot be
+    /// parsed as an IRC message.
+    pub fn send_to_server<S>(&self, server_id: ServerId, msg: S) -> Result<()>
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        let msg = parse_raw_msg(msg.into())?;
+
+        push_to_outbox(&self.outbox, server_id, LibReaction::RawMsg(msg));
+
+        Ok(())
+    }
+
     fn compose_msgs<S1, S2, M>(
         &self,
         dest: MsgDest,
@@ -85,13 +136,20 @@ impl State {
         S2: Display,
         M: IntoIterator<Item = S2>,
     {
+        let max_len = usize::from(self.config.max_msgs_per_reaction);
+
         // Not `SmallVec`, because we're guessing that the caller expects multiple messages.
         let mut output = Vec::new();
 
         for msg in msgs {
-            match self.compose_msg(dest, addressee.borrow(), msg)? {
-                Some(m) => output.push(m),
-                None => {}
+            if let Some(m) = self.compose_msg(dest, addressee.borrow(), msg)? {
+                flatten_lib_reaction(m, &mut output);
+            }
+
+            // Stop composing as soon as the limit is exceeded, in case the caller has passed an
+            // enormous number of messages. The output is truncated in `limit_output`.
+            if output.len() > max_len {
+                break;
             }
         }
 
@@ -102,6 +160,43 @@ impl State {
         }
     }
 
+    /// Limits the given output, which is to be sent to the given destination, to the configured
+    /// `max messages per reaction`, replacing the last message allowed with a note that the output
+    /// has been truncated if it exceeds that limit.
+    pub(super) fn limit_output(
+        &self,
+        dest: MsgDest,
+        output: Option<LibReaction<Message>>,
+    ) -> Result<Option<LibReaction<Message>>> {
+        let max_len = usize::from(self.config.max_msgs_per_reaction);
+
+        let mut msgs = Vec::new();
+
+        if let Some(output) = output {
+            flatten_lib_reaction(output, &mut msgs);
+        }
+
+        if msgs.len() > max_len {
+            warn!(
+                "Truncating output to {} to {} messages.",
+                self.msg_dest_dbg_string(dest),
+                max_len
+            );
+
+            msgs.truncate(max_len - 1);
+
+            if let Some(m) = self.compose_msg(dest, "", OUTPUT_TRUNCATED_STR)? {
+                flatten_lib_reaction(m, &mut msgs);
+            }
+        }
+
+        match msgs.len() {
+            0 => Ok(None),
+            1 => Ok(Some(msgs.remove(0))),
+            _ => Ok(Some(LibReaction::Multi(msgs))),
+        }
+    }
+
     /// Given a message's metadata, returns a guess at the destination to which replies to the
     /// message should be sent.
     pub fn guess_reply_dest<'a>(
@@ -118,7 +213,7 @@ impl State {
     ) -> Result<MsgDest<'a>> {
         Ok(MsgDest {
             server_id,
-            target: if target == self.nick(server_id)? {
+            target: if self.is_own_nick(server_id, target)? {
                 // The message was sent to the bot in one-to-one messaging, so replies should be
                 // sent in one-to-one messaging to the sender.
                 nick.ok_or(ErrorKind::ReceivedMsgHasBadPrefix)?
@@ -130,22 +225,59 @@ impl State {
         })
     }
 
+    /// Returns the length of the message prefix that the server is expected to prepend to messages
+    /// that the bot sends.
+    ///
+    /// Until the server has reported the bot's prefix to it (see `update_prefix_info`), the bot's
+    /// hostname is unknown, and the server may have prepended `~` to its username, so a worst-case
+    /// length is assumed for this part of the prefix.
     // TODO: Use the more up-to-date nick info from `IrcClient::current_nickname` in `irc` 0.14.
     fn prefix_len(&self, server_id: ServerId) -> Result<usize> {
-        Ok(self.read_msg_prefix(server_id)?.len())
+        let prefix = self.read_msg_prefix(server_id)?;
+
+        match prefix.parse() {
+            MsgPrefix {
+                host: Some(host), ..
+            } if !host.is_empty() => Ok(prefix.len()),
+            MsgPrefix { nick, user, .. } => {
+                // nick!~user@host
+                Ok(nick.map_or(0, str::len)
+                    + "!~".len()
+                    + user.map_or(0, str::len)
+                    + "@".len()
+                    + IRC_WORST_CASE_HOST_LEN)
+            }
+        }
+    }
+
+    /// Returns the configured command prefix, if any.
+    pub(super) fn command_prefix(&self) -> Option<&str> {
+        self.config.command_prefix.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns whether private messages to the bot must begin with the command prefix (or address
+    /// the bot by nickname) to be treated as bot commands.
+    pub(super) fn pm_requires_cmd_prefix(&self) -> bool {
+        self.config.require_cmd_prefix_in_pms
     }
 
     /// Returns the maximum number of bytes that can be sent as the content of a single `PRIVMSG`
     /// to the specified destination.
+    ///
+    /// IRC limits each message, as relayed by the server to its recipients, to
+    /// `IRC_MESSAGE_MAX_LEN` bytes, including the prefix that the server prepends and the
+    /// terminating CR-LF. IRCv3 message tags, which the bot doesn't send but a server may add when
+    /// relaying a message, don't count against this limit, so it's the same whether or not tags
+    /// are in use. The length returned here is a number of bytes of UTF-8, not of characters, and
+    /// errs on the side of caution where the bot's prefix is not yet known exactly.
     pub fn privmsg_content_max_len(&self, MsgDest { server_id, target }: MsgDest) -> Result<usize> {
         // :nick!user@host PRIVMSG target :message
         // :nick!user@host NOTICE target :message
-        let raw_len_limit = 512;
+        let raw_len_limit = IRC_MESSAGE_MAX_LEN;
         let punctuation_len = {
-            let line_terminator_len = 2;
             let spaces = 3;
             let colons = 2;
-            colons + spaces + line_terminator_len
+            colons + spaces + IRC_LINE_TERMINATOR_LEN
         };
         let cmd_len = "PRIVMSG".len();
         let metadata_len = self.prefix_len(server_id)? + cmd_len + target.len() + punctuation_len;
@@ -163,14 +295,21 @@ where
         return f(msg);
     }
 
+    for line in split_msg(msg, msg_len_limit) {
+        f(line)?
+    }
+
+    Ok(())
+}
+
+/// Splits the given message into lines shorter than `msg_len_limit` bytes, preferring to split at
+/// whitespace. A word too long to fit on a line is split at a UTF-8 character boundary.
+fn split_msg(msg: &str, msg_len_limit: usize) -> impl Iterator<Item = &str> {
     let mut split_end_idx = 0;
 
-    let lines = msg
-        .match_indices(char::is_whitespace)
+    msg.match_indices(char::is_whitespace)
         .peekable()
-        .batching(|iter| {
-            debug_assert!(msg.len() >= msg_len_limit);
-
+        .batching(move |iter| {
             let split_start_idx = split_end_idx;
 
             if split_start_idx >= msg.len() {
@@ -178,7 +317,7 @@ where
             }
 
             while let Some(&(next_space_idx, _)) = iter.peek() {
-                if msg[split_start_idx..next_space_idx].len() < msg_len_limit {
+                if next_space_idx - split_start_idx < msg_len_limit {
                     split_end_idx = next_space_idx;
                     iter.next();
                 } else {
@@ -186,31 +325,125 @@ where
                 }
             }
 
-            if iter.peek().is_none() {
+            if msg.len() - split_start_idx < msg_len_limit {
                 split_end_idx = msg.len()
             } else if split_end_idx <= split_start_idx {
-                split_end_idx = cmp::min(split_start_idx + msg_len_limit, msg.len())
+                split_end_idx =
+                    floor_char_boundary(msg, split_start_idx + msg_len_limit.saturating_sub(1));
+
+                if split_end_idx <= split_start_idx {
+                    // The limit is too small for even one character; send one anyway rather
+                    // than loop forever.
+                    split_end_idx = split_start_idx
+                        + msg[split_start_idx..]
+                            .chars()
+                            .next()
+                            .map_or(0, char::len_utf8);
+                }
             }
 
             Some(msg[split_start_idx..split_end_idx].trim())
-        });
+        })
+}
 
-    for line in lines {
-        f(line)?
+/// Returns the greatest index not greater than `idx` that lies on a UTF-8 character boundary in
+/// `s`.
+fn floor_char_boundary(s: &str, idx: usize) -> usize {
+    if idx >= s.len() {
+        return s.len();
     }
 
-    Ok(())
+    let mut idx = idx;
+
+    while !s.is_char_boundary(idx) {
+        idx -= 1;
+    }
+
+    idx
 }
 
+/// Appends the individual IRC messages that make up the given reaction to the given `Vec`.
+fn flatten_lib_reaction(reaction: LibReaction<Message>, output: &mut Vec<LibReaction<Message>>) {
+    match reaction {
+        LibReaction::RawMsg(_) => output.push(reaction),
+        LibReaction::Multi(reactions) => {
+            for reaction in reactions {
+                flatten_lib_reaction(reaction, output)
+            }
+        }
+    }
+}
+
+/// Converts the given reaction to the messages that are to be sent in response to a message from
+/// the given sender to the given target, limiting them to the configured `max messages per
+/// reaction`.
 fn handle_reaction(
     state: &Arc<State>,
     server_id: ServerId,
+    outbox: &OutboxPort,
     prefix: OwningMsgPrefix,
     target: &str,
     reaction: Reaction,
-    bot_nick: String,
 ) -> Result<Option<LibReaction<Message>>> {
-    let (reply_target, reply_addressee) = if target == bot_nick {
+    let reply_target = if state.is_own_nick(server_id, target)? {
+        prefix.parse().nick.unwrap_or(target).to_owned()
+    } else {
+        target.to_owned()
+    };
+
+    let output = handle_reaction_unlimited(state, server_id, outbox, prefix, target, reaction)?;
+
+    state.limit_output(
+        MsgDest {
+            server_id,
+            target: &reply_target,
+        },
+        output,
+    )
+}
+
+fn handle_reaction_unlimited(
+    state: &Arc<State>,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    prefix: OwningMsgPrefix,
+    target: &str,
+    reaction: Reaction,
+) -> Result<Option<LibReaction<Message>>> {
+    let reaction = match reaction {
+        Reaction::Delay(delay, reaction) => {
+            let target = target.to_owned();
+            return schedule_reaction(state, server_id, outbox, delay, move |state, outbox| {
+                handle_reaction(state, server_id, outbox, prefix, &target, *reaction)
+            })
+            .map(|()| None);
+        }
+        Reaction::Seq(reactions) => {
+            let lib_reactions = reactions
+                .into_iter()
+                .filter_map(|reaction| {
+                    handle_reaction_unlimited(
+                        state,
+                        server_id,
+                        outbox,
+                        prefix.clone(),
+                        target,
+                        reaction,
+                    )
+                    .transpose()
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            return Ok(if lib_reactions.is_empty() {
+                None
+            } else {
+                Some(LibReaction::Multi(lib_reactions))
+            });
+        }
+        r => r,
+    };
+
+    let (reply_target, reply_addressee) = if state.is_own_nick(server_id, target)? {
         (prefix.parse().nick.unwrap(), "")
     } else {
         (target, prefix.parse().nick.unwrap_or(""))
@@ -227,20 +460,76 @@ fn handle_reaction(
         Reaction::Msgs(a) => state.compose_msgs(reply_dest, "", a.iter()),
         Reaction::Reply(s) => state.compose_msg(reply_dest, reply_addressee, &s),
         Reaction::Replies(a) => state.compose_msgs(reply_dest, reply_addressee, a.iter()),
-        Reaction::RawMsg(s) => Ok(Some(LibReaction::RawMsg(s.parse()?))),
+        Reaction::ReplyTo { nick, text } => {
+            let nick = NickName::new(nick.as_ref())?;
+            state.compose_msg(reply_dest, nick.to_string(), &text)
+        }
+        Reaction::RawMsg(s) => Ok(Some(LibReaction::RawMsg(parse_raw_msg(s)?))),
+        Reaction::Silent(note) => {
+            info!("[{}] {}", state.msg_dest_dbg_string(reply_dest), note);
+
+            #[cfg(feature = "metrics")]
+            state.metrics.record_silent_reaction();
+
+            Ok(None)
+        }
         Reaction::Quit(msg) => Ok(Some(mk_quit(msg))),
+        Reaction::Kick { chan, nick, reason } => Ok(Some(LibReaction::RawMsg(mk_kick(
+            &chan,
+            &nick,
+            reason.as_ref().map(AsRef::as_ref),
+        )?))),
+        Reaction::Mode {
+            target,
+            modes,
+            args,
+        } => Ok(Some(LibReaction::RawMsg(mk_mode(&target, &modes, &args)?))),
+        Reaction::Delay(..) | Reaction::Seq(..) => unreachable!(),
     }
 }
 
+/// Has the given function called on the scheduler thread once the given delay has elapsed, and
+/// the reaction that it returns sent.
+///
+/// As with any other output, the delayed reaction is discarded by the sending thread if, by the
+/// time it comes due, the bot no longer has a connection to the server with the given ID.
+fn schedule_reaction<F>(
+    state: &Arc<State>,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    delay: Duration,
+    f: F,
+) -> Result<()>
+where
+    F: FnOnce(&Arc<State>, &OutboxPort) -> Result<Option<LibReaction<Message>>> + Send + 'static,
+{
+    let state_alias = state.clone();
+    let outbox = outbox.clone();
+
+    state.scheduler.schedule(delay, move || {
+        let state = state_alias;
+
+        let lib_reaction = match f(&state, &outbox) {
+            Ok(r) => r,
+            Err(e) => state.handle_err(e, "handling a delayed reaction"),
+        };
+
+        push_to_outbox(&outbox, server_id, lib_reaction);
+    })
+}
+
 fn handle_bot_command_or_trigger(
     state: &Arc<State>,
     server_id: ServerId,
+    outbox: &OutboxPort,
     prefix: OwningMsgPrefix,
     target: String,
     msg: String,
-    bot_nick: String,
+    is_action: bool,
 ) -> Option<LibReaction<Message>> {
     let reaction = (|| {
+        let bot_nick = state.nick(server_id)?;
+
         let metadata = MsgMetadata {
             prefix: prefix.parse(),
             dest: MsgDest {
@@ -249,24 +538,90 @@ fn handle_bot_command_or_trigger(
             },
         };
 
-        let cmd_ln = parse_msg_to_nick(&msg, metadata.dest.target, &bot_nick).unwrap_or("");
+        // Messages that use the command prefix rather than the bot's nickname are taken to be
+        // meant only as bot commands, so as not to run triggers on arbitrary channel chatter.
+        // Messages addressed to the bot in neither way are seen only by triggers that are always
+        // watching, which see all messages. Private messages count as addressed to the bot unless
+        // the bot is configured to require the command prefix in them too. CTCP `ACTION`s are
+        // never taken to be addressed to the bot.
+        let pm_requires_prefix = state.pm_requires_cmd_prefix();
+        let (cmd_ln, is_prefixed_cmd, is_addressed) = if is_action {
+            (msg.as_str(), false, false)
+        } else {
+            match parse_msg_to_nick(&msg, metadata.dest.target, &bot_nick, pm_requires_prefix) {
+                Some(cmd_ln) => (cmd_ln, false, true),
+                None => match parse_prefixed_cmd(
+                    &msg,
+                    metadata.dest.target,
+                    &bot_nick,
+                    state.command_prefix(),
+                    pm_requires_prefix,
+                ) {
+                    Some(cmd_ln) => (cmd_ln, true, true),
+                    None => (msg.as_str(), false, false),
+                },
+            }
+        };
 
         let mut cmd_name_and_args = cmd_ln.splitn(2, char::is_whitespace);
         let cmd_name = cmd_name_and_args.next().unwrap_or("");
         let cmd_args = cmd_name_and_args.next().unwrap_or("").trim();
 
-        if let Some(r) = bot_cmd::run(state, cmd_name, cmd_args, &metadata)? {
-            Ok(bot_command_reaction(cmd_name, r))
+        let cmd_result = if is_addressed {
+            bot_cmd::run(state, cmd_name, cmd_args, &metadata)?
+        } else {
+            None
+        };
+
+        let cmd_reaction = |cmd_name: &str, r| {
+            bot_command_reaction(state, server_id, outbox, &prefix, &target, cmd_name, r)
+        };
+
+        let reaction = if let Some(r) = cmd_result {
+            cmd_reaction(cmd_name, r)?
+        } else if !is_addressed || is_prefixed_cmd {
+            Reaction::None
         } else if let Some(r) = trigger::run_any_matching(state, cmd_ln, &metadata)? {
-            Ok(bot_command_reaction("<trigger>", r))
+            cmd_reaction("<trigger>", r)?
         } else {
-            Ok(Reaction::None)
+            Reaction::None
+        };
+
+        // Triggers that are always watching see messages that weren't meant for the bot, so
+        // their errors are passed to the error handler rather than reported where the message was
+        // sent, and don't keep the other reactions from being sent.
+        let watcher_reactions = trigger::run_always_watching(state, &msg, &metadata)
+            .into_iter()
+            .filter_map(|result| {
+                let reaction = match result {
+                    Ok(BotCmdResult::LibErr(e)) | Err(e) => Err(e),
+                    Ok(r) => cmd_reaction("<trigger>", r),
+                };
+
+                match reaction {
+                    Ok(r) => Some(r),
+                    Err(e) => {
+                        let err_reaction =
+                            state.handle_err(e, "running a trigger that is always watching");
+                        push_to_outbox(outbox, server_id, err_reaction);
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if watcher_reactions.is_empty() {
+            Ok(reaction)
+        } else {
+            Ok(Reaction::Seq(
+                iter::once(reaction).chain(watcher_reactions).collect(),
+            ))
         }
     })();
 
-    match reaction
-        .and_then(|reaction| handle_reaction(state, server_id, prefix, &target, reaction, bot_nick))
-    {
+    match reaction.and_then(|reaction| {
+        handle_reaction(state, server_id, outbox, prefix, &target, reaction)
+    }) {
         Ok(r) => r,
         Err(e) => Some(LibReaction::RawMsg(
             aatxe::Command::PRIVMSG(
@@ -278,9 +633,24 @@ fn handle_bot_command_or_trigger(
     }
 }
 
-fn bot_command_reaction(cmd_name: &str, result: BotCmdResult) -> Reaction {
+/// Converts the given result of the bot command with the given name (or of a trigger) into a
+/// reaction. If the result is `BotCmdResult::Defer`, the deferred work is started on a new thread,
+/// whose result will be handled and sent when it is ready, and the acknowledgment is returned.
+fn bot_command_reaction(
+    state: &Arc<State>,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    prefix: &OwningMsgPrefix,
+    target: &str,
+    cmd_name: &str,
+    result: BotCmdResult,
+) -> Result<Reaction> {
     let cmd_result = match result {
         BotCmdResult::Ok(r) => Ok(r),
+        BotCmdResult::Defer(ack, work) => {
+            run_deferred(state, server_id, outbox, prefix, target, cmd_name, work)?;
+            Ok(ack)
+        }
         BotCmdResult::Unauthorized => Err(format!(
             "My apologies, but you do not appear to have sufficient \
              authority to use my {:?} command.",
@@ -312,12 +682,130 @@ fn bot_command_reaction(cmd_name: &str, result: BotCmdResult) -> Reaction {
         BotCmdResult::BotErrMsg(s) => Err(format!("Internal error: {}", s).into()),
     };
 
-    match cmd_result {
+    Ok(match cmd_result {
         Ok(r) => r,
         Err(s) => Reaction::Msg(s),
+    })
+}
+
+/// Runs the given deferred work of the bot command with the given name (or of a trigger) on a new
+/// thread, and sends the reaction to its result when it is ready.
+fn run_deferred(
+    state: &Arc<State>,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    prefix: &OwningMsgPrefix,
+    target: &str,
+    cmd_name: &str,
+    work: Box<DeferredHandler>,
+) -> Result<()> {
+    let state = state.clone();
+    let outbox = outbox.clone();
+    let prefix = prefix.clone();
+    let target = target.to_owned();
+    let cmd_name = cmd_name.to_owned();
+
+    let thread_spawn_result = thread::Builder::new().spawn(move || {
+        let result = match util::run_handler("deferred handler", cmd_name.clone(), || {
+            work.run(&state)
+        }) {
+            Ok(r) => r,
+            Err(e) => BotCmdResult::LibErr(e),
+        };
+
+        let lib_reaction = bot_command_reaction(
+            &state, server_id, &outbox, &prefix, &target, &cmd_name, result,
+        )
+        .and_then(|reaction| {
+            handle_reaction(&state, server_id, &outbox, prefix.clone(), &target, reaction)
+        });
+
+        let lib_reaction = match lib_reaction {
+            Ok(r) => r,
+            Err(e) => state.handle_err(e, "handling the result of deferred work"),
+        };
+
+        push_to_outbox(&outbox, server_id, lib_reaction);
+    });
+
+    match thread_spawn_result {
+        Ok(thread::JoinHandle { .. }) => Ok(()),
+        Err(e) => Err(ErrorKind::ThreadSpawnFailure(e).into()),
     }
 }
 
+/// Parses a raw IRC message, first checking that it contains no CR, LF, or NUL character, any of
+/// which could be used to smuggle additional IRC commands after the intended one.
+fn parse_raw_msg(msg: Cow<'static, str>) -> Result<Message> {
+    if msg.contains(&['\r', '\n', '\0'][..]) {
+        bail!(ErrorKind::RawMsgHasLineBreak(msg))
+    }
+
+    Ok(msg.parse()?)
+}
+
+/// Checks that the given parameter of an IRC message that the bot is to send contains no control
+/// characters, which could be used to smuggle additional IRC commands after the intended one, nor,
+/// if `is_middle` is true, whitespace, which would split the parameter into several.
+fn check_msg_param(desc: &'static str, param: &str, is_middle: bool) -> Result<()> {
+    let is_invalid_char = |c: char| c.is_control() || (is_middle && c.is_whitespace());
+
+    ensure!(
+        !param.contains(is_invalid_char),
+        ErrorKind::MsgParamInvalid(desc.into(), param.to_owned().into())
+    );
+
+    Ok(())
+}
+
+/// Builds a `KICK` message, checking that its parameters are valid.
+fn mk_kick(chan: &str, nick: &str, reason: Option<&str>) -> Result<Message> {
+    let chan = ChannelName::new(chan)?;
+    let nick = NickName::new(nick)?;
+
+    if let Some(reason) = reason {
+        check_msg_param("kick reason", reason, false)?;
+    }
+
+    Ok(aatxe::Command::KICK(
+        chan.to_string(),
+        nick.to_string(),
+        reason.map(ToOwned::to_owned),
+    )
+    .into())
+}
+
+/// Builds a `MODE` message, checking that its parameters are valid. The target must be a channel
+/// name or a nickname.
+fn mk_mode<S>(target: &str, modes: &str, args: &[S]) -> Result<Message>
+where
+    S: AsRef<str>,
+{
+    check_msg_param("modes", modes, true)?;
+
+    for arg in args {
+        check_msg_param("mode argument", arg.as_ref(), true)?;
+    }
+
+    let mode_str = iter::once(modes)
+        .chain(args.iter().map(AsRef::as_ref))
+        .join(" ");
+
+    let cmd = if let Ok(chan) = ChannelName::new(target) {
+        aatxe::Command::ChannelMODE(
+            chan.to_string(),
+            aatxe::Mode::as_channel_modes(&mode_str)?,
+        )
+    } else {
+        aatxe::Command::UserMODE(
+            NickName::new(target)?.to_string(),
+            aatxe::Mode::as_user_modes(&mode_str)?,
+        )
+    };
+
+    Ok(cmd.into())
+}
+
 pub fn mk_quit<'a>(msg: Option<Cow<'a, str>>) -> LibReaction<Message> {
     let quit = aatxe::Command::QUIT(
         msg.map(Cow::into_owned)
@@ -372,6 +860,8 @@ pub(super) fn handle_msg(
         }
     };
 
+    run_raw_handlers(state, server_id, outbox, &msg);
+
     match msg {
         Message {
             command: aatxe::Command::PRIVMSG(target, msg),
@@ -385,10 +875,94 @@ pub(super) fn handle_msg(
             target,
             msg,
         ),
+        Message {
+            command: aatxe::Command::NICK(new_nick),
+            prefix,
+            ..
+        } => handle_nick_change(
+            state,
+            server_id,
+            OwningMsgPrefix::from_string(prefix.unwrap_or_default()),
+            new_nick,
+        ),
+        Message {
+            command: aatxe::Command::JOIN(channels, ..),
+            prefix,
+            ..
+        } => handle_join(
+            state,
+            server_id,
+            outbox,
+            OwningMsgPrefix::from_string(prefix.unwrap_or_default()),
+            &channels,
+        ),
+        Message {
+            command: aatxe::Command::INVITE(_, channel),
+            prefix,
+            ..
+        } => handle_invite(
+            state,
+            server_id,
+            outbox,
+            OwningMsgPrefix::from_string(prefix.unwrap_or_default()),
+            channel,
+        ),
         Message {
             command: aatxe::Command::UserMODE(nick, modes),
             ..
         } => handle_user_modes_change(state, server_id, outbox, nick, modes),
+        Message {
+            command: aatxe::Command::Response(aatxe::Response::RPL_WELCOME, args, _),
+            ..
+        } => handle_001(state, server_id, args),
+        Message {
+            command: aatxe::Command::CAP(_, subcommand, param, suffix),
+            ..
+        } => handle_cap(
+            state,
+            server_id,
+            outbox,
+            subcommand,
+            &suffix.or(param).unwrap_or_default(),
+        ),
+        Message {
+            command: aatxe::Command::AUTHENTICATE(data),
+            ..
+        } => sasl::handle_authenticate(state, server_id, &data),
+        Message {
+            command: aatxe::Command::Response(response @ aatxe::Response::RPL_SASLSUCCESS, ..),
+            ..
+        }
+        | Message {
+            command: aatxe::Command::Response(response @ aatxe::Response::ERR_SASLFAIL, ..),
+            ..
+        }
+        | Message {
+            command: aatxe::Command::Response(response @ aatxe::Response::ERR_SASLTOOLONG, ..),
+            ..
+        }
+        | Message {
+            command: aatxe::Command::Response(response @ aatxe::Response::ERR_SASLABORT, ..),
+            ..
+        }
+        | Message {
+            command: aatxe::Command::Response(response @ aatxe::Response::ERR_SASLALREADY, ..),
+            ..
+        } => sasl::handle_sasl_result(state, server_id, outbox, response),
+        Message {
+            command: aatxe::Command::Response(aatxe::Response::ERR_NICKNAMEINUSE, args, _),
+            ..
+        } => {
+            // The `irc` crate will try the next of the alternate nicknames set in its
+            // configuration, if any remain; we learn which nickname is accepted from the
+            // `RPL_WELCOME` or `NICK` message that follows.
+            warn!(
+                "[{}] Nickname in use: {:?}",
+                server_socket_addr_dbg_string,
+                args.get(1)
+            );
+            Ok(())
+        }
         Message {
             command: aatxe::Command::Response(aatxe::Response::RPL_ENDOFMOTD, ..),
             ..
@@ -397,6 +971,23 @@ pub(super) fn handle_msg(
             command: aatxe::Command::Response(aatxe::Response::ERR_NOMOTD, ..),
             ..
         } => handle_motd_end(state, server_id, outbox),
+        Message {
+            command: aatxe::Command::Response(response, args, suffix),
+            ..
+        } if whois::is_whois_reply(response) => {
+            if response == aatxe::Response::RPL_WHOISUSER {
+                handle_whois_user(state, server_id, &args)?;
+            }
+
+            whois::handle_whois_reply(
+                state,
+                server_id,
+                outbox,
+                response,
+                &args,
+                suffix.as_ref().map(AsRef::as_ref),
+            )
+        }
         Message {
             command: aatxe::Command::Response(aatxe::Response::RPL_MYINFO, ..),
             ..
@@ -408,6 +999,51 @@ pub(super) fn handle_msg(
     }
 }
 
+/// Runs the loaded modules' raw message handlers on the given message, sending any resulting
+/// reactions. An error from one handler is passed to the error handler and doesn't prevent the
+/// other handlers, or the bot's own handling of the message, from proceeding.
+fn run_raw_handlers(
+    state: &Arc<State>,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    msg: &Message,
+) {
+    let target = match msg.command {
+        aatxe::Command::PRIVMSG(ref target, _) | aatxe::Command::NOTICE(ref target, _) => {
+            Some(target.as_str())
+        }
+        _ => msg.response_target(),
+    };
+
+    for result in raw_handler::run_all(state, server_id, msg) {
+        let lib_reaction = result.and_then(|reaction| match (reaction, target) {
+            (Reaction::None, _) => Ok(None),
+            (reaction, Some(target)) => handle_reaction(
+                state,
+                server_id,
+                outbox,
+                OwningMsgPrefix::from_string(msg.prefix.clone().unwrap_or_default()),
+                target,
+                reaction,
+            ),
+            (reaction, None) => Err(format!(
+                "A raw message handler returned a reaction to a message that pertains to no \
+                 channel or user: reaction {:?}; message {:?}",
+                reaction,
+                msg.to_string().trim_end_matches("\r\n")
+            )
+            .into()),
+        });
+
+        let lib_reaction = match lib_reaction {
+            Ok(r) => r,
+            Err(e) => state.handle_err(e, "running a raw message handler"),
+        };
+
+        push_to_outbox(outbox, server_id, lib_reaction);
+    }
+}
+
 fn handle_privmsg(
     state: &Arc<State>,
     server_id: ServerId,
@@ -422,33 +1058,386 @@ fn handle_privmsg(
         msg
     );
 
+    if state.is_ignored(prefix.parse())? {
+        debug!("Ignoring message from ignored user {:?}", prefix.parse());
+        return Ok(());
+    }
+
+    // A CTCP `ACTION` (as sent with `/me`) is handled like other messages, with its text in place
+    // of the message, except that it's never taken to be a bot command. Other CTCP messages are
+    // queries, which are answered only when sent to the bot itself.
+    let (msg, is_action) = match parse_ctcp_action(&msg) {
+        Some(text) => (text.to_owned(), true),
+        None if msg.starts_with(CTCP_DELIM) => {
+            return if state.is_own_nick(server_id, &target)? {
+                handle_ctcp_query(state, server_id, outbox, &prefix, &msg)
+            } else {
+                Ok(())
+            };
+        }
+        None => (msg, false),
+    };
+
+    // The bot's message to itself must be recognized before checking whether messages are
+    // addressed to the bot, as it needn't begin with the command prefix.
+    let is_self_msg = match prefix.parse().nick {
+        Some(nick) => case_insensitive_str_cmp(nick, target.as_str()) == Ordering::Equal,
+        None => false,
+    };
+
+    if is_self_msg && msg.trim() == UPDATE_MSG_PREFIX_STR {
+        return update_prefix_info(state, server_id, &prefix.parse());
+    }
+
     let bot_nick = state.nick(server_id)?;
 
-    if !is_msg_to_nick(&target, &msg, &bot_nick) {
+    let pm_requires_prefix = state.pm_requires_cmd_prefix();
+
+    let is_addressed = !is_action
+        && (is_msg_to_nick(&target, &msg, &bot_nick, pm_requires_prefix)
+            || parse_prefixed_cmd(
+                &msg,
+                &target,
+                &bot_nick,
+                state.command_prefix(),
+                pm_requires_prefix,
+            )
+            .is_some());
+
+    if !is_addressed
+        && !state
+            .triggers
+            .values()
+            .flat_map(|triggers| triggers.iter())
+            .any(|t| t.always_watching)
+    {
         return Ok(());
     }
 
-    if prefix.parse().nick == Some(&target) && msg.trim() == UPDATE_MSG_PREFIX_STR {
-        update_prefix_info(state, server_id, &prefix.parse())
-    } else {
-        // This could take a while or panic, so do it in a new thread.
+    // This could take a while or panic, so do it in a new thread.
+
+    // These are cheap to clone, supposedly.
+    let state = state.clone();
+    let outbox = outbox.clone();
 
-        // These are cheap to clone, supposedly.
-        let state = state.clone();
-        let outbox = outbox.clone();
+    let thread_spawn_result = thread::Builder::new().spawn(move || {
+        let lib_reaction = handle_bot_command_or_trigger(
+            &state, server_id, &outbox, prefix, target, msg, is_action,
+        );
 
-        let thread_spawn_result = thread::Builder::new().spawn(move || {
-            let lib_reaction =
-                handle_bot_command_or_trigger(&state, server_id, prefix, target, msg, bot_nick);
+        push_to_outbox(&outbox, server_id, lib_reaction);
+    });
 
-            push_to_outbox(&outbox, server_id, lib_reaction);
-        });
+    match thread_spawn_result {
+        Ok(thread::JoinHandle { .. }) => Ok(()),
+        Err(e) => Err(ErrorKind::ThreadSpawnFailure(e).into()),
+    }
+}
+
+/// If the given message is a Client-to-Client Protocol (CTCP) `ACTION`, returns its text.
+fn parse_ctcp_action(msg: &str) -> Option<&str> {
+    if !msg.starts_with(CTCP_DELIM) {
+        return None;
+    }
+
+    let mut parts = msg.trim_matches(CTCP_DELIM).splitn(2, ' ');
+
+    match parts.next() {
+        Some(tag) if tag.eq_ignore_ascii_case("ACTION") => Some(parts.next().unwrap_or("")),
+        _ => None,
+    }
+}
+
+/// Handles a Client-to-Client Protocol (CTCP) query sent to the bot, replying to `VERSION`,
+/// `PING`, `TIME`, and `SOURCE` queries and ignoring others.
+fn handle_ctcp_query(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    prefix: &OwningMsgPrefix,
+    msg: &str,
+) -> Result<()> {
+    let query = msg.trim_matches(CTCP_DELIM);
+    let mut query_parts = query.splitn(2, ' ');
+    let tag = query_parts.next().unwrap_or("").to_ascii_uppercase();
+    let params = query_parts.next().unwrap_or("");
+
+    let reply = match tag.as_ref() {
+        "VERSION" => format!(
+            "VERSION {}.rs {} <{}>",
+            state.framework_crate_name(),
+            state.framework_version_str(),
+            state.framework_homepage_url_str()
+        ),
+        "PING" => format!("PING {}", params),
+        "TIME" => format!("TIME {}", Local::now().to_rfc2822()),
+        "SOURCE" => format!("SOURCE {}", state.framework_homepage_url_str()),
+        _ => return Ok(()),
+    };
+
+    let nick = match prefix.parse().nick {
+        Some(nick) => nick.to_owned(),
+        None => return Err(ErrorKind::ReceivedMsgHasBadPrefix.into()),
+    };
+
+    debug!("Replying to CTCP {} query from {:?}.", tag, nick);
+
+    push_to_outbox(
+        outbox,
+        server_id,
+        LibReaction::RawMsg(
+            aatxe::Command::NOTICE(nick, format!("{}{}{}", CTCP_DELIM, reply, CTCP_DELIM)).into(),
+        ),
+    );
+
+    Ok(())
+}
+
+/// Handles a `NICK` message, which informs us that someone's nickname has changed. If the
+/// nickname that has changed is the bot's own, the stored message prefix is updated, so that the
+/// bot continues to recognize when it is being addressed.
+fn handle_nick_change(
+    state: &State,
+    server_id: ServerId,
+    prefix: OwningMsgPrefix,
+    new_nick: String,
+) -> Result<()> {
+    let old_nick = match prefix.parse().nick {
+        Some(nick) => nick.to_owned(),
+        None => return Ok(()),
+    };
+
+    if !state.is_own_nick(server_id, &old_nick)? {
+        return Ok(());
+    }
+
+    info!(
+        "[{}] My nickname has changed from {:?} to {:?}.",
+        state.server_socket_addr_dbg_string(server_id),
+        old_nick,
+        new_nick
+    );
+
+    update_prefix_info(
+        state,
+        server_id,
+        &MsgPrefix {
+            nick: Some(&new_nick),
+            user: None,
+            host: None,
+        },
+    )
+}
+
+/// Handles a `JOIN` message. If the user joining the channel(s) is the bot itself, sends each
+/// channel's configured greeting, if any, to that channel.
+fn handle_join(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    prefix: OwningMsgPrefix,
+    channels: &str,
+) -> Result<()> {
+    let is_own_join = match prefix.parse().nick {
+        Some(nick) => state.is_own_nick(server_id, nick)?,
+        None => false,
+    };
+
+    if !is_own_join {
+        return Ok(());
+    }
+
+    for channel in channels.split(',') {
+        let channel_id = match state.channel_id(MsgDest {
+            server_id,
+            target: channel,
+        })? {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let greeting = match state
+            .channel_config(&channel_id)
+            .and_then(|cfg| cfg.greeting.as_ref())
+        {
+            Some(greeting) => greeting,
+            None => continue,
+        };
+
+        let dest = MsgDest {
+            server_id,
+            target: channel,
+        };
+
+        push_to_outbox(outbox, server_id, state.compose_msg(dest, "", greeting)?);
+    }
 
-        match thread_spawn_result {
-            Ok(thread::JoinHandle { .. }) => Ok(()),
-            Err(e) => Err(ErrorKind::ThreadSpawnFailure(e).into()),
+    Ok(())
+}
+
+/// Handles an `INVITE` message, joining the channel to which the bot has been invited if the
+/// configuration says to accept the invitation.
+fn handle_invite(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    prefix: OwningMsgPrefix,
+    channel: String,
+) -> Result<()> {
+    let server_dbg = state.server_socket_addr_dbg_string(server_id);
+
+    if !state.config.accept_invites {
+        debug!("[{}] Ignoring invitation to {:?}.", server_dbg, channel);
+        return Ok(());
+    }
+
+    if state.config.accept_invites_only_from_admins && !state.have_admin(prefix.parse())? {
+        debug!(
+            "[{}] Ignoring invitation to {:?} from non-administrator {:?}.",
+            server_dbg,
+            channel,
+            prefix.as_str()
+        );
+        return Ok(());
+    }
+
+    let channel = match ChannelName::new(channel.as_str()) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("[{}] Ignoring invitation: {}", server_dbg, e);
+            return Ok(());
         }
+    };
+
+    let already_joined = state
+        .joined_channels(server_id)?
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|c| ChannelName::new(c.as_str()).ok())
+        .any(|c| c == channel);
+
+    if already_joined {
+        debug!(
+            "[{}] Ignoring invitation to {}, which I already am in.",
+            server_dbg, channel
+        );
+        return Ok(());
+    }
+
+    {
+        let mut invite_joins = state
+            .invite_joins
+            .lock_clean("the times of invitation joins")?;
+
+        let invite_joins = invite_joins
+            .entry(server_id)
+            .or_insert_with(ChannelMap::new);
+
+        // Forget joins long enough ago that they no longer matter, so that this map doesn't grow
+        // without bound.
+        invite_joins.retain(|_, t| t.elapsed() < INVITE_REJOIN_INTERVAL);
+
+        if invite_joins.contains_key(&channel) {
+            warn!(
+                "[{}] Ignoring invitation to {}, which I recently joined on invitation.",
+                server_dbg, channel
+            );
+            return Ok(());
+        }
+
+        invite_joins.insert(channel.clone(), Instant::now());
+    }
+
+    info!(
+        "[{}] Joining {} on invitation from {:?}.",
+        server_dbg,
+        channel,
+        prefix.as_str()
+    );
+
+    push_to_outbox(
+        outbox,
+        server_id,
+        LibReaction::RawMsg(aatxe::Command::JOIN(channel.to_string(), None, None).into()),
+    );
+
+    Ok(())
+}
+
+/// Handles a `CAP` message, keeping track of which IRCv3 capabilities the server has enabled for
+/// the bot's connection.
+///
+/// A `CAP ACK` lists capabilities that the server has enabled, or, if prefixed with `-`, disabled,
+/// at the bot's request; a `CAP DEL` lists capabilities that the server no longer offers. A
+/// `CAP NAK` means that the server rejected the bot's request as a whole, so nothing changes.
+fn handle_cap(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    subcommand: CapSubCommand,
+    caps: &str,
+) -> Result<()> {
+    match subcommand {
+        CapSubCommand::ACK | CapSubCommand::DEL => {
+            let enabled_caps = {
+                let mut server = state.write_server(server_id)?;
+
+                for cap in caps.split_whitespace() {
+                    if subcommand == CapSubCommand::DEL || cap.starts_with('-') {
+                        server.caps.remove(cap.trim_start_matches('-'));
+                    } else {
+                        server.caps.insert(cap.to_owned());
+                    }
+                }
+
+                server.caps.clone()
+            };
+
+            debug!(
+                "[{}] Enabled IRCv3 capabilities: {:?}",
+                state.server_socket_addr_dbg_string(server_id),
+                enabled_caps
+            );
+        }
+        CapSubCommand::NAK => warn!(
+            "[{}] Server refused to change IRCv3 capabilities: {:?}",
+            state.server_socket_addr_dbg_string(server_id),
+            caps
+        ),
+        _ => {}
     }
+
+    sasl::handle_cap(state, server_id, outbox, subcommand, caps)
+}
+
+/// Handles an `RPL_WELCOME` message, the first argument of which is the nickname by which the
+/// server knows the bot. This may differ from the configured nickname if that nickname was
+/// unavailable and an alternate nickname was used.
+fn handle_001(state: &State, server_id: ServerId, args: Vec<String>) -> Result<()> {
+    state.write_server(server_id)?.connect_time = Some(Instant::now());
+
+    let nick = match args.first() {
+        Some(nick) if !nick.is_empty() => nick,
+        _ => return Ok(()),
+    };
+
+    if *nick != state.nick(server_id)? {
+        info!(
+            "[{}] Registered with the nickname {:?}.",
+            state.server_socket_addr_dbg_string(server_id),
+            nick
+        );
+    }
+
+    update_prefix_info(
+        state,
+        server_id,
+        &MsgPrefix {
+            nick: Some(nick),
+            user: None,
+            host: None,
+        },
+    )
 }
 
 fn handle_user_modes_change(
@@ -478,7 +1467,7 @@ fn handle_user_mode_change(
         mode = mode
     );
 
-    match (nick == state.nick(server_id)?, mode) {
+    match (state.is_own_nick(server_id, nick)?, mode) {
         (true, aatxe::Mode::Plus(aatxe::UserMode::Unknown(ch), _))
             if Some(ch) == state.get_server_config(server_id)?.await_registration_mode =>
         {
@@ -586,22 +1575,119 @@ fn update_prefix_info(state: &State, _server_id: ServerId, prefix: &MsgPrefix) -
     Ok(())
 }
 
+/// Handles an `RPL_WHOISUSER` message, the arguments of which are the bot's own nickname and the
+/// nickname, username, and hostname of the user queried. If the user queried is the bot itself,
+/// its stored message prefix is updated accordingly.
+fn handle_whois_user(state: &State, server_id: ServerId, args: &[String]) -> Result<()> {
+    match (args.get(1), args.get(2), args.get(3)) {
+        (Some(nick), Some(user), Some(host)) if state.is_own_nick(server_id, nick)? => {
+            update_prefix_info(
+                state,
+                server_id,
+                &MsgPrefix {
+                    nick: Some(nick),
+                    user: Some(user),
+                    host: Some(host),
+                },
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Repeatedly waits for the `prefix update interval` set in the configuration and then asks the
+/// server with the given `ServerId` for the bot's message prefix, so that changes in the prefix
+/// (e.g., from a hostname cloak applied after connection) are eventually noticed.
+pub(super) fn update_prefix_periodically(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+) -> Result<()> {
+    let interval = state.config.prefix_update_interval;
+
+    loop {
+        thread::sleep(interval);
+
+        // The server won't relay messages to the bot until it has welcomed the bot.
+        if state.read_server(server_id)?.connect_time.is_none() {
+            continue;
+        }
+
+        push_to_outbox(
+            outbox,
+            server_id,
+            send_msg_prefix_update_request(state, server_id)?,
+        );
+    }
+}
+
 fn handle_004(state: &State, server_id: ServerId) -> Result<LibReaction<Message>> {
     // The server has finished sending the protocol-mandated welcome messages.
 
     send_msg_prefix_update_request(state, server_id)
 }
 
-// TODO: Run `send_msg_prefix_update_request` periodically.
+/// Returns a message that will cause the server to tell the bot its message prefix: either a
+/// message from the bot to itself or, if `learn prefix by self-message` is disabled in the
+/// configuration, a `WHOIS` query about the bot.
 fn send_msg_prefix_update_request(
     state: &State,
     server_id: ServerId,
 ) -> Result<LibReaction<Message>> {
-    Ok(LibReaction::RawMsg(
-        aatxe::Command::PRIVMSG(
-            state.nick(server_id)?.to_owned(),
-            UPDATE_MSG_PREFIX_STR.to_owned(),
-        )
-        .into(),
-    ))
+    let nick = state.nick(server_id)?.to_owned();
+
+    let cmd = if state.config.learn_prefix_by_self_msg {
+        aatxe::Command::PRIVMSG(nick, UPDATE_MSG_PREFIX_STR.to_owned())
+    } else {
+        aatxe::Command::WHOIS(None, nick)
+    };
+
+    Ok(LibReaction::RawMsg(cmd.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_long_non_ascii_word() {
+        let msg = "é".repeat(100);
+
+        let lines = split_msg(&msg, 15).collect::<Vec<_>>();
+
+        assert_eq!(lines.concat(), msg);
+        assert!(lines.iter().all(|line| line.len() < 15));
+    }
+
+    #[test]
+    fn raw_msg_cannot_smuggle_commands() {
+        assert!(parse_raw_msg("PRIVMSG #x :hi".into()).is_ok());
+        assert!(parse_raw_msg("PRIVMSG #x :hi\r\nKICK #x victim".into()).is_err());
+        assert!(parse_raw_msg("PRIVMSG #x :hi\nKICK #x victim".into()).is_err());
+        assert!(parse_raw_msg("PRIVMSG #x :hi\0".into()).is_err());
+    }
+
+    #[test]
+    fn kick_and_mode_params_cannot_smuggle_commands() {
+        assert!(mk_kick("#x", "victim", Some("bye")).is_ok());
+        assert!(mk_kick("#x", "victim\r\nQUIT", None).is_err());
+        assert!(mk_kick("#x", "victim", Some("bye\r\nQUIT")).is_err());
+        assert!(mk_kick("#x", "victim", Some("bye\0")).is_err());
+        assert!(mk_kick("x", "victim", None).is_err());
+
+        assert!(mk_mode("#x", "+o", &["someone"]).is_ok());
+        assert!(mk_mode("someone", "+i", &[] as &[&str]).is_ok());
+        assert!(mk_mode("#x", "+o", &["someone\r\nQUIT"]).is_err());
+        assert!(mk_mode("#x", "+o", &["someone else"]).is_err());
+        assert!(mk_mode("#x", "+o\n", &["someone"]).is_err());
+        assert!(mk_mode("some\none", "+i", &[] as &[&str]).is_err());
+    }
+
+    quickcheck! {
+        fn split_msg_respects_limit(msg: String, limit: usize) -> bool {
+            let limit = limit % 64 + 5;
+
+            split_msg(&msg, limit).all(|line| line.len() < limit)
+        }
+    }
 }
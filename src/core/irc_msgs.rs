@@ -1,6 +1,8 @@
 use super::Result;
 use super::ServerId;
+use std::cmp::Ordering;
 use std::fmt;
+use util::irc::case_insensitive_str_cmp;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct MsgDest<'a> {
@@ -23,7 +25,7 @@ pub struct MsgMetadata<'a> {
     pub prefix: MsgPrefix<'a>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct OwningMsgPrefix {
     backing: String,
 }
@@ -46,26 +48,73 @@ fn prefix_from_pircolate<'a>(
     }
 }
 
-pub(super) fn is_msg_to_nick(target: &str, msg: &str, nick: &str) -> bool {
-    target == nick
-        || msg == nick
-        || (msg.starts_with(nick)
-            && (msg.find(|c: char| [':', ','].contains(&c)) == Some(nick.len())))
+/// If `msg` consists of the given nickname, or begins with it followed by `:` or `,` (comparing
+/// nicknames case-insensitively), returns the rest of the message.
+fn strip_addressee<'msg>(msg: &'msg str, nick: &str) -> Option<&'msg str> {
+    let is_separator = |c: char| [':', ','].contains(&c);
+
+    let (addressee, rest) = match msg.find(is_separator) {
+        Some(idx) => msg.split_at(idx),
+        None => (msg, ""),
+    };
+
+    if case_insensitive_str_cmp(addressee, nick) == Ordering::Equal {
+        Some(rest.trim_start_matches(is_separator).trim())
+    } else {
+        None
+    }
+}
+
+/// Returns whether `msg` is addressed to the bot, whose nickname is `nick`, either by nickname or
+/// by being sent directly to the bot. If `pm_requires_prefix` is true, a message sent directly to
+/// the bot counts only if it's addressed to the bot by nickname.
+pub(super) fn is_msg_to_nick(
+    target: &str,
+    msg: &str,
+    nick: &str,
+    pm_requires_prefix: bool,
+) -> bool {
+    parse_msg_to_nick(msg, target, nick, pm_requires_prefix).is_some()
 }
 
+/// If `text` is addressed to the bot, whose nickname is `nick` (see `is_msg_to_nick`), returns
+/// the message without any leading nickname by which it was addressed.
 pub(super) fn parse_msg_to_nick<'msg>(
     text: &'msg str,
     target: &str,
     nick: &str,
+    pm_requires_prefix: bool,
 ) -> Option<&'msg str> {
-    if is_msg_to_nick(target, text, nick) {
-        Some(
-            text.trim_start_matches(nick)
-                .trim_start_matches(|c: char| [':', ','].contains(&c))
-                .trim(),
-        )
-    } else {
-        None
+    match strip_addressee(text, nick) {
+        Some(rest) => Some(rest),
+        None if case_insensitive_str_cmp(target, nick) == Ordering::Equal
+            && !pm_requires_prefix =>
+        {
+            Some(text.trim())
+        }
+        None => None,
+    }
+}
+
+/// If `text` is a message sent to a channel (rather than directly to the bot, unless
+/// `pm_requires_prefix` is true) that begins with the given command prefix, returns the rest of the
+/// message.
+pub(super) fn parse_prefixed_cmd<'msg>(
+    text: &'msg str,
+    target: &str,
+    nick: &str,
+    cmd_prefix: Option<&str>,
+    pm_requires_prefix: bool,
+) -> Option<&'msg str> {
+    match cmd_prefix {
+        Some(cmd_prefix)
+            if (case_insensitive_str_cmp(target, nick) != Ordering::Equal
+                || pm_requires_prefix)
+                && text.starts_with(cmd_prefix) =>
+        {
+            Some(text[cmd_prefix.len()..].trim())
+        }
+        _ => None,
     }
 }
 
@@ -142,6 +191,10 @@ impl OwningMsgPrefix {
         parse_prefix(&self.backing)
     }
 
+    pub fn as_str(&self) -> &str {
+        &self.backing
+    }
+
     /// Returns the exact length of the message prefix.
     pub fn len(&self) -> usize {
         self.backing.len()
@@ -172,3 +225,50 @@ impl OwningMsgPrefix {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pm_cmd_parsing() {
+        let parse = |text, pm_requires_prefix| {
+            parse_msg_to_nick(text, "bot", "bot", pm_requires_prefix)
+                .or_else(|| parse_prefixed_cmd(text, "bot", "bot", Some("!"), pm_requires_prefix))
+        };
+
+        assert_eq!(parse("ping", false), Some("ping"));
+        assert_eq!(parse("!ping", false), Some("!ping"));
+        assert_eq!(parse("ping", true), None);
+        assert_eq!(parse("!ping", true), Some("ping"));
+        assert_eq!(parse("bot: ping", true), Some("ping"));
+    }
+
+    #[test]
+    fn channel_cmd_parsing() {
+        let parse = |text| {
+            parse_msg_to_nick(text, "#chan", "bot", false)
+                .or_else(|| parse_prefixed_cmd(text, "#chan", "bot", Some("!"), false))
+        };
+
+        assert_eq!(parse("ping"), None);
+        assert_eq!(parse("!ping"), Some("ping"));
+        assert_eq!(parse("bot, ping"), Some("ping"));
+        assert_eq!(parse("Bot: ping"), Some("ping"));
+        assert_eq!(parse("BOT"), Some(""));
+        assert_eq!(parse("botany: ping"), None);
+    }
+
+    #[test]
+    fn pm_target_is_casefolded() {
+        assert_eq!(parse_msg_to_nick("ping", "Bot", "bot", false), Some("ping"));
+        assert_eq!(
+            parse_msg_to_nick("botany", "bot", "bot", false),
+            Some("botany")
+        );
+        assert_eq!(
+            parse_prefixed_cmd("!ping", "BOT", "bot", Some("!"), false),
+            None
+        );
+    }
+}
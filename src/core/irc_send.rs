@@ -18,10 +18,15 @@ pub(super) type OutboxPort = crossbeam_channel::Sender<OutboxRecord>;
 
 #[derive(Debug)]
 pub(super) struct OutboxRecord {
-    server_id: ServerId,
-    output: LibReaction<Message>,
+    pub(super) server_id: ServerId,
+    pub(super) output: LibReaction<Message>,
 }
 
+/// Queues the given output to be sent to the given server.
+///
+/// Each call enqueues a single record, and the outbox is drained by a single thread, so the
+/// messages of a `LibReaction::Multi` are sent contiguously, in order, without messages from other
+/// reactions being interleaved among them.
 pub(super) fn push_to_outbox<O>(outbox_sender: &OutboxPort, server_id: ServerId, output: O)
 where
     O: Into<Option<LibReaction<Message>>>,
@@ -55,6 +60,9 @@ pub(super) fn send_main(
     // [2018-01-08 - c74d] At least with `crossbeam_channel`'s MPSC queue implementation, this loop
     // will run until — and the sending thread will exit when — all receiving (and
     // command-handling, etc.) threads have exited. Not having to implement that myself is nice.
+    //
+    // As the `State` now holds a sender of its own (for `State::send_to_server`), this loop runs
+    // for as long as the process does.
     for record in outbox_receiver.iter() {
         let OutboxRecord {
             server_id, output, ..
@@ -78,15 +86,38 @@ pub(super) fn send_main(
             Some(client) => client.clone(),
             None => {
                 warn!(
-                    "Can't send to unknown server {server_id:?}. Discarding {output:?}.",
-                    server_id = server_id,
+                    "Can't send to unknown server {server}. Discarding {output:?}.",
+                    server = state.server_name_dbg_string(server_id),
                     output = output
                 );
                 continue;
             }
         };
 
-        send_reaction(&state, &aatxe_client, thread_label, output)
+        #[cfg(feature = "metrics")]
+        state.metrics.record_msgs_sent(server_id, &output);
+
+        send_reaction(&state, &aatxe_client, output)
+    }
+
+    Ok(())
+}
+
+/// Sends the given output to every server to which the bot is connected, bypassing the outbox, for
+/// use when the sending thread may be unable to send it, such as when a thread's failure is to
+/// make the bot quit.
+pub(super) fn send_to_all_servers(state: &State, output: LibReaction<Message>) -> Result<()> {
+    let aatxe_clients = match state.aatxe_clients.read() {
+        Ok(map) => map,
+        Err(_) => {
+            return Err(
+                ErrorKind::LockPoisoned("the associative array of IRC connections".into()).into(),
+            );
+        }
+    };
+
+    for aatxe_client in aatxe_clients.values() {
+        send_reaction(state, aatxe_client, output.clone())
     }
 
     Ok(())
@@ -112,19 +143,14 @@ pub(super) fn process_outgoing_msg(
     }
 }
 
-fn send_reaction(
-    state: &State,
-    aatxe_client: &aatxe::IrcClient,
-    thread_label: &str,
-    reaction: LibReaction<Message>,
-) {
-    send_reaction_with_err_cb(state, aatxe_client, thread_label, reaction, |err| {
+fn send_reaction(state: &State, aatxe_client: &aatxe::IrcClient, reaction: LibReaction<Message>) {
+    send_reaction_with_err_cb(aatxe_client, reaction, |err| {
         let err_reaction = match state.handle_err_generic(err) {
             Some(r) => r,
             None => return,
         };
 
-        send_reaction_with_err_cb(state, aatxe_client, thread_label, err_reaction, |err| {
+        send_reaction_with_err_cb(aatxe_client, err_reaction, |err| {
             error!(
                 "Encountered error {:?} while handling error; stopping error handling to avoid \
                  potential infinite recursion.",
@@ -135,9 +161,7 @@ fn send_reaction(
 }
 
 fn send_reaction_with_err_cb<ErrCb>(
-    state: &State,
     aatxe_client: &aatxe::IrcClient,
-    thread_label: &str,
     reaction: LibReaction<Message>,
     err_cb: ErrCb,
 ) where
@@ -149,9 +173,32 @@ fn send_reaction_with_err_cb<ErrCb>(
             Err(e) => err_cb(e.into()),
         },
         LibReaction::Multi(reactions) => {
-            for reaction in reactions {
-                send_reaction(state, aatxe_client, thread_label, reaction)
+            // Send all the messages before handling any errors, so that no error report is sent
+            // between the messages of a multi-message reaction.
+            let mut errs = Vec::new();
+
+            for msg in flatten_reactions(reactions) {
+                if let Err(e) = aatxe_client.send(msg) {
+                    errs.push(e);
+                }
             }
+
+            for e in errs {
+                err_cb(e.into())
+            }
+        }
+    }
+}
+
+fn flatten_reactions(reactions: Vec<LibReaction<Message>>) -> Vec<Message> {
+    let mut msgs = Vec::with_capacity(reactions.len());
+
+    for reaction in reactions {
+        match reaction {
+            LibReaction::RawMsg(msg) => msgs.push(msg),
+            LibReaction::Multi(reactions) => msgs.extend(flatten_reactions(reactions)),
         }
     }
+
+    msgs
 }
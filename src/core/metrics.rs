@@ -0,0 +1,404 @@
+//! Counters of the bot's activity, which can be served over HTTP in the [Prometheus text exposition
+//! format] for monitoring.
+//!
+//! This module is compiled only if the Cargo feature `metrics` is enabled, and the counters are
+//! served only if the configuration field `metrics address` is set.
+//!
+//! [Prometheus text exposition format]: <https://prometheus.io/docs/instrumenting/exposition_formats/>
+
+use super::LibReaction;
+use super::Result;
+use super::ServerId;
+use super::State;
+use irc::proto::Message;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long to wait for an HTTP client to send its request and accept the response, in all
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The greatest length, in bytes, of a request's request line and headers together
+const MAX_REQUEST_LEN: u64 = 8 * 1024;
+
+/// The greatest number of requests that are handled at once; further connections are closed
+/// without a response.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// The path at which the metrics are served
+const METRICS_PATH: &str = "/metrics";
+
+#[derive(Debug, Default)]
+pub(super) struct Metrics {
+    msgs_received: Mutex<BTreeMap<ServerId, u64>>,
+
+    msgs_sent: Mutex<BTreeMap<ServerId, u64>>,
+
+    cmd_runs: Mutex<BTreeMap<Cow<'static, str>, u64>>,
+
+    errors: AtomicUsize,
+
+    silent_reactions: AtomicUsize,
+}
+
+impl Metrics {
+    pub(super) fn record_msg_received(&self, server_id: ServerId) {
+        increment(&self.msgs_received, server_id, 1)
+    }
+
+    pub(super) fn record_msgs_sent(&self, server_id: ServerId, reaction: &LibReaction<Message>) {
+        increment(&self.msgs_sent, server_id, msg_qty(reaction))
+    }
+
+    pub(super) fn record_cmd_run(&self, cmd_name: Cow<'static, str>) {
+        increment(&self.cmd_runs, cmd_name, 1)
+    }
+
+    pub(super) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_silent_reaction(&self) {
+        self.silent_reactions.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn increment<K>(counters: &Mutex<BTreeMap<K, u64>>, key: K, qty: u64)
+where
+    K: Ord,
+{
+    // A poisoned lock means only that a count may have been lost, which is no reason to fail.
+    let mut counters = counters.lock().unwrap_or_else(PoisonError::into_inner);
+
+    *counters.entry(key).or_insert(0) += qty;
+}
+
+fn msg_qty(reaction: &LibReaction<Message>) -> u64 {
+    match *reaction {
+        LibReaction::RawMsg(_) => 1,
+        LibReaction::Multi(ref reactions) => reactions.iter().map(msg_qty).sum(),
+    }
+}
+
+/// A TCP stream whose reads and writes fail once a deadline has passed
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    fn time_left(&self) -> io::Result<Duration> {
+        let now = Instant::now();
+
+        if now < self.deadline {
+            Ok(self.deadline - now)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "The HTTP client took too long.",
+            ))
+        }
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.time_left()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.time_left()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Serves the bot's metrics over HTTP at the given address, handling each request on a thread of
+/// its own, until the listening socket fails.
+pub(super) fn serve(state: Arc<State>, addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let active_request_count = Arc::new(AtomicUsize::new(0));
+
+    info!("Serving metrics at <http://{}{}>.", addr, METRICS_PATH);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!("Failed to accept a request for metrics: {}", e);
+                continue;
+            }
+        };
+
+        if active_request_count.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_REQUESTS {
+            active_request_count.fetch_sub(1, Ordering::SeqCst);
+            debug!("Too many requests for metrics are being handled; dropping a connection.");
+            continue;
+        }
+
+        let state = state.clone();
+        let active_request_count_alias = active_request_count.clone();
+
+        let thread_spawn_result = thread::Builder::new()
+            .name("metrics request".into())
+            .spawn(move || {
+                if let Err(e) = respond(&state, stream) {
+                    debug!("Failed to respond to a request for metrics: {}", e);
+                }
+                active_request_count_alias.fetch_sub(1, Ordering::SeqCst);
+            });
+
+        if let Err(e) = thread_spawn_result {
+            active_request_count.fetch_sub(1, Ordering::SeqCst);
+            debug!("Failed to spawn a thread to handle a request for metrics: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Responds to the HTTP request, if any, on the given stream, which must be sent and answered
+/// within `REQUEST_TIMEOUT`.
+fn respond(state: &State, stream: TcpStream) -> Result<()> {
+    let mut stream = DeadlineStream {
+        stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    };
+
+    let mut request_line = String::new();
+    let request_is_too_long;
+
+    {
+        let mut reader = BufReader::new((&mut stream).take(MAX_REQUEST_LEN));
+
+        reader.read_line(&mut request_line)?;
+
+        // Skip the request headers, which end with an empty line.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > "\r\n".len() {
+            header.clear();
+        }
+
+        request_is_too_long = reader.get_ref().limit() == 0;
+    }
+
+    let (status, body) = match request_line.split_whitespace().nth(1) {
+        _ if request_is_too_long => (
+            "431 Request Header Fields Too Large",
+            b"The request is too long.\n".to_vec(),
+        ),
+        Some(METRICS_PATH) => ("200 OK", render(state)?),
+        _ => (
+            "404 Not Found",
+            format!("Metrics are served at {}.\n", METRICS_PATH).into_bytes(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n",
+        status,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+
+    Ok(())
+}
+
+/// Renders the bot's metrics in the Prometheus text exposition format.
+fn render(state: &State) -> Result<Vec<u8>> {
+    let metrics = &state.metrics;
+    let mut out = Vec::new();
+
+    let by_server_name = |counters: &Mutex<BTreeMap<ServerId, u64>>| {
+        counters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(&server_id, &count)| (state.server_name_dbg_string(server_id), count))
+            .collect::<Vec<_>>()
+    };
+
+    write_family(
+        &mut out,
+        "irc_messages_received_total",
+        "Messages received from IRC servers.",
+        "counter",
+        "server",
+        by_server_name(&metrics.msgs_received),
+    )?;
+
+    write_family(
+        &mut out,
+        "irc_messages_sent_total",
+        "Messages sent to IRC servers.",
+        "counter",
+        "server",
+        by_server_name(&metrics.msgs_sent),
+    )?;
+
+    write_family(
+        &mut out,
+        "botcmd_runs_total",
+        "Bot commands run, by command name.",
+        "counter",
+        "command",
+        metrics
+            .cmd_runs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(name, &count)| (name.clone(), count))
+            .collect::<Vec<_>>(),
+    )?;
+
+    write_family(
+        &mut out,
+        "bot_errors_total",
+        "Errors passed to the bot's error handler.",
+        "counter",
+        "",
+        Some(("", metrics.errors.load(Ordering::Relaxed) as u64)),
+    )?;
+
+    write_family(
+        &mut out,
+        "bot_silent_reactions_total",
+        "Reactions that were logged rather than sent.",
+        "counter",
+        "",
+        Some(("", metrics.silent_reactions.load(Ordering::Relaxed) as u64)),
+    )?;
+
+    write_family(
+        &mut out,
+        "irc_server_connected",
+        "Whether the bot is connected to each IRC server (1) or not (0).",
+        "gauge",
+        "server",
+        state
+            .server_ids()
+            .into_iter()
+            .map(|server_id| {
+                let connected = state.server_uptime(server_id)?.is_some();
+                Ok((state.server_name_dbg_string(server_id), connected as u64))
+            })
+            .collect::<Result<Vec<_>>>()?,
+    )?;
+
+    Ok(out)
+}
+
+/// Writes a metric family with the given samples, each of which is labeled with the given label
+/// name and its value, unless the label name is empty.
+fn write_family<I, L>(
+    out: &mut Vec<u8>,
+    name: &str,
+    help: &str,
+    kind: &str,
+    label_name: &str,
+    samples: I,
+) -> Result<()>
+where
+    I: IntoIterator<Item = (L, u64)>,
+    L: AsRef<str>,
+{
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} {}", name, kind)?;
+
+    for (label_value, value) in samples {
+        if label_name.is_empty() {
+            writeln!(out, "{} {}", name, value)?;
+        } else {
+            writeln!(
+                out,
+                "{}{{{}=\"{}\"}} {}",
+                name,
+                label_name,
+                escape_label_value(label_value.as_ref()),
+                value
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn escape_label_value(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_harness::TestBot;
+    use super::super::test_harness::MINIMAL_CONFIG;
+    use super::*;
+    use modules;
+
+    #[test]
+    fn oversized_request() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server_end, _) = listener.accept().unwrap();
+
+        // A request line of this length, which is never terminated, must not be read in full.
+        client
+            .write_all(&vec![b'a'; MAX_REQUEST_LEN as usize])
+            .unwrap();
+        respond(bot.state(), server_end).unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 431 "), "{:?}", response);
+    }
+
+    #[test]
+    fn family_format() {
+        let mut out = Vec::new();
+
+        write_family(
+            &mut out,
+            "botcmd_runs_total",
+            "Bot commands run.",
+            "counter",
+            "command",
+            vec![("quote", 3), ("say \"hi\"\\", 1)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# HELP botcmd_runs_total Bot commands run.\n\
+             # TYPE botcmd_runs_total counter\n\
+             botcmd_runs_total{command=\"quote\"} 3\n\
+             botcmd_runs_total{command=\"say \\\"hi\\\"\\\\\"} 1\n"
+        );
+    }
+}
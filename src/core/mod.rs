@@ -3,16 +3,20 @@ pub use self::bot_cmd::BotCmdAuthLvl;
 pub use self::bot_cmd::BotCmdResult;
 pub use self::bot_cmd::BotCommand;
 pub use self::config::Config;
+pub use self::config::ConfigFormat;
 pub use self::config::IntoConfig;
 pub use self::err::Error;
 pub use self::err::ErrorKind;
 pub use self::err::Result;
 pub use self::handler::BotCmdHandler;
+pub use self::handler::DeferredHandler;
 pub use self::handler::ErrorHandler;
 pub use self::handler::HandlerContext;
 pub use self::handler::ModuleFeatureRef;
 pub use self::handler::ModuleLoadHandler;
+pub use self::handler::RawMsgHandler;
 pub use self::handler::TriggerHandler;
+pub use self::ignore::IgnoreMask;
 use self::irc_msgs::parse_msg_to_nick;
 pub use self::irc_msgs::MsgDest;
 pub use self::irc_msgs::MsgMetadata;
@@ -22,9 +26,12 @@ use self::irc_send::push_to_outbox;
 use self::misc_traits::GetDebugInfo;
 pub use self::modl_sys::mk_module;
 pub use self::modl_sys::Module;
-use self::modl_sys::ModuleFeatureInfo;
-use self::modl_sys::ModuleInfo;
+pub use self::modl_sys::ModuleFeatureInfo;
+pub use self::modl_sys::ModuleFeatureKind;
+pub use self::modl_sys::ModuleInfo;
+pub use self::module_store::ModuleStore;
 use self::modl_sys::ModuleLoadMode;
+pub use self::raw_handler::RawHandler;
 pub use self::reaction::ErrorReaction;
 use self::reaction::LibReaction;
 pub use self::reaction::Reaction;
@@ -41,14 +48,20 @@ use rand::StdRng;
 use std::borrow::Borrow;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::RwLock;
 use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 use util;
+use util::irc::ChannelMap;
 use uuid::Uuid;
 
 pub(crate) mod bot_cmd;
@@ -56,15 +69,25 @@ pub(crate) mod bot_cmd;
 mod config;
 mod err;
 mod handler;
+mod ignore;
 mod irc_comm;
 mod irc_msgs;
 mod irc_send;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod misc_traits;
 mod modl_sys;
+mod module_store;
 mod pkg_info;
+mod raw_handler;
 mod reaction;
+mod sasl;
+mod scheduler;
 mod state;
+#[cfg(test)]
+pub(crate) mod test_harness;
 mod trigger;
+mod whois;
 
 const THREAD_NAME_FAIL: &str = "This thread is unnamed?! We specifically gave it a name; what \
                                 happened?!";
@@ -79,24 +102,57 @@ pub struct State {
 
     addressee_suffix: Cow<'static, str>,
 
+    cmd_cooldowns: Mutex<HashMap<(Cow<'static, str>, String), Instant>>,
+
+    /// How many times each command has been run since the bot started or these counts were reset
+    cmd_usage: Mutex<HashMap<Cow<'static, str>, u64>>,
+
     commands: BTreeMap<Cow<'static, str>, BotCommand>,
 
     config: config::Config,
 
+    /// How many times `reload_config` has changed the configuration
+    config_generation: AtomicUsize,
+
+    disabled_modules: RwLock<BTreeSet<Cow<'static, str>>>,
+
     #[debug(skip)]
     error_handler: Arc<ErrorHandler>,
 
+    ignore_list: RwLock<Vec<IgnoreMask>>,
+
+    /// When the bot last joined each channel in response to an invitation
+    invite_joins: Mutex<BTreeMap<ServerId, ChannelMap<Instant>>>,
+
     module_data_path: PathBuf,
 
     modules: BTreeMap<Cow<'static, str>, Arc<Module>>,
 
+    /// The contents of the modules' key–value stores that have been used, by module name
+    module_stores: Mutex<BTreeMap<String, module_store::StoreMap>>,
+
+    #[cfg(feature = "metrics")]
+    metrics: metrics::Metrics,
+
     // TODO: This is server-specific.
     msg_prefix: RwLock<OwningMsgPrefix>,
 
+    /// The sending end of the queue of messages to be sent to servers
+    outbox: irc_send::OutboxPort,
+
+    pending_whois: Mutex<Vec<whois::PendingWhois>>,
+
+    raw_handlers: Vec<RawHandler>,
+
     rng: Mutex<StdRng>,
 
+    #[debug(skip)]
+    scheduler: scheduler::Scheduler,
+
     servers: BTreeMap<ServerId, RwLock<Server>>,
 
+    start_time: Instant,
+
     triggers: BTreeMap<TriggerPriority, Vec<Trigger>>,
 }
 
@@ -107,9 +163,13 @@ struct Server {
     socket_addr_string: String,
     motd_finished: bool,
     registration_mode_obtained: bool,
+    connect_time: Option<Instant>,
+
+    /// The IRCv3 capabilities that the server has acknowledged enabling for the bot's connection
+    caps: BTreeSet<String>,
 }
 
-#[derive(Copy, Clone, CustomDebug, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Clone, CustomDebug, Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub struct ServerId {
     #[debug(with = "util::fmt::debug_uuid")]
     uuid: Uuid,
@@ -130,7 +190,7 @@ impl ServerId {
 }
 
 /// The index of a server in the list of servers in the configuration file
-#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
 struct ServerConfigIndex(u16);
 
 impl TryFrom<usize> for ServerConfigIndex {
@@ -148,6 +208,7 @@ impl State {
         config: config::Config,
         module_data_path: PathBuf,
         error_handler: ErrF,
+        outbox: irc_send::OutboxPort,
     ) -> Result<State>
     where
         ErrF: ErrorHandler,
@@ -159,15 +220,32 @@ impl State {
 
         Ok(State {
             aatxe_clients: Default::default(),
-            addressee_suffix: ": ".into(),
+            addressee_suffix: match config.addressee_suffix {
+                Some(ref suffix) => suffix.clone().into(),
+                None => ": ".into(),
+            },
+            cmd_cooldowns: Default::default(),
+            cmd_usage: Default::default(),
             commands: Default::default(),
             config: config,
+            config_generation: Default::default(),
+            disabled_modules: Default::default(),
             error_handler: Arc::new(error_handler),
+            ignore_list: Default::default(),
+            invite_joins: Default::default(),
             module_data_path,
             modules: Default::default(),
+            module_stores: Default::default(),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
             msg_prefix,
+            outbox,
+            pending_whois: Default::default(),
+            raw_handlers: Default::default(),
             rng: Mutex::new(StdRng::from_rng(EntropyRng::new())?),
+            scheduler: Default::default(),
             servers: Default::default(),
+            start_time: Instant::now(),
             triggers: Default::default(),
         })
     }
@@ -178,6 +256,9 @@ impl State {
     {
         let desc = desc.borrow();
 
+        #[cfg(feature = "metrics")]
+        self.metrics.record_error();
+
         let reaction = self.error_handler.run(err);
 
         match reaction {
@@ -237,7 +318,14 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
         }
     };
 
-    let mut state = match State::new(config, module_data_path, error_handler) {
+    let (outbox_sender, outbox_receiver) = crossbeam_channel::bounded(irc_send::OUTBOX_SIZE);
+
+    let mut state = match State::new(
+        config,
+        module_data_path,
+        error_handler,
+        outbox_sender.clone(),
+    ) {
         Ok(s) => {
             trace!("Assembled bot state.");
             s
@@ -293,6 +381,8 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
             socket_addr_string,
             motd_finished: false,
             registration_mode_obtained: false,
+            connect_time: None,
+            caps: Default::default(),
         };
 
         match servers.insert(server_id, RwLock::new(server)) {
@@ -300,8 +390,9 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
             Some(other_server) => {
                 error!(
                     "This shouldn't happen, but there was already a server registered with ID \
-                     {server_id:?}: {other_server:?}",
+                     {server_id:?} (for server {server_name}): {other_server:?}",
                     server_id = server_id,
+                    server_name = state.server_name_dbg_string(server_id),
                     other_server = other_server.read().expect(LOCK_EARLY_POISON_FAIL),
                 );
                 return;
@@ -325,8 +416,6 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
         }
     };
 
-    let (outbox_sender, outbox_receiver) = crossbeam_channel::bounded(irc_send::OUTBOX_SIZE);
-
     spawn_thread(
         &state,
         "*".into(),
@@ -335,6 +424,10 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
         |state| irc_send::send_main(state, outbox_receiver),
     );
 
+    if let Some(ref addr) = state.config.metrics_address {
+        serve_metrics(&state, addr);
+    }
+
     for (&server_id, server) in &state.servers {
         let server = server.read().expect(LOCK_EARLY_POISON_FAIL);
 
@@ -344,19 +437,41 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
 
         let aatxe_client = match aatxe_reactor.prepare_client_and_connect(&server.aatxe_config) {
             Ok(client) => {
-                trace!("Connected to server {:?}.", server.socket_addr_string);
+                trace!(
+                    "Connected to server {} ({:?}).",
+                    state.server_name_dbg_string(server_id),
+                    server.socket_addr_string
+                );
                 client
             }
             Err(err) => {
                 error!(
-                    "Failed to connect to server {:?}: {} ({:?})",
-                    server.socket_addr_string, err, err,
+                    "Failed to connect to server {} ({:?}): {} ({:?})",
+                    state.server_name_dbg_string(server_id),
+                    server.socket_addr_string,
+                    err,
+                    err,
                 );
                 continue;
             }
         };
 
-        let caps_to_request = &[aatxe::Capability::MultiPrefix];
+        let use_sasl = match state.get_server_config(server_id) {
+            Ok(cfg) => cfg.sasl.is_some(),
+            Err(e) => {
+                error!(
+                    "Failed to look up configuration of server {:?}: {}",
+                    server.socket_addr_string, e
+                );
+                continue;
+            }
+        };
+
+        let caps_to_request: &[_] = if use_sasl {
+            &[aatxe::Capability::MultiPrefix, aatxe::Capability::Sasl]
+        } else {
+            &[aatxe::Capability::MultiPrefix]
+        };
 
         match aatxe_client.send_cap_req(caps_to_request) {
             Ok(()) => debug!(
@@ -375,7 +490,14 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
             }
         }
 
-        match aatxe_client.identify() {
+        let identify_result = if use_sasl {
+            // Capability negotiation will be ended once SASL authentication is complete.
+            sasl::identify_without_cap_end(&aatxe_client)
+        } else {
+            aatxe_client.identify().map_err(Into::into)
+        };
+
+        match identify_result {
             Ok(()) => debug!(
                 "recv[{}]: Sent identification sequence to server.",
                 server.socket_addr_string
@@ -401,13 +523,28 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
                 // `IrcServer` implementing `Debug`, add the other server to this message.
                 error!(
                     "This shouldn't happen, but there was already a server registered \
-                     with ID {server_id:?}!",
+                     with ID {server_id:?} (for server {server_name})!",
                     server_id = server_id,
+                    server_name = state.server_name_dbg_string(server_id),
                 );
                 return;
             }
         }
 
+        if state.config.prefix_update_interval != Duration::from_secs(0) {
+            let prefix_outbox = outbox_sender.clone();
+
+            spawn_thread(
+                &state,
+                server.socket_addr_string.clone(),
+                "prefix",
+                |addr| format!("message prefix update thread for server {:?}", addr),
+                move |state| {
+                    irc_comm::update_prefix_periodically(&state, server_id, &prefix_outbox)
+                },
+            );
+        }
+
         aatxe_reactor.register_client_with_handler(aatxe_client, move |_aatxe_client, msg| {
             handle_msg(&state_alias, server_id, &outbox_sender_clone, Ok(msg));
 
@@ -419,6 +556,8 @@ pub fn run<Cfg, ModlData, ErrF, ModlCtor, Modls>(
         Ok(()) => trace!("IRC reactor shut down normally."),
         Err(e) => error!("IRC reactor shut down abnormally: {}", e),
     }
+
+    state.shut_down_modules();
 }
 
 fn handle_msg(
@@ -427,12 +566,37 @@ fn handle_msg(
     outbox: &irc_send::OutboxPort,
     input: Result<Message>,
 ) {
+    #[cfg(feature = "metrics")]
+    state.metrics.record_msg_received(server_id);
+
     match input.and_then(|msg| irc_comm::handle_msg(&state, server_id, outbox, msg)) {
         Ok(()) => {}
         Err(e) => push_to_outbox(outbox, server_id, state.handle_err_generic(e)),
     }
 }
 
+#[cfg(feature = "metrics")]
+fn serve_metrics(state: &Arc<State>, addr: &str) {
+    let addr = addr.to_owned();
+
+    spawn_thread(
+        state,
+        addr.clone(),
+        "metrics",
+        |addr| format!("metrics server at {:?}", addr),
+        move |state| metrics::serve(state, &addr),
+    );
+}
+
+#[cfg(not(feature = "metrics"))]
+fn serve_metrics(_: &Arc<State>, addr: &str) {
+    warn!(
+        "The configuration asks for metrics to be served at {:?}, but this bot was built without \
+         the Cargo feature `metrics`.",
+        addr
+    );
+}
+
 fn spawn_thread<F, PurposeF>(
     state: &Arc<State>,
     addr: String,
@@ -453,11 +617,21 @@ fn spawn_thread<F, PurposeF>(
 
         trace!("{}: Starting....", thread_label);
 
-        match business(state_alias) {
+        match business(state_alias.clone()) {
             Ok(()) => debug!("{}: Thread exited successfully.", thread_label),
 
-            // TODO: Call `state.error_handler`.
-            Err(err) => error!("{}: Thread exited with error: {:?}", thread_label, err),
+            Err(err) => {
+                error!("{}: Thread exited with error: {:?}", thread_label, err);
+
+                // If the error handler says to quit, send the quit message to each server directly,
+                // as the failed thread may have been the one that sends queued messages. Once all
+                // the connections have been closed, the IRC reactor stops.
+                if let Some(quit) = state_alias.handle_err(err, thread_label) {
+                    if let Err(err) = irc_send::send_to_all_servers(&state_alias, quit) {
+                        error!("{}: Failed to quit: {:?}", thread_label, err);
+                    }
+                }
+            }
         }
     });
 
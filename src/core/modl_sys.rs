@@ -7,6 +7,8 @@ use super::Error;
 use super::ErrorKind;
 use super::GetDebugInfo;
 use super::ModuleLoadHandler;
+use super::RawHandler;
+use super::RawMsgHandler;
 use super::Result;
 use super::State;
 use super::Trigger;
@@ -19,7 +21,10 @@ use std;
 use std::borrow::Cow;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::Duration;
 use util;
+use util::lock::ReadLockExt;
+use util::lock::WriteLockExt;
 use uuid::Uuid;
 use yaml_rust::Yaml;
 
@@ -34,6 +39,9 @@ pub struct Module {
 
     #[debug(skip)]
     on_load: SmallVec<[Box<ModuleLoadHandler>; 1]>,
+
+    #[debug(skip)]
+    on_shutdown: SmallVec<[Box<ModuleLoadHandler>; 1]>,
 }
 
 impl PartialEq for Module {
@@ -63,6 +71,7 @@ pub struct ModuleBuilder {
     name: Cow<'static, str>,
     features: Vec<ModuleFeature>,
     on_load: SmallVec<[Box<ModuleLoadHandler>; 1]>,
+    on_shutdown: SmallVec<[Box<ModuleLoadHandler>; 1]>,
 }
 
 pub fn mk_module<'modl, S>(name: S) -> ModuleBuilder
@@ -73,6 +82,7 @@ where
         name: name.into(),
         features: Default::default(),
         on_load: Default::default(),
+        on_shutdown: Default::default(),
     }
 }
 
@@ -105,6 +115,14 @@ impl ModuleBuilder {
             .unwrap()
             .unwrap_or(Yaml::Hash(Default::default()));
 
+        let mut cooldown = None;
+
+        for attr in attrs {
+            match *attr {
+                BotCmdAttr::Cooldown(d) => cooldown = Some(d),
+            }
+        }
+
         let cmd = ModuleFeature::Command {
             name: name,
             usage_str: syntax,
@@ -112,14 +130,9 @@ impl ModuleBuilder {
             help_msg: help_msg.into(),
             auth_lvl: auth_lvl,
             handler: handler.into(),
+            cooldown,
         };
 
-        for attr in attrs {
-            match *attr {
-                // ...
-            }
-        }
-
         self.features.push(cmd);
 
         self
@@ -140,9 +153,11 @@ impl ModuleBuilder {
         S2: Into<Cow<'static, str>>,
         Attrs: IntoIterator<Item = &'attr TriggerAttr>,
     {
+        let mut always_watching = false;
+
         for attr in attrs {
-            match attr {
-                &TriggerAttr::AlwaysWatching => unimplemented!(),
+            match *attr {
+                TriggerAttr::AlwaysWatching => always_watching = true,
             }
         }
 
@@ -155,6 +170,7 @@ impl ModuleBuilder {
             help_msg: help_msg.into(),
             handler: handler.into(),
             priority,
+            always_watching,
             uuid: Uuid::new_v4(),
         };
 
@@ -163,6 +179,26 @@ impl ModuleBuilder {
         self
     }
 
+    /// Adds a handler function that will be run on every raw IRC message that the bot receives,
+    /// of whatever type, before the bot otherwise handles the message.
+    ///
+    /// This allows a module to observe and react to messages, such as `KICK` and `MODE` messages
+    /// and numeric replies, that don't invoke bot commands or triggers. See [`RawMsgHandler`] for
+    /// details.
+    ///
+    /// [`RawMsgHandler`]: <trait.RawMsgHandler.html>
+    pub fn raw_handler<S>(mut self, name: S, handler: Box<RawMsgHandler>) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        self.features.push(ModuleFeature::RawHandler {
+            name: name.into(),
+            handler: handler.into(),
+        });
+
+        self
+    }
+
     /// Sets a handler function for loading or reloading the module's configuration.
     ///
     /// The given `handler` function will be called when the module is first loaded, as well as if
@@ -177,21 +213,36 @@ impl ModuleBuilder {
         self
     }
 
+    /// Sets a handler function to be called when the bot shuts down, such as to write out any data
+    /// that the module has buffered.
+    ///
+    /// Multiple such handler functions may be set, as with [`on_load`].
+    ///
+    /// [`on_load`]: <#method.on_load>
+    pub fn on_shutdown(mut self, handler: Box<ModuleLoadHandler>) -> Self {
+        self.on_shutdown.push(handler);
+
+        self
+    }
+
     pub fn end(self) -> Module {
         let ModuleBuilder {
             name,
             mut features,
             mut on_load,
+            mut on_shutdown,
         } = self;
 
         features.shrink_to_fit();
         on_load.shrink_to_fit();
+        on_shutdown.shrink_to_fit();
 
         Module {
             name: name,
             uuid: Uuid::new_v4(),
             features: features,
             on_load,
+            on_shutdown,
         }
     }
 }
@@ -202,6 +253,12 @@ pub struct ModuleInfo {
     name: String,
 }
 
+impl ModuleInfo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 #[derive(CustomDebug)]
 enum ModuleFeature {
     Command {
@@ -217,6 +274,8 @@ enum ModuleFeature {
 
         #[debug(skip)]
         handler: Arc<BotCmdHandler>,
+
+        cooldown: Option<Duration>,
     },
     Trigger {
         name: Cow<'static, str>,
@@ -230,8 +289,16 @@ enum ModuleFeature {
 
         priority: TriggerPriority,
 
+        always_watching: bool,
+
         uuid: Uuid,
     },
+    RawHandler {
+        name: Cow<'static, str>,
+
+        #[debug(skip)]
+        handler: Arc<RawMsgHandler>,
+    },
 }
 
 impl GetDebugInfo for ModuleFeature {
@@ -243,6 +310,7 @@ impl GetDebugInfo for ModuleFeature {
             kind: match self {
                 &ModuleFeature::Command { .. } => ModuleFeatureKind::Command,
                 &ModuleFeature::Trigger { .. } => ModuleFeatureKind::Trigger,
+                &ModuleFeature::RawHandler { .. } => ModuleFeatureKind::RawHandler,
             },
         }
     }
@@ -256,10 +324,21 @@ pub struct ModuleFeatureInfo {
     kind: ModuleFeatureKind,
 }
 
+impl ModuleFeatureInfo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &ModuleFeatureKind {
+        &self.kind
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ModuleFeatureKind {
     Command,
     Trigger,
+    RawHandler,
 }
 
 impl ModuleFeature {
@@ -267,6 +346,7 @@ impl ModuleFeature {
         match self {
             &ModuleFeature::Command { ref name, .. } => name.as_ref(),
             &ModuleFeature::Trigger { ref name, .. } => name.as_ref(),
+            &ModuleFeature::RawHandler { ref name, .. } => name.as_ref(),
         }
     }
 
@@ -300,6 +380,17 @@ impl GetDebugInfo for Trigger {
     }
 }
 
+impl GetDebugInfo for RawHandler {
+    type Output = ModuleFeatureInfo;
+
+    fn dbg_info(&self) -> ModuleFeatureInfo {
+        ModuleFeatureInfo {
+            name: self.name.to_string(),
+            kind: ModuleFeatureKind::RawHandler,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ModuleLoadMode {
     /// Emit an error if any of the new module's features conflict with already present modules'
@@ -313,6 +404,21 @@ pub enum ModuleLoadMode {
 }
 
 impl State {
+    /// Returns information about each loaded module, along with information about each of the
+    /// features that the module provides, in order of the modules' names.
+    pub fn modules_info(&self) -> Result<Vec<(ModuleInfo, Vec<ModuleFeatureInfo>)>> {
+        Ok(self
+            .modules
+            .values()
+            .map(|module| {
+                (
+                    module.dbg_info(),
+                    module.features.iter().map(GetDebugInfo::dbg_info).collect(),
+                )
+            })
+            .collect())
+    }
+
     pub fn load_modules<Modls>(
         &mut self,
         modules: Modls,
@@ -392,10 +498,117 @@ impl State {
         Ok(())
     }
 
-    fn load_module_feature<'modl>(
+    /// Removes the module with the given name, along with all of its commands, triggers, and raw
+    /// message handlers.
+    ///
+    /// Returns the removed module, or an error if no module with the given name is loaded.
+    pub fn unload_module(&mut self, name: &str) -> Result<Arc<Module>> {
+        debug!("Unloading module {:?}", name);
+
+        let module = match self.modules.remove(name) {
+            Some(m) => m,
+            None => bail!(ErrorKind::UnknownModule(name.to_owned())),
+        };
+
+        self.commands.retain(|_, cmd| cmd.provider != module);
+
+        for triggers in self.triggers.values_mut() {
+            triggers.retain(|trigger| trigger.provider != module);
+        }
+
+        self.triggers.retain(|_, triggers| !triggers.is_empty());
+
+        self.raw_handlers
+            .retain(|raw_handler| raw_handler.provider != module);
+
+        self.disabled_modules
+            .write_clean("the set of disabled modules")?
+            .remove(name);
+
+        Ok(module)
+    }
+
+    /// Disables the loaded module with the given name, such that its commands, triggers, and raw
+    /// message handlers are ignored until it is re-enabled with [`enable_module`].
+    ///
+    /// Unlike [`unload_module`], this does not require exclusive access to the `State`, and so can
+    /// be done while the bot is running.
+    ///
+    /// Returns `false` if the module already was disabled.
+    ///
+    /// [`enable_module`]: <#method.enable_module>
+    /// [`unload_module`]: <#method.unload_module>
+    pub fn disable_module(&self, name: &str) -> Result<bool> {
+        let module = self.get_module(name)?;
+
+        Ok(self
+            .disabled_modules
+            .write_clean("the set of disabled modules")?
+            .insert(module.name.clone()))
+    }
+
+    /// Re-enables the module with the given name after it has been disabled with
+    /// [`disable_module`].
+    ///
+    /// The module's load handlers (see [`ModuleBuilder::on_load`]) are run again before the module
+    /// is re-enabled, so that, e.g., any data that the module loads from the module data directory
+    /// are reloaded. If a load handler fails, the module remains disabled.
+    ///
+    /// Returns `false` if the module was not disabled.
+    ///
+    /// [`ModuleBuilder::on_load`]: <struct.ModuleBuilder.html#method.on_load>
+    /// [`disable_module`]: <#method.disable_module>
+    pub fn enable_module(&self, name: &str) -> Result<bool> {
+        let module = self.get_module(name)?;
+
+        if !self.module_is_disabled(&module.name)? {
+            return Ok(false);
+        }
+
+        for handler in &module.on_load {
+            handler.run(self)?;
+        }
+
+        Ok(self
+            .disabled_modules
+            .write_clean("the set of disabled modules")?
+            .remove(name))
+    }
+
+    /// Returns whether the module with the given name has been disabled with [`disable_module`].
+    ///
+    /// [`disable_module`]: <#method.disable_module>
+    pub fn module_is_disabled(&self, name: &str) -> Result<bool> {
+        Ok(self
+            .disabled_modules
+            .read_clean("the set of disabled modules")?
+            .contains(name))
+    }
+
+    /// Runs the shutdown handlers (see [`ModuleBuilder::on_shutdown`]) of all loaded modules,
+    /// logging any errors, as the bot is about to exit.
+    ///
+    /// [`ModuleBuilder::on_shutdown`]: <struct.ModuleBuilder.html#method.on_shutdown>
+    pub(super) fn shut_down_modules(&self) {
+        for module in self.modules.values() {
+            for handler in &module.on_shutdown {
+                if let Err(e) = handler.run(self) {
+                    error!("Failed to shut down module {:?}: {}", module.name, e);
+                }
+            }
+        }
+    }
+
+    pub(super) fn get_module(&self, name: &str) -> Result<&Arc<Module>> {
+        self.modules
+            .get(name)
+            .ok_or_else(|| ErrorKind::UnknownModule(name.to_owned()).into())
+    }
+
+    fn load_module_feature(
         &mut self,
         provider: Arc<Module>,
-        feature: &'modl ModuleFeature,
+        feature: &ModuleFeature,
         mode: ModuleLoadMode,
     ) -> Result<()> {
         trace!("Loading module feature (phase 1): {:?}", feature.dbg_info());
@@ -407,7 +620,7 @@ impl State {
                 (ModuleLoadMode::Replace, Some(old)) => Some(old.dbg_info()),
                 (ModuleLoadMode::Add, Some(old)) => Some(old.dbg_info()),
             },
-            &ModuleFeature::Trigger { .. } => None,
+            &ModuleFeature::Trigger { .. } | &ModuleFeature::RawHandler { .. } => None,
         } {
             bail!(ErrorKind::ModuleFeatureRegistryClash(
                 existing_feature,
@@ -420,10 +633,10 @@ impl State {
         Ok(())
     }
 
-    fn force_load_module_feature<'modl>(
+    fn force_load_module_feature(
         &mut self,
         provider: Arc<Module>,
-        feature: &'modl ModuleFeature,
+        feature: &ModuleFeature,
     ) {
         trace!("Loading module feature (phase 2): {:?}", feature.dbg_info());
 
@@ -435,6 +648,7 @@ impl State {
                 ref usage_str,
                 ref usage_yaml,
                 ref help_msg,
+                cooldown,
             } => {
                 self.commands.insert(
                     name.clone(),
@@ -446,6 +660,7 @@ impl State {
                         usage_str: usage_str.clone(),
                         usage_yaml: usage_yaml.clone(),
                         help_msg: help_msg.clone(),
+                        cooldown,
                     },
                 );
             }
@@ -455,6 +670,7 @@ impl State {
                 ref handler,
                 ref help_msg,
                 priority,
+                always_watching,
                 uuid,
             } => {
                 self.triggers
@@ -466,10 +682,21 @@ impl State {
                         regex: regex.clone(),
                         handler: handler.clone(),
                         priority,
+                        always_watching,
                         help_msg: help_msg.clone(),
                         uuid,
                     });
             }
+            &ModuleFeature::RawHandler {
+                ref name,
+                ref handler,
+            } => {
+                self.raw_handlers.push(RawHandler {
+                    provider,
+                    name: name.clone(),
+                    handler: handler.clone(),
+                });
+            }
         };
     }
 }
@@ -0,0 +1,264 @@
+use super::Result;
+use super::State;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_yaml;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use util::lock::MutexExt;
+
+/// The name of the directory, in the module data directory, in which modules' stores are kept
+const STORE_DIR_NAME: &str = "module-store";
+
+/// The contents of a module's store, mapping keys to values
+pub(super) type StoreMap = BTreeMap<String, serde_yaml::Value>;
+
+/// A handle to the persistent key–value store of a module, with which the module can save and
+/// retrieve values of any type that can be serialized and deserialized with Serde, without
+/// managing files itself.
+///
+/// Each module has its own store, which is kept in the file `module-store/<module name>.yaml` in
+/// the bot's module data directory. The store is read from that file when it is first used, and
+/// the file is rewritten whenever the store is changed.
+///
+/// A `ModuleStore` can be obtained with [`HandlerContext::module_store`] or
+/// [`State::module_store`].
+///
+/// [`HandlerContext::module_store`]: <struct.HandlerContext.html#method.module_store>
+/// [`State::module_store`]: <struct.State.html#method.module_store>
+#[derive(Debug)]
+pub struct ModuleStore<'s> {
+    state: &'s State,
+    module_name: &'s str,
+}
+
+impl State {
+    /// Returns a handle to the persistent key–value store of the loaded module with the given
+    /// name. See [`ModuleStore`].
+    ///
+    /// [`ModuleStore`]: <struct.ModuleStore.html>
+    pub fn module_store(&self, module_name: &str) -> Result<ModuleStore> {
+        Ok(ModuleStore::new(self, &self.get_module(module_name)?.name))
+    }
+}
+
+impl<'s> ModuleStore<'s> {
+    pub(super) fn new(state: &'s State, module_name: &'s str) -> Self {
+        ModuleStore { state, module_name }
+    }
+
+    /// Returns the value stored under the given key, or `None` if there is none.
+    ///
+    /// An error will result if the stored value can't be deserialized as a `T`.
+    pub fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.with_store(|_, store| match store.get(key) {
+            Some(value) => Ok(Some(serde_yaml::from_value(value.clone())?)),
+            None => Ok(None),
+        })
+    }
+
+    /// Stores the given value under the given key, replacing any value already stored under that
+    /// key.
+    pub fn put<T>(&self, key: &str, value: &T) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let value = serde_yaml::to_value(value)?;
+
+        self.with_store(|path, store| {
+            let old_value = store.insert(key.to_owned(), value);
+            save_or_restore(path, store, key, old_value)
+        })
+    }
+
+    /// Replaces the value stored under the given key with the result of calling the given function
+    /// on that value, or on `None` if there is none, and returns the new value.
+    ///
+    /// Unlike a `get` followed by a `put`, this happens atomically, so that, e.g., concurrent
+    /// increments of a counter are not lost.
+    pub fn update<T, F>(&self, key: &str, f: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(Option<T>) -> T,
+    {
+        self.with_store(|path, store| {
+            let old_value = match store.get(key) {
+                Some(value) => Some(serde_yaml::from_value(value.clone())?),
+                None => None,
+            };
+
+            let new_value = f(old_value);
+
+            let old_value = store.insert(key.to_owned(), serde_yaml::to_value(&new_value)?);
+            save_or_restore(path, store, key, old_value)?;
+
+            Ok(new_value)
+        })
+    }
+
+    /// Removes the value stored under the given key, returning whether there was one.
+    pub fn remove(&self, key: &str) -> Result<bool> {
+        self.with_store(|path, store| match store.remove(key) {
+            Some(old_value) => save_or_restore(path, store, key, Some(old_value)).map(|()| true),
+            None => Ok(false),
+        })
+    }
+
+    /// Returns the keys under which values are stored, in order.
+    pub fn keys(&self) -> Result<Vec<String>> {
+        self.with_store(|_, store| Ok(store.keys().cloned().collect()))
+    }
+
+    /// Calls the given function with the path of this module's store file and the contents of the
+    /// store, which are read from the file first if they haven't been already.
+    fn with_store<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Path, &mut StoreMap) -> Result<T>,
+    {
+        let path = self
+            .state
+            .module_data_path()?
+            .join(STORE_DIR_NAME)
+            .join(format!("{}.yaml", self.module_name));
+
+        let mut stores = self
+            .state
+            .module_stores
+            .lock_clean("the modules' key–value stores")?;
+
+        if !stores.contains_key(self.module_name) {
+            let store = if path.exists() {
+                serde_yaml::from_str(&fs::read_to_string(&path)?)?
+            } else {
+                StoreMap::new()
+            };
+
+            stores.insert(self.module_name.to_owned(), store);
+        }
+
+        let store = stores
+            .get_mut(self.module_name)
+            .expect("We just made sure that this store is loaded!");
+
+        f(&path, store)
+    }
+}
+
+/// Saves the given store, which has just been changed under the given key, to the file at the given
+/// path. If that fails, the change is undone by restoring the given old value under the key, or
+/// removing the key if there was none, so that the store in memory stays as it is on disk.
+fn save_or_restore(
+    path: &Path,
+    store: &mut StoreMap,
+    key: &str,
+    old_value: Option<serde_yaml::Value>,
+) -> Result<()> {
+    let result = save(path, store);
+
+    if result.is_err() {
+        match old_value {
+            Some(value) => store.insert(key.to_owned(), value),
+            None => store.remove(key),
+        };
+    }
+
+    result
+}
+
+/// Writes the given store to the file at the given path, by way of a temporary file, so that the
+/// existing file isn't lost if writing fails.
+fn save(path: &Path, store: &StoreMap) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp_path = path.with_extension("yaml.tmp");
+
+    fs::write(&tmp_path, serde_yaml::to_string(store)?)?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::irc_send::OUTBOX_SIZE;
+    use super::super::test_harness::TestBot;
+    use super::super::test_harness::MINIMAL_CONFIG;
+    use super::super::ErrorKind;
+    use super::super::ErrorReaction;
+    use super::super::IntoConfig;
+    use super::*;
+    use crossbeam_channel;
+    use modules;
+
+    #[test]
+    fn round_trip() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let store = ModuleStore::new(bot.state(), "default");
+
+        assert_eq!(store.get::<u32>("count").unwrap(), None);
+
+        store.put("count", &1u32).unwrap();
+        assert_eq!(store.get::<u32>("count").unwrap(), Some(1));
+
+        assert_eq!(
+            store
+                .update("count", |n: Option<u32>| n.unwrap_or(0) + 1)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            store
+                .update("other", |n: Option<u32>| n.unwrap_or(0) + 1)
+                .unwrap(),
+            1
+        );
+        assert_eq!(store.keys().unwrap(), ["count", "other"]);
+
+        // The store is read back from its file by a new `State` with the same data directory.
+        let state = State::new(
+            MINIMAL_CONFIG.into_config().unwrap(),
+            bot.state().module_data_path().unwrap().to_owned(),
+            |_| ErrorReaction::Proceed,
+            crossbeam_channel::bounded(OUTBOX_SIZE).0,
+        )
+        .unwrap();
+        let reread_store = ModuleStore::new(&state, "default");
+
+        assert_eq!(reread_store.get::<u32>("count").unwrap(), Some(2));
+
+        match *store.get::<String>("count").unwrap_err().kind() {
+            ErrorKind::SerdeYaml(_) => {}
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+
+        assert!(store.remove("count").unwrap());
+        assert!(!store.remove("count").unwrap());
+        assert_eq!(store.get::<u32>("count").unwrap(), None);
+    }
+
+    #[test]
+    fn failed_save_changes_nothing() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let store = ModuleStore::new(bot.state(), "default");
+        let data_path = bot.state().module_data_path().unwrap();
+
+        store.put("count", &1u32).unwrap();
+
+        // Put a file where the store's directory should be, so that saving fails.
+        fs::remove_dir_all(data_path.join(STORE_DIR_NAME)).unwrap();
+        fs::write(data_path.join(STORE_DIR_NAME), "").unwrap();
+
+        assert!(store.put("count", &2u32).is_err());
+        assert!(store.update("new", |_: Option<u32>| 1).is_err());
+        assert!(store.remove("count").is_err());
+
+        assert_eq!(store.get::<u32>("count").unwrap(), Some(1));
+        assert_eq!(store.keys().unwrap(), ["count"]);
+    }
+}
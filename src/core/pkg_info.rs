@@ -14,6 +14,10 @@ lazy_static! {
         option_env!("CARGO_PKG_VERSION"),
     ]);
     pub(super) static ref HOMEPAGE_STR: &'static str = choose(&[option_env!("CARGO_PKG_HOMEPAGE")]);
+    pub(super) static ref GIT_COMMIT_STR: &'static str =
+        choose(&[option_env!("IRC_BOT_RS_GIT_COMMIT")]);
+    pub(super) static ref BUILD_DATE_STR: &'static str =
+        choose(&[option_env!("IRC_BOT_RS_BUILD_DATE")]);
     pub(super) static ref BRIEF_CREDITS_STRING: String = format!(
         "Built with <{url}> {ver}",
         url = HOMEPAGE_STR.deref(),
@@ -45,4 +49,23 @@ impl State {
     pub fn framework_homepage_url_str(&self) -> &'static str {
         &HOMEPAGE_STR
     }
+
+    /// Returns a `&str` containing either the hash of the Git commit from which the bot framework
+    /// was built or the text `"<unknown>"`.
+    pub fn framework_git_commit_str(&self) -> &'static str {
+        &GIT_COMMIT_STR
+    }
+
+    /// Returns a `&str` containing either the date and time, in UTC and in [RFC 3339] format, at
+    /// which the bot framework was built or the text `"<unknown>"`.
+    ///
+    /// If the environment variable `SOURCE_DATE_EPOCH` was set when the bot framework was built,
+    /// the date and time that it specified are returned instead, per the [Reproducible Builds]
+    /// specification.
+    ///
+    /// [RFC 3339]: <https://tools.ietf.org/html/rfc3339>
+    /// [Reproducible Builds]: <https://reproducible-builds.org/specs/source-date-epoch/>
+    pub fn framework_build_date_str(&self) -> &'static str {
+        &BUILD_DATE_STR
+    }
 }
@@ -0,0 +1,36 @@
+use super::Module;
+use super::RawMsgHandler;
+use super::Reaction;
+use super::Result;
+use super::ServerId;
+use super::State;
+use irc::proto::Message;
+use std::borrow::Cow;
+use std::sync::Arc;
+use util;
+
+#[derive(CustomDebug)]
+pub struct RawHandler {
+    pub name: Cow<'static, str>,
+
+    pub provider: Arc<Module>,
+
+    #[debug(skip)]
+    pub(super) handler: Arc<RawMsgHandler>,
+}
+
+/// Runs every raw message handler of every enabled module on the given message, in the order in
+/// which the handlers were loaded, returning their results.
+pub(super) fn run_all(state: &State, server_id: ServerId, msg: &Message) -> Vec<Result<Reaction>> {
+    state
+        .raw_handlers
+        .iter()
+        .filter(|h| !state.module_is_disabled(&h.provider.name).unwrap_or(true))
+        .map(|h| {
+            util::run_handler("raw message handler", h.name.clone(), || {
+                h.handler.run(state, server_id, msg)
+            })
+            .and_then(|result| result)
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::fmt;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum Reaction {
@@ -8,8 +9,54 @@ pub enum Reaction {
     Msgs(Cow<'static, [Cow<'static, str>]>),
     Reply(Cow<'static, str>),
     Replies(Cow<'static, [Cow<'static, str>]>),
+
+    /// Send the given text where a `Reply` would be sent, but addressed to the user with the given
+    /// nickname rather than to the user whose message is being handled, e.g., to pass a message on
+    /// to someone else in the same channel. An error will result if `nick` is not a valid
+    /// nickname.
+    ReplyTo {
+        nick: Cow<'static, str>,
+        text: Cow<'static, str>,
+    },
+
     RawMsg(Cow<'static, str>),
+
+    /// Send nothing, but log the given note describing what the handler did, e.g., for a trigger
+    /// that passively records something about a conversation. If the bot is built with the Cargo
+    /// feature `metrics`, this is also counted in the bot's metrics.
+    Silent(Cow<'static, str>),
+
     Quit(Option<Cow<'static, str>>),
+
+    /// React with the given reaction after the given duration has elapsed, as though the handler
+    /// had returned it at that time. If the server to which the reaction would be sent has been
+    /// disconnected by then, the reaction will be discarded.
+    Delay(Duration, Box<Reaction>),
+
+    /// Kick the user with the given nickname from the given channel, with an optional reason. An
+    /// error will result if `chan` is not a valid channel name, if `nick` is not a valid nickname,
+    /// or if `reason` contains a control character.
+    Kick {
+        chan: Cow<'static, str>,
+        nick: Cow<'static, str>,
+        reason: Option<Cow<'static, str>>,
+    },
+
+    /// Set or unset modes of the given target, which may be a channel or a user. The `modes`
+    /// string should be as in an IRC `MODE` message (e.g., `+o` or `-v`), and `args` should hold
+    /// the modes' parameters, if any (e.g., the nickname of the user to be given operator
+    /// status). An error will result if `target` is neither a valid channel name nor a valid
+    /// nickname, or if `modes` or any of `args` contains a control character or whitespace.
+    Mode {
+        target: Cow<'static, str>,
+        modes: Cow<'static, str>,
+        args: Cow<'static, [Cow<'static, str>]>,
+    },
+
+    /// React with each of the given reactions, in the order given. The messages resulting from
+    /// the reactions that are not delayed will be sent as a contiguous unit, as with
+    /// `LibReaction::Multi`.
+    Seq(Vec<Reaction>),
 }
 
 #[derive(Debug)]
@@ -26,11 +73,26 @@ where
 {
     /// React by sending an IRC message. No line-wrapping or other formatting will be performed on
     /// the message, except that the message-terminating sequence of a carriage return character
-    /// and a line feed character ("CR-LF") will be appended. If the message exceeds 512 octets in
-    /// length (including the terminating CR-LF sequence, but excluding any IRCv3 message tags), it
-    /// may be truncated to 512 octets.
+    /// and a line feed character ("CR-LF") will be appended. If the message exceeds
+    /// `util::irc::IRC_MESSAGE_MAX_LEN` (512) octets in length (including the terminating CR-LF
+    /// sequence, but excluding any IRCv3 message tags), it may be truncated to that length.
     RawMsg(Msg),
 
-    /// Return multiple reactions, which will be processed in the order given.
+    /// Return multiple reactions, which will be processed in the order given. The resulting
+    /// messages will be sent as a contiguous unit, without messages from other reactions being
+    /// interleaved among them.
     Multi(Vec<LibReaction<Msg>>),
 }
+
+impl Reaction {
+    /// If this reaction would have the bot quit, whether immediately or after a delay, returns the
+    /// quit message that would be used.
+    pub(super) fn quit_msg(&self) -> Option<&Option<Cow<'static, str>>> {
+        match self {
+            Reaction::Quit(msg) => Some(msg),
+            Reaction::Delay(_, inner) => inner.quit_msg(),
+            Reaction::Seq(reactions) => reactions.iter().filter_map(Reaction::quit_msg).next(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Support for authenticating to IRC servers via the Simple Authentication and Security Layer
+//! (SASL), as specified at <https://ircv3.net/specs/extensions/sasl-3.1>.
+//!
+//! Only the `PLAIN` mechanism is supported. The `irc` crate's `identify` method ends capability
+//! negotiation immediately, which would leave no opportunity to authenticate, so servers with SASL
+//! configured are instead identified to with `identify_without_cap_end`, and capability
+//! negotiation is ended once authentication has succeeded or failed.
+
+use super::config;
+use super::irc_send::push_to_outbox;
+use super::irc_send::OutboxPort;
+use super::reaction::LibReaction;
+use super::Result;
+use super::ServerId;
+use super::State;
+use irc::client::prelude as aatxe;
+use irc::client::prelude::Client as AatxeClient;
+use irc::proto::CapSubCommand;
+
+/// The greatest length of the argument of an `AUTHENTICATE` message.
+const AUTHENTICATE_CHUNK_LEN: usize = 400;
+
+/// Sends the identification sequence that the `irc` crate's `identify` method would send, but
+/// without first ending capability negotiation.
+pub(super) fn identify_without_cap_end(client: &aatxe::IrcClient) -> Result<()> {
+    let config = client.config();
+
+    if !config.password().is_empty() {
+        client.send(aatxe::Command::PASS(config.password().to_owned()))?;
+    }
+
+    client.send(aatxe::Command::NICK(config.nickname()?.to_owned()))?;
+    client.send(aatxe::Command::USER(
+        config.username().to_owned(),
+        "0".to_owned(),
+        config.real_name().to_owned(),
+    ))?;
+
+    Ok(())
+}
+
+/// Handles a `CAP ACK` or `CAP NAK` message, beginning authentication if the server has
+/// acknowledged our request for the `sasl` capability.
+pub(super) fn handle_cap(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    subcommand: CapSubCommand,
+    caps: &str,
+) -> Result<()> {
+    if state.get_server_config(server_id)?.sasl.is_none() {
+        return Ok(());
+    }
+
+    if !caps.split_whitespace().any(|cap| cap == "sasl") {
+        return Ok(());
+    }
+
+    match subcommand {
+        CapSubCommand::ACK => {
+            debug!(
+                "[{}] Server acknowledged SASL capability; authenticating.",
+                state.server_socket_addr_dbg_string(server_id)
+            );
+            send(
+                outbox,
+                server_id,
+                aatxe::Command::AUTHENTICATE("PLAIN".into()),
+            );
+        }
+        CapSubCommand::NAK => {
+            error!(
+                "[{}] Server refused SASL capability; proceeding without authenticating.",
+                state.server_socket_addr_dbg_string(server_id)
+            );
+            end_cap_negotiation(outbox, server_id);
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Handles an `AUTHENTICATE` message from the server. A `+` means that the server is ready to
+/// receive our credentials.
+pub(super) fn handle_authenticate(state: &State, server_id: ServerId, data: &str) -> Result<()> {
+    let config::Sasl {
+        ref username,
+        ref password,
+        ..
+    } = match state.get_server_config(server_id)?.sasl {
+        Some(ref sasl) => sasl,
+        None => return Ok(()),
+    };
+
+    if data != "+" {
+        return Ok(());
+    }
+
+    let payload = base64_encode(format!("{0}\0{0}\0{1}", username, password).as_bytes());
+
+    // The payload contains the password, so it's sent directly rather than through the outbox,
+    // which logs the messages that pass through it.
+    state.with_aatxe_client(server_id, |client| {
+        let mut last_chunk_len = 0;
+
+        for chunk in payload.as_bytes().chunks(AUTHENTICATE_CHUNK_LEN) {
+            // The payload is ASCII, so this is lossless.
+            let chunk = String::from_utf8_lossy(chunk).into_owned();
+            last_chunk_len = chunk.len();
+            client.send(aatxe::Command::AUTHENTICATE(chunk))?;
+        }
+
+        // A payload whose length is a multiple of the chunk length must be followed by an empty
+        // chunk, which is represented as `+`.
+        if last_chunk_len == AUTHENTICATE_CHUNK_LEN {
+            client.send(aatxe::Command::AUTHENTICATE("+".into()))?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Handles a numeric reply that ends SASL authentication, successfully or otherwise, by ending
+/// capability negotiation so that connection registration can complete.
+pub(super) fn handle_sasl_result(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    response: aatxe::Response,
+) -> Result<()> {
+    if state.get_server_config(server_id)?.sasl.is_none() {
+        return Ok(());
+    }
+
+    match response {
+        aatxe::Response::RPL_SASLSUCCESS => info!(
+            "[{}] Authenticated via SASL.",
+            state.server_socket_addr_dbg_string(server_id)
+        ),
+        _ => error!(
+            "[{}] SASL authentication failed: {:?}",
+            state.server_socket_addr_dbg_string(server_id),
+            response
+        ),
+    }
+
+    end_cap_negotiation(outbox, server_id);
+
+    Ok(())
+}
+
+fn end_cap_negotiation(outbox: &OutboxPort, server_id: ServerId) {
+    send(
+        outbox,
+        server_id,
+        aatxe::Command::CAP(None, CapSubCommand::END, None, None),
+    );
+}
+
+fn send(outbox: &OutboxPort, server_id: ServerId, cmd: aatxe::Command) {
+    push_to_outbox(outbox, server_id, LibReaction::RawMsg(cmd.into()));
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(input.len() * 4 / 3 + 4);
+
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_encode_examples() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        assert_eq!(base64_encode(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(
+            base64_encode(b"jilles\0jilles\0sesame"),
+            "amlsbGVzAGppbGxlcwBzZXNhbWU="
+        );
+    }
+}
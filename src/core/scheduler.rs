@@ -0,0 +1,182 @@
+//! A single thread on which work that is to be done after a delay, such as sending the reactions
+//! of `Reaction::Delay`, is run when it comes due.
+
+use super::ErrorKind;
+use super::Result;
+use crossbeam_channel;
+use crossbeam_channel::RecvTimeoutError;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use util;
+use util::lock::MutexExt;
+
+/// Work to be run by the scheduler thread.
+///
+/// This trait is implemented for all suitable one-shot functions. The work should not block for
+/// long, as no other work is run until it returns.
+pub(super) trait Job: Send + 'static {
+    fn run(self: Box<Self>);
+}
+
+impl<F> Job for F
+where
+    F: FnOnce() + Send + 'static,
+{
+    fn run(self: Box<Self>) {
+        (*self)()
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Scheduler {
+    /// The sending end of the queue of the scheduler thread, which is started when the first job
+    /// is scheduled
+    sender: Mutex<Option<crossbeam_channel::Sender<ScheduledJob>>>,
+
+    /// The number of jobs that have been scheduled, with which jobs that come due at the same time
+    /// are kept in the order in which they were scheduled
+    job_count: AtomicUsize,
+}
+
+struct ScheduledJob {
+    due: Instant,
+    seq: usize,
+    job: Box<Job>,
+}
+
+impl Scheduler {
+    /// Has the given job run on the scheduler thread once the given delay has elapsed.
+    pub(super) fn schedule<J>(&self, delay: Duration, job: J) -> Result<()>
+    where
+        J: Job,
+    {
+        let mut sender = self.sender.lock_clean("the scheduler's queue")?;
+
+        if sender.is_none() {
+            let (new_sender, receiver) = crossbeam_channel::unbounded();
+
+            thread::Builder::new()
+                .name("scheduler".into())
+                .spawn(move || run(&receiver))
+                .map_err(ErrorKind::ThreadSpawnFailure)?;
+
+            *sender = Some(new_sender);
+        }
+
+        let scheduled_job = ScheduledJob {
+            due: Instant::now() + delay,
+            seq: self.job_count.fetch_add(1, atomic::Ordering::SeqCst),
+            job: Box::new(job),
+        };
+
+        let send_result = match *sender {
+            Some(ref sender) => sender.send(scheduled_job),
+            None => unreachable!(),
+        };
+
+        if send_result.is_err() {
+            // The scheduler thread has stopped, so start another for the next job.
+            *sender = None;
+            return Err("The scheduler thread has stopped unexpectedly.".into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs jobs received from the given queue as they come due, until the queue's sending end has
+/// been dropped.
+fn run(receiver: &crossbeam_channel::Receiver<ScheduledJob>) {
+    let mut queue = BinaryHeap::new();
+
+    loop {
+        let now = Instant::now();
+
+        loop {
+            match queue.peek() {
+                Some(&ScheduledJob { due, .. }) if due <= now => {}
+                _ => break,
+            }
+
+            if let Some(ScheduledJob { job, .. }) = queue.pop() {
+                if let Err(e) =
+                    util::run_handler("scheduled job", "", AssertUnwindSafe(move || job.run()))
+                {
+                    error!("{}", e);
+                }
+            }
+        }
+
+        let new_job = match queue.peek() {
+            Some(&ScheduledJob { due, .. }) => match receiver.recv_timeout(due - now) {
+                Ok(job) => Some(job),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            },
+            None => match receiver.recv() {
+                Ok(job) => Some(job),
+                Err(_) => return,
+            },
+        };
+
+        if let Some(job) = new_job {
+            queue.push(job);
+        }
+    }
+}
+
+// `BinaryHeap` is a max-heap, so these are ordered such that the job that is due soonest is the
+// greatest.
+
+impl Ord for ScheduledJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.due, other.seq).cmp(&(self.due, self.seq))
+    }
+}
+
+impl PartialOrd for ScheduledJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScheduledJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledJob {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_run_in_order_of_due_time() {
+        let scheduler = Scheduler::default();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+
+        for &(delay_ms, label) in &[(300, "c"), (100, "a"), (200, "b1"), (200, "b2")] {
+            let sender = sender.clone();
+            scheduler
+                .schedule(Duration::from_millis(delay_ms), move || {
+                    sender.send(label).unwrap()
+                })
+                .unwrap();
+        }
+
+        let labels = (0..4)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(1)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(labels, ["a", "b1", "b2", "c"]);
+    }
+}
@@ -2,21 +2,32 @@ use super::config;
 use super::irc_msgs::OwningMsgPrefix;
 use super::BotCommand;
 use super::ErrorKind;
+use super::MsgDest;
 use super::MsgPrefix;
 use super::Result;
 use super::Server;
 use super::ServerConfigIndex;
 use super::ServerId;
 use super::State;
+use irc::client::data::AccessLevel as AatxeAccessLevel;
 use irc::client::prelude as aatxe;
+use irc::client::prelude::Client as AatxeClient;
 use rand::StdRng;
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::path::Path;
+use std::sync::atomic::Ordering as AtomicOrdering;
 use std::sync::LockResult;
 use std::sync::MutexGuard;
 use std::sync::RwLock;
 use std::sync::RwLockReadGuard;
 use std::sync::RwLockWriteGuard;
+use std::time::Duration;
+use util::irc::case_insensitive_str_cmp;
+use util::irc::ChannelId;
+use util::irc::ChannelName;
+use util::lock::MutexExt;
+use util::lock::ReadLockExt;
 
 impl State {
     pub fn nick(&self, server_id: ServerId) -> Result<String> {
@@ -27,16 +38,171 @@ impl State {
             .map(ToOwned::to_owned)
     }
 
+    /// Returns whether the given string is the bot's own nickname on the server with the given
+    /// `ServerId`, comparing nicknames per IRC's rules for case-folding.
+    pub fn is_own_nick(&self, server_id: ServerId, nick: &str) -> Result<bool> {
+        Ok(case_insensitive_str_cmp(nick, self.nick(server_id)?) == Ordering::Equal)
+    }
+
+    /// Returns the name of the server with the given `ServerId`, as given in the bot's
+    /// configuration.
+    pub fn server_name(&self, server_id: ServerId) -> Result<&str> {
+        Ok(&self.get_server_config(server_id)?.name)
+    }
+
+    /// Returns a string identifying the given message destination for logging purposes, of the
+    /// form `server/target`, with the server identified by its name as given in the bot's
+    /// configuration.
+    pub fn msg_dest_dbg_string(&self, MsgDest { server_id, target }: MsgDest) -> String {
+        format!("{}/{}", self.server_name_dbg_string(server_id), target)
+    }
+
+    /// Returns a string identifying the server with the given `ServerId` by its name, as given in
+    /// the bot's configuration, for logging purposes.
+    pub(super) fn server_name_dbg_string(&self, server_id: ServerId) -> String {
+        match self.server_name(server_id) {
+            Ok(name) => name.to_owned(),
+            Err(e) => format!("<unknown server {:?} ({})>", server_id, e),
+        }
+    }
+
+    /// Returns the server-qualified identifier of the channel identified by the given `MsgDest`, or
+    /// `None` if the `MsgDest`'s target is not a channel name, e.g., if it is a nickname.
+    pub fn channel_id(&self, MsgDest { server_id, target }: MsgDest) -> Result<Option<ChannelId>> {
+        let channel = match ChannelName::new(target) {
+            Ok(channel) => channel,
+            Err(_) => return Ok(None),
+        };
+
+        Ok(Some(ChannelId::new(self.server_name(server_id)?, channel)))
+    }
+
+    /// Returns the server-qualified identifiers of all channels listed in the bot's configuration.
+    pub fn configured_channel_ids(&self) -> Vec<ChannelId> {
+        self.config
+            .servers
+            .iter()
+            .flat_map(|server| {
+                server
+                    .channels
+                    .iter()
+                    .map(move |chan| ChannelId::new(server.name.as_str(), chan.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Returns whether the channel `viewer` can see the channel `seen`, in the sense defined in the
+    /// documentation of the per-channel settings `can see` and `seen by` in the bot's
+    /// configuration. All channels can see themselves.
+    pub fn channel_can_see(&self, viewer: &ChannelId, seen: &ChannelId) -> Result<bool> {
+        if viewer == seen {
+            return Ok(true);
+        }
+
+        if let Some(cfg) = self.channel_config(viewer) {
+            if let Some(ref regex) = *cfg.can_see.read_clean("a `can see` regex")? {
+                if regex.is_match(&seen.to_string()) {
+                    return Ok(true);
+                }
+            }
+        }
+
+        if let Some(cfg) = self.channel_config(seen) {
+            if let Some(ref regex) = *cfg.seen_by.read_clean("a `seen by` regex")? {
+                if regex.is_match(&viewer.to_string()) {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Re-reads the bot's configuration file and updates the per-channel settings `can see` and
+    /// `seen by` of the channels already listed in the bot's configuration to match it, returning
+    /// the number of channels whose settings changed. Other settings are left as they were.
+    ///
+    /// The new configuration must be valid as a whole, or nothing is changed.
+    pub fn reload_config(&self) -> Result<usize> {
+        let path = match self.config.source_path {
+            Some(ref path) => path,
+            None => bail!(ErrorKind::ConfigNotReloadable),
+        };
+
+        let new_config = config::Config::try_from_path(path)?;
+
+        let changed = self.config.update_channel_visibility(&new_config)?;
+
+        if changed > 0 {
+            self.config_generation.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+
+        Ok(changed)
+    }
+
+    /// Returns a number that changes whenever `reload_config` changes the bot's configuration, so
+    /// that anything cached on the basis of the configuration can be keyed by it.
+    pub fn config_generation(&self) -> usize {
+        self.config_generation.load(AtomicOrdering::SeqCst)
+    }
+
+    pub(super) fn channel_config(&self, id: &ChannelId) -> Option<&config::Channel> {
+        self.config
+            .servers
+            .iter()
+            .filter(|server| server.name == id.server())
+            .flat_map(|server| server.channels.iter())
+            .find(|chan| chan.name == *id.channel())
+    }
+
+    /// Returns the `ServerId`s of all the servers to which the bot is configured to connect.
+    pub fn server_ids(&self) -> Vec<ServerId> {
+        self.servers.keys().cloned().collect()
+    }
+
+    /// Returns how long it has been since this `State` was constructed, which is approximately how
+    /// long the bot has been running.
+    pub fn uptime(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Returns how long it has been since the bot last registered its connection to the server with
+    /// the given `ServerId`, or `None` if it has not yet done so.
+    pub fn server_uptime(&self, server_id: ServerId) -> Result<Option<Duration>> {
+        Ok(self
+            .read_server(server_id)?
+            .connect_time
+            .map(|t| t.elapsed()))
+    }
+
+    /// Returns whether the IRCv3 capability with the given name, such as `account-tag`, is enabled
+    /// on the bot's connection to the server with the given `ServerId`, i.e., whether the server
+    /// has acknowledged the bot's request for it and not since disabled it.
+    pub fn has_cap(&self, server_id: ServerId, cap: &str) -> Result<bool> {
+        Ok(self.read_server(server_id)?.caps.contains(cap))
+    }
+
     pub fn module_data_path(&self) -> Result<&Path> {
         Ok(self.module_data_path.as_ref())
     }
 
     pub fn command(&self, name: &str) -> Result<Option<&BotCommand>> {
-        Ok(self.commands.get(name))
+        match self.commands.get(name) {
+            Some(cmd) if self.module_is_disabled(&cmd.provider.name)? => Ok(None),
+            cmd => Ok(cmd),
+        }
     }
 
     pub fn command_names(&self) -> Result<Vec<Cow<'static, str>>> {
-        Ok(self.commands.keys().cloned().collect())
+        let mut names = Vec::with_capacity(self.commands.len());
+
+        for (name, cmd) in &self.commands {
+            if !self.module_is_disabled(&cmd.provider.name)? {
+                names.push(name.clone());
+            }
+        }
+
+        Ok(names)
     }
 
     pub fn have_admin(
@@ -60,6 +226,35 @@ impl State {
         ))
     }
 
+    /// Returns whether the user with the given message prefix has channel operator status, or
+    /// higher, in the channel identified by the given `MsgDest`. If the `MsgDest` does not identify
+    /// a channel, e.g., if it is the bot's own nickname, returns `false`.
+    pub fn have_channel_op(
+        &self,
+        MsgDest { server_id, target }: MsgDest,
+        prefix: MsgPrefix,
+    ) -> Result<bool> {
+        let nick = match prefix.nick {
+            Some(nick) => nick,
+            None => return Ok(false),
+        };
+
+        if self.is_own_nick(server_id, target)? {
+            return Ok(false);
+        }
+
+        self.with_aatxe_client(server_id, |client| {
+            Ok(client
+                .list_users(target)
+                .unwrap_or_default()
+                .iter()
+                .any(|user| {
+                    case_insensitive_str_cmp(user.get_nickname(), nick) == Ordering::Equal
+                        && user.highest_access_level() >= AatxeAccessLevel::Oper
+                }))
+        })
+    }
+
     // TODO: This is server-specific.
     // TODO: This should be named `read_stored_msg_prefix`, because it may not be our actual
     // current message prefix.
@@ -87,9 +282,10 @@ impl State {
         match self.servers.get(&server_id) {
             Some(lock) => match access(lock) {
                 Ok(guard) => Ok(guard),
-                Err(_) => {
-                    Err(ErrorKind::LockPoisoned(format!("server {:?}", server_id).into()).into())
-                }
+                Err(_) => Err(ErrorKind::LockPoisoned(
+                    format!("server {}", self.server_name_dbg_string(server_id)).into(),
+                )
+                .into()),
             },
             None => Err(ErrorKind::UnknownServer(server_id).into()),
         }
@@ -149,6 +345,54 @@ impl State {
             .ok_or(ErrorKind::UnknownServer(server_id))?)
     }
 
+    /// Returns the names of the channels that the bot is in on the server with the given
+    /// `ServerId`, as tracked from the `JOIN`, `PART`, and `KICK` messages that the bot has
+    /// received, or `None` if the underlying IRC library has been built without channel tracking.
+    pub fn joined_channels(&self, server_id: ServerId) -> Result<Option<Vec<String>>> {
+        self.with_aatxe_client_private(server_id, |client| Ok(client.list_channels()))
+    }
+
+    /// Returns the nicknames of the users whom the bot sees in the given channel on the server with
+    /// the given `ServerId`, or `None` if the bot is not in that channel (or the underlying IRC
+    /// library has been built without user tracking).
+    pub fn channel_users(&self, server_id: ServerId, channel: &str) -> Result<Option<Vec<String>>> {
+        self.with_aatxe_client_private(server_id, |client| {
+            Ok(client.list_users(channel).map(|users| {
+                users
+                    .iter()
+                    .map(|user| user.get_nickname().to_owned())
+                    .collect()
+            }))
+        })
+    }
+
+    /// Returns the names of the commands that have been run since the bot started or
+    /// `reset_command_usage` was last called, each with the number of times it has been run, in
+    /// descending order of that number.
+    pub fn command_usage(&self) -> Result<Vec<(Cow<'static, str>, u64)>> {
+        let mut usage = self
+            .cmd_usage
+            .lock_clean("the command usage counts")?
+            .iter()
+            .map(|(name, &count)| (name.clone(), count))
+            .collect::<Vec<_>>();
+
+        usage.sort_by(|&(ref name_a, count_a), &(ref name_b, count_b)| {
+            count_b.cmp(&count_a).then_with(|| name_a.cmp(name_b))
+        });
+
+        Ok(usage)
+    }
+
+    /// Resets the counts of how many times each command has been run.
+    pub fn reset_command_usage(&self) -> Result<()> {
+        self.cmd_usage
+            .lock_clean("the command usage counts")?
+            .clear();
+
+        Ok(())
+    }
+
     /// Allows access to a random number generator that's stored centrally, to avoid the cost of
     /// repeatedly initializing one.
     pub fn rng(&self) -> Result<MutexGuard<StdRng>> {
@@ -187,3 +431,36 @@ fn check_admin_cred(candidate: Option<&str>, control: &Option<String>) -> bool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::test_harness::TestBot;
+    use core::test_harness::MINIMAL_CONFIG;
+    use modules;
+
+    #[test]
+    fn channel_op_status() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()])
+            .with_mock_connection(":irc.example.net 353 test-bot = #test :@Alice +bob carol\r\n");
+        let have_channel_op = |target, nick| {
+            let dest = MsgDest {
+                server_id: bot.server_id(),
+                target,
+            };
+            let prefix = MsgPrefix {
+                nick: Some(nick),
+                user: None,
+                host: None,
+            };
+
+            bot.state().have_channel_op(dest, prefix).unwrap()
+        };
+
+        assert!(have_channel_op("#test", "alice"));
+        assert!(!have_channel_op("#test", "bob"));
+        assert!(!have_channel_op("#test", "carol"));
+        assert!(!have_channel_op("#elsewhere", "alice"));
+        assert!(!have_channel_op("test-bot", "alice"));
+    }
+}
@@ -0,0 +1,401 @@
+//! A harness for testing the bot's handling of IRC messages, and thereby the behavior of its
+//! modules, without connecting to any IRC server.
+
+use super::irc_comm;
+use super::irc_send::OutboxRecord;
+use super::irc_send::OUTBOX_SIZE;
+use super::modl_sys::ModuleLoadMode;
+use super::ErrorReaction;
+use super::IntoConfig;
+use super::LibReaction;
+use super::Module;
+use super::Server;
+use super::ServerId;
+use super::State;
+use crossbeam_channel;
+use irc::client::prelude as aatxe;
+use irc::client::prelude::Client as AatxeClient;
+use irc::proto::Message;
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// How long to wait for the bot to react to a message, since commands and triggers are handled in
+/// threads of their own
+const FIRST_OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long to wait for further output once the bot has begun to react to a message
+const MORE_OUTPUT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A configuration with which a `TestBot` can be assembled, with a single server, `test`
+pub(crate) const MINIMAL_CONFIG: &str = "{nickname: test-bot, servers: [{name: test, host: \
+                                         irc.example.net, port: 6697}]}";
+
+/// A counter with which to give each `TestBot` in this process its own module data directory
+static TEST_BOT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A bot, with a minimal `State` and a single server to which it never connects, to which raw IRC
+/// messages can be fed as though received from that server, and whose resulting output is
+/// captured rather than sent anywhere.
+pub(crate) struct TestBot {
+    state: Arc<State>,
+    server_id: ServerId,
+    outbox_receiver: crossbeam_channel::Receiver<OutboxRecord>,
+}
+
+impl TestBot {
+    /// Assembles a bot with the given configuration, which must specify exactly one server, and
+    /// the given modules, panicking on failure.
+    ///
+    /// The bot's module data directory is a fresh temporary directory, which is removed when the
+    /// `TestBot` is dropped.
+    pub(crate) fn new<Cfg, Modls>(config: Cfg, modules: Modls) -> Self
+    where
+        Cfg: IntoConfig,
+        Modls: IntoIterator<Item = Module>,
+    {
+        let config = config.into_config().unwrap();
+
+        assert_eq!(
+            config.aatxe_configs.len(),
+            1,
+            "A `TestBot` needs one server."
+        );
+
+        let module_data_path = env::temp_dir().join(format!(
+            "irc-bot-test-harness-{}-{}",
+            process::id(),
+            TEST_BOT_COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let (outbox_sender, outbox_receiver) = crossbeam_channel::bounded(OUTBOX_SIZE);
+
+        let mut state = State::new(
+            config,
+            module_data_path,
+            |err| {
+                error!("Error in test bot: {}", err);
+                ErrorReaction::Proceed
+            },
+            outbox_sender,
+        )
+        .unwrap();
+
+        if let Err(errs) = state.load_modules(modules, ModuleLoadMode::Add) {
+            panic!("Failed to load modules: {:?}", errs);
+        }
+
+        let (config_idx, ref aatxe_config) = state.config.aatxe_configs[0];
+        let server_id = ServerId::new(config_idx);
+
+        let server = Server {
+            id: server_id,
+            aatxe_config: aatxe_config.clone(),
+            socket_addr_string: "irc.example.net:6697".into(),
+            motd_finished: true,
+            registration_mode_obtained: true,
+            connect_time: None,
+            caps: Default::default(),
+        };
+
+        state.servers.insert(server_id, RwLock::new(server));
+
+        TestBot {
+            state: Arc::new(state),
+            server_id,
+            outbox_receiver,
+        }
+    }
+
+    /// Gives the bot a client whose connection is a mock, which goes nowhere, so that the bot can
+    /// look up such things as which channels it's in, as it would with a real connection.
+    ///
+    /// The client first handles the given raw IRC messages (each with its terminating CR-LF
+    /// sequence) as though received from the server, from which it learns, e.g., who is in which
+    /// channel. These messages aren't fed to the bot itself.
+    pub(crate) fn with_mock_connection(self, incoming: &str) -> Self {
+        let aatxe_config = aatxe::Config {
+            use_mock_connection: Some(true),
+            mock_initial_value: Some(incoming.to_owned()),
+            ..(*self.state.config.aatxe_configs[0].1).clone()
+        };
+        let aatxe_client = aatxe::IrcClient::from_config(aatxe_config).unwrap();
+
+        aatxe_client.for_each_incoming(|_| {}).unwrap();
+
+        self.state
+            .aatxe_clients
+            .write()
+            .unwrap()
+            .insert(self.server_id, aatxe_client);
+
+        self
+    }
+
+    pub(crate) fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub(crate) fn server_id(&self) -> ServerId {
+        self.server_id
+    }
+
+    /// Handles the given raw IRC message (without the terminating CR-LF sequence) as though it had
+    /// been received from the bot's server, and returns the messages that the bot sends in
+    /// response, in order and without the terminating CR-LF sequence.
+    ///
+    /// As the bot handles commands and triggers in threads of their own, this waits briefly for
+    /// output, so it shouldn't be used with handlers whose reactions are delayed.
+    pub(crate) fn feed(&self, line: &str) -> Vec<String> {
+        let msg = line
+            .parse::<Message>()
+            .unwrap_or_else(|err| panic!("Invalid IRC message {:?}: {}", line, err));
+
+        if let Err(err) = irc_comm::handle_msg(&self.state, self.server_id, &self.state.outbox, msg)
+        {
+            panic!("Failed to handle IRC message {:?}: {}", line, err);
+        }
+
+        self.wait_for_output(FIRST_OUTPUT_TIMEOUT)
+    }
+
+    /// Waits up to the given time for the bot to send messages other than in response to a
+    /// message fed to it, such as delayed reactions, and returns them as `feed` does.
+    pub(crate) fn wait_for_output(&self, timeout: Duration) -> Vec<String> {
+        let mut output = Vec::new();
+        let mut timeout = timeout;
+
+        while let Ok(record) = self.outbox_receiver.recv_timeout(timeout) {
+            assert_eq!(record.server_id, self.server_id);
+            push_lines(&mut output, record.output);
+            timeout = MORE_OUTPUT_TIMEOUT;
+        }
+
+        output
+    }
+}
+
+impl Drop for TestBot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.state.module_data_path);
+    }
+}
+
+fn push_lines(output: &mut Vec<String>, reaction: LibReaction<Message>) {
+    match reaction {
+        LibReaction::RawMsg(msg) => output.push(msg.to_string().trim_end().to_owned()),
+        LibReaction::Multi(reactions) => {
+            for reaction in reactions {
+                push_lines(output, reaction)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::irc_comm::INVITE_REJOIN_INTERVAL;
+    use modules;
+    use std::time::Instant;
+    use util::irc::ChannelMap;
+    use util::irc::ChannelName;
+
+    #[test]
+    fn ping() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: ping"),
+            ["PRIVMSG #test :alice: pong"]
+        );
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG test-bot :ping"),
+            ["PRIVMSG alice :pong"]
+        );
+        assert!(bot
+            .feed(":alice!alice@example.org PRIVMSG #test :ping")
+            .is_empty());
+    }
+
+    #[test]
+    fn cap_tracking() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let has_cap = |cap| bot.state().has_cap(bot.server_id(), cap).unwrap();
+
+        assert!(!has_cap("account-tag"));
+
+        bot.feed(":irc.example.net CAP test-bot ACK :multi-prefix account-tag");
+        assert!(has_cap("multi-prefix"));
+        assert!(has_cap("account-tag"));
+
+        bot.feed(":irc.example.net CAP test-bot NAK :away-notify");
+        assert!(!has_cap("away-notify"));
+
+        bot.feed(":irc.example.net CAP test-bot ACK :-multi-prefix");
+        assert!(!has_cap("multi-prefix"));
+        assert!(has_cap("account-tag"));
+
+        bot.feed(":irc.example.net CAP test-bot DEL :account-tag");
+        assert!(!has_cap("account-tag"));
+    }
+
+    #[test]
+    fn ctcp() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default(), modules::karma()]);
+
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG test-bot :\x01PING 123\x01"),
+            ["NOTICE alice :\x01PING 123\x01"]
+        );
+        assert!(bot
+            .feed(":alice!alice@example.org PRIVMSG #test :\x01PING 123\x01")
+            .is_empty());
+
+        // Actions are seen by triggers that are always watching, but aren't bot commands.
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG #test :\x01ACTION gives alice++\x01"),
+            ["PRIVMSG #test :alice: You can't change your own karma."]
+        );
+        assert!(bot
+            .feed(":alice!alice@example.org PRIVMSG test-bot :\x01ACTION ping\x01")
+            .is_empty());
+    }
+
+    #[test]
+    fn greeting() {
+        let bot = TestBot::new(
+            "{nickname: test-bot, servers: [{name: test, host: irc.example.net, port: 6697, \
+             channels: [{name: '#test', greeting: Welcome!}]}]}",
+            vec![modules::default()],
+        );
+
+        assert_eq!(
+            bot.feed(":test-bot!bot@example.org JOIN #test"),
+            ["PRIVMSG #test :Welcome!"]
+        );
+        assert!(bot.feed(":alice!alice@example.org JOIN #test").is_empty());
+        assert!(bot.feed(":test-bot!bot@example.org JOIN #other").is_empty());
+    }
+
+    #[test]
+    fn invite_joins() {
+        let bot = TestBot::new(
+            "{nickname: test-bot, accept invites: true, servers: [{name: test, host: \
+             irc.example.net, port: 6697}]}",
+            vec![modules::default()],
+        )
+        .with_mock_connection("");
+        let invite = |channel| {
+            bot.feed(&format!(
+                ":alice!alice@example.org INVITE test-bot {}",
+                channel
+            ))
+        };
+        let old_channel = ChannelName::new("#old").unwrap();
+
+        if let Some(long_ago) = Instant::now().checked_sub(INVITE_REJOIN_INTERVAL) {
+            bot.state
+                .invite_joins
+                .lock()
+                .unwrap()
+                .entry(bot.server_id)
+                .or_insert_with(ChannelMap::new)
+                .insert(old_channel.clone(), long_ago);
+        }
+
+        assert_eq!(invite("#tea"), ["JOIN #tea"]);
+
+        // The bot doesn't rejoin a channel that it joined on invitation in the past minute, but
+        // it forgets older such joins.
+        assert!(invite("#tea").is_empty());
+        assert!(!bot.state.invite_joins.lock().unwrap()[&bot.server_id].contains_key(&old_channel));
+        assert_eq!(invite("#old"), ["JOIN #old"]);
+    }
+
+    #[test]
+    fn max_msgs_per_reaction() {
+        let bot = TestBot::new(
+            "{nickname: test-bot, max messages per reaction: 3, servers: [{name: test, host: \
+             irc.example.net, port: 6697}]}",
+            vec![modules::default(), modules::test()],
+        );
+
+        // A single reply of many lines is limited like multiple replies.
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: echo-args [1, 2, 3, 4, 5]"),
+            [
+                "PRIVMSG #test :alice: - 1",
+                "PRIVMSG #test :- 2",
+                "PRIVMSG #test :(output truncated)"
+            ]
+        );
+    }
+
+    #[test]
+    fn delayed_reaction() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default(), modules::remind()]);
+
+        assert!(bot
+            .feed(":dave!dave@example.org PRIVMSG #test :test-bot: remind {in: 2s, msg: tea}")
+            .is_empty());
+        assert_eq!(
+            bot.wait_for_output(Duration::from_secs(3)),
+            ["PRIVMSG #test :dave: tea"]
+        );
+    }
+
+    #[test]
+    fn whois() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: whois bob"),
+            ["WHOIS bob"]
+        );
+        assert!(bot
+            .feed(":irc.example.net 311 test-bot bob bob host.example.net * :Bob")
+            .is_empty());
+        assert!(bot
+            .feed(":irc.example.net 319 test-bot bob :#secret")
+            .is_empty());
+        assert_eq!(
+            bot.feed(":irc.example.net 318 test-bot bob :End of /WHOIS list."),
+            ["PRIVMSG #test :alice: bob is bob@host.example.net (Bob)."]
+        );
+    }
+
+    #[test]
+    fn pm_requires_cmd_prefix() {
+        let bot = TestBot::new(
+            "{nickname: test-bot, command prefix: '.', require command prefix in private \
+             messages: true, servers: [{name: test, host: irc.example.net, port: 6697}]}",
+            vec![modules::default()],
+        );
+
+        assert!(bot
+            .feed(":alice!alice@example.org PRIVMSG test-bot :ping")
+            .is_empty());
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG test-bot :.ping"),
+            ["PRIVMSG alice :pong"]
+        );
+
+        // The bot's message to itself, with which it learns its message prefix, doesn't use the
+        // command prefix.
+        bot.feed(":test-bot!bot@host.example.net PRIVMSG test-bot :!!! UPDATE MESSAGE PREFIX !!!");
+        assert_eq!(
+            bot.state()
+                .read_msg_prefix(bot.server_id())
+                .unwrap()
+                .parse()
+                .host,
+            Some("host.example.net")
+        );
+    }
+}
@@ -27,6 +27,11 @@ pub struct Trigger {
 
     pub priority: TriggerPriority,
 
+    /// Whether the trigger has the attribute [`TriggerAttr::AlwaysWatching`]
+    ///
+    /// [`TriggerAttr::AlwaysWatching`]: <enum.TriggerAttr.html#variant.AlwaysWatching>
+    pub always_watching: bool,
+
     #[debug(skip)]
     pub(super) handler: Arc<TriggerHandler>,
 
@@ -45,7 +50,10 @@ pub enum TriggerAttr {
     /// Use this attribute for triggers that should trigger even on messages that aren't addressed
     /// to the bot.
     ///
-    /// As of 2018-01-11, this doesn't actually do anything yet.
+    /// Such a trigger's regex is matched against the whole text of every message that the bot
+    /// sees, whether addressed to it or not, and every such trigger that matches is run, in
+    /// addition to any bot command or other trigger that the message invokes. This suits triggers
+    /// that passively watch conversation, which should not preclude one another.
     AlwaysWatching,
 }
 
@@ -86,7 +94,8 @@ impl Trigger {
     }
 }
 
-/// Returns `None` if no trigger matched.
+/// Runs one trigger without the attribute `AlwaysWatching` that matches the given text, chosen at
+/// random from among those of the highest priority. Returns `None` if no trigger matched.
 pub(super) fn run_any_matching(
     state: &State,
     text: &str,
@@ -102,6 +111,8 @@ pub(super) fn run_any_matching(
         if let Some(t) = triggers
             .rand_iter()
             .with_rng(state.rng()?.deref_mut())
+            .filter(|t| !t.always_watching)
+            .filter(|t| !state.module_is_disabled(&t.provider.name).unwrap_or(true))
             .filter(|t| t.read_regex().map(|rx| rx.is_match(text)).unwrap_or(false))
             .next()
         {
@@ -110,11 +121,38 @@ pub(super) fn run_any_matching(
         }
     }
 
-    let trigger = match trigger {
-        Some(t) => t,
-        None => return Ok(None),
-    };
+    match trigger {
+        Some(t) => run(state, t, text, msg_metadata).map(Some),
+        None => Ok(None),
+    }
+}
 
+/// Runs every trigger with the attribute `AlwaysWatching` that matches the given text, in order of
+/// priority, highest first, returning their results, each of which may be an error independently
+/// of the others.
+pub(super) fn run_always_watching(
+    state: &State,
+    text: &str,
+    msg_metadata: &MsgMetadata,
+) -> Vec<Result<BotCmdResult>> {
+    state
+        .triggers
+        .values()
+        .rev()
+        .flat_map(|triggers| triggers.iter())
+        .filter(|t| t.always_watching)
+        .filter(|t| !state.module_is_disabled(&t.provider.name).unwrap_or(true))
+        .filter(|t| t.read_regex().map(|rx| rx.is_match(text)).unwrap_or(false))
+        .map(|t| run(state, t, text, msg_metadata))
+        .collect()
+}
+
+fn run(
+    state: &State,
+    trigger: &Trigger,
+    text: &str,
+    msg_metadata: &MsgMetadata,
+) -> Result<BotCmdResult> {
     let ctx = HandlerContext {
         state,
         this_feature: ModuleFeatureRef::Trigger(trigger),
@@ -128,9 +166,7 @@ pub(super) fn run_any_matching(
          trigger didn't match!",
     );
 
-    Ok(Some(util::run_handler(
-        "trigger",
-        trigger.name.clone(),
-        || trigger.handler.run(ctx, args),
-    )?))
+    util::run_handler("trigger", trigger.name.clone(), || {
+        trigger.handler.run(ctx, args)
+    })
 }
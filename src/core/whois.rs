@@ -0,0 +1,186 @@
+//! Support for the `WHOIS` IRC command, whose results arrive as a series of numeric replies that
+//! must be collected and assembled before they can be passed on to whoever asked for them.
+
+use super::irc_send::push_to_outbox;
+use super::irc_send::OutboxPort;
+use super::MsgDest;
+use super::Result;
+use super::ServerId;
+use super::State;
+use irc::client::prelude as aatxe;
+use std::time::Duration;
+use std::time::Instant;
+use util::irc::NickName;
+use util::lock::MutexExt;
+
+/// How long the bot waits for a server to answer a `WHOIS` query in full before forgetting the
+/// query
+const WHOIS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The greatest number of `WHOIS` queries that may be pending at once; if another is requested,
+/// the oldest is forgotten
+const MAX_PENDING_WHOIS: usize = 32;
+
+/// A `WHOIS` query that has been sent to a server and not yet answered in full
+#[derive(Debug)]
+pub(super) struct PendingWhois {
+    server_id: ServerId,
+
+    nick: NickName,
+
+    /// The targets to which the result should be sent, each with the nickname of the user to whom
+    /// the result should be addressed, or the empty string for no addressee
+    requesters: Vec<(String, String)>,
+
+    info: Vec<String>,
+
+    /// When the query was sent
+    sent_time: Instant,
+}
+
+impl State {
+    /// Registers a request for `WHOIS` information about the user with the given nickname on the
+    /// server with the given `ServerId`. When the server's reply has been received in full, it will
+    /// be sent to `reply_target`, addressed to `addressee` unless that is empty.
+    ///
+    /// Returns whether a `WHOIS` query needs to be sent, i.e., `false` if a query about the same
+    /// user already is pending, in which case its result will be sent to this requester as well.
+    ///
+    /// A query that the server hasn't answered in full within a minute is forgotten, as is the
+    /// oldest pending query if there are too many.
+    pub fn request_whois(
+        &self,
+        server_id: ServerId,
+        nick: &NickName,
+        reply_target: &str,
+        addressee: &str,
+    ) -> Result<bool> {
+        let mut pending = self
+            .pending_whois
+            .lock_clean("the pending `WHOIS` queries")?;
+        let requester = (reply_target.to_owned(), addressee.to_owned());
+
+        pending.retain(|query| query.sent_time.elapsed() < WHOIS_TIMEOUT);
+
+        if let Some(query) = pending.iter_mut().find(|q| q.is_about(server_id, nick)) {
+            query.requesters.push(requester);
+            return Ok(false);
+        }
+
+        if pending.len() >= MAX_PENDING_WHOIS {
+            let query = pending.remove(0);
+            warn!("Forgetting unanswered `WHOIS` query about {}.", query.nick);
+        }
+
+        pending.push(PendingWhois {
+            server_id,
+            nick: nick.clone(),
+            requesters: vec![requester],
+            info: Vec::new(),
+            sent_time: Instant::now(),
+        });
+
+        Ok(true)
+    }
+}
+
+impl PendingWhois {
+    fn is_about(&self, server_id: ServerId, nick: &NickName) -> bool {
+        self.server_id == server_id && self.nick == *nick
+    }
+}
+
+/// Returns whether the given numeric reply is one that `handle_whois_reply` handles.
+pub(super) fn is_whois_reply(response: aatxe::Response) -> bool {
+    [
+        aatxe::Response::RPL_WHOISUSER,
+        aatxe::Response::RPL_WHOISSERVER,
+        aatxe::Response::ERR_NOSUCHNICK,
+        aatxe::Response::RPL_ENDOFWHOIS,
+    ]
+    .contains(&response)
+}
+
+/// Handles a numeric reply to a `WHOIS` query, recording the information that it carries, or, if
+/// it is `RPL_ENDOFWHOIS`, sending the assembled information to those who requested it.
+///
+/// Replies to `WHOIS` queries that the bot did not send on a user's behalf are ignored, as is the
+/// list of channels that the user is in (`RPL_WHOISCHANNELS`), which may include secret or private
+/// channels that the bot can see but the requester shouldn't.
+pub(super) fn handle_whois_reply(
+    state: &State,
+    server_id: ServerId,
+    outbox: &OutboxPort,
+    response: aatxe::Response,
+    args: &[String],
+    suffix: Option<&str>,
+) -> Result<()> {
+    // The first argument is the bot's own nickname; the second is the nickname queried.
+    let nick = match args.get(1).map(|nick| NickName::new(nick.as_str())) {
+        Some(Ok(nick)) => nick,
+        _ => return Ok(()),
+    };
+    let suffix = suffix.unwrap_or("");
+
+    let mut pending = state
+        .pending_whois
+        .lock_clean("the pending `WHOIS` queries")?;
+
+    let idx = match pending.iter().position(|q| q.is_about(server_id, &nick)) {
+        Some(idx) => idx,
+        None => return Ok(()),
+    };
+
+    let line = match response {
+        aatxe::Response::RPL_WHOISUSER => match (args.get(2), args.get(3)) {
+            (Some(user), Some(host)) => format!("{} is {}@{} ({})", nick, user, host, suffix),
+            _ => return Ok(()),
+        },
+        aatxe::Response::RPL_WHOISSERVER => match args.get(2) {
+            Some(server) => format!("connected to {} ({})", server, suffix),
+            None => return Ok(()),
+        },
+        aatxe::Response::ERR_NOSUCHNICK => format!("There is no user with the nickname {}", nick),
+        aatxe::Response::RPL_ENDOFWHOIS => {
+            let query = pending.remove(idx);
+            drop(pending);
+            return send_result(state, outbox, query);
+        }
+        _ => return Ok(()),
+    };
+
+    pending[idx].info.push(line);
+
+    Ok(())
+}
+
+fn send_result(state: &State, outbox: &OutboxPort, query: PendingWhois) -> Result<()> {
+    let PendingWhois {
+        server_id,
+        nick,
+        requesters,
+        info,
+        ..
+    } = query;
+
+    let result = if info.is_empty() {
+        format!("The server sent no information about {}.", nick)
+    } else {
+        format!("{}.", info.join("; "))
+    };
+
+    for (target, addressee) in requesters {
+        let dest = MsgDest {
+            server_id,
+            target: &target,
+        };
+
+        push_to_outbox(
+            outbox,
+            server_id,
+            state.compose_msg(dest, addressee, &result)?,
+        );
+    }
+
+    Ok(())
+}
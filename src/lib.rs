@@ -1,17 +1,20 @@
-#![recursion_limit = "128"]
+#![recursion_limit = "256"]
 #![deny(unsafe_code)]
 
+extern crate chrono;
 extern crate clockpro_cache;
 extern crate crossbeam_channel;
 extern crate inlinable_string;
 extern crate irc;
 extern crate itertools;
+extern crate native_tls;
 extern crate quantiles;
 extern crate rand;
 extern crate rando;
 extern crate ref_slice;
 extern crate regex;
 extern crate serde;
+extern crate serde_json;
 extern crate serde_yaml;
 extern crate smallbitvec;
 extern crate smallvec;
@@ -0,0 +1,423 @@
+use chrono::DateTime;
+use chrono::Local;
+use core::*;
+use irc::client::prelude as aatxe;
+use irc::proto::Message;
+use serde_yaml;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::Once;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+use util::irc::ChannelName;
+use util::lock::MutexExt;
+use util::lock::ReadLockExt;
+use util::lock::WriteLockExt;
+use util::regex::config as rx_cfg;
+use util::regex::Regex;
+
+/// The name of the module's configuration file, in the module data directory
+const CONFIG_FILE_NAME: &str = "channel-log.yaml";
+
+/// The name of the directory, in the module data directory, in which logs are written
+const LOG_DIR_NAME: &str = "channel-log";
+
+/// The name (without extension) given to log files that are not rotated
+const UNROTATED_LOG_FILE_STEM: &str = "all";
+
+/// The delimiter of Client-to-Client Protocol (CTCP) messages
+const CTCP_DELIM: char = '\x01';
+
+const DEFAULT_FLUSH_INTERVAL_SECS: u16 = 10;
+
+lazy_static! {
+    static ref CONFIG: RwLock<Option<ChannelLogConfig>> = RwLock::new(None);
+    static ref LOG_FILES: Mutex<BTreeMap<PathBuf, BufWriter<File>>> = Mutex::new(BTreeMap::new());
+}
+
+static FLUSHER_START: Once = Once::new();
+
+/// This module writes the messages sent in the channels in which it is enabled, and users' joining
+/// and leaving those channels, to log files in the bot's module data directory.
+///
+/// The module is configured with the file `channel-log.yaml` in the bot's module data directory,
+/// which should constitute a YAML mapping with the following fields:
+///
+/// - `channels` — The value of this field should be a string, which is to be parsed as a [`regex`]
+/// and matched against the channel identifiers (as defined in the documentation of the bot's
+/// configuration) of the channels in which the bot sees activity, to determine whether that
+/// channel should be logged.
+///
+/// - `rotation` — The value of this field, if specified, should be one of the strings `daily`,
+/// `monthly`, `yearly`, and `never`, specifying how often a new log file should be started for
+/// each channel. This field is optional; its value defaults to `daily`.
+///
+/// - `flush interval` — The value of this field, if specified, should be a positive integer, which
+/// is to be used as the number of seconds for which log lines may be buffered before they are
+/// written out. This field is optional; its value defaults to 10. Any lines still buffered are
+/// written out when the bot shuts down.
+///
+/// If the file does not exist, nothing is logged. The file is read when the module is loaded or
+/// reloaded.
+///
+/// The logs are written in the directory `channel-log` in the module data directory, in a
+/// subdirectory for each server, named with the server's name as given in the bot's
+/// configuration, and therein a subdirectory for each channel. Each log file is named with the
+/// local date on which it was started (e.g., `2018-08-27.log` with daily rotation), or `all.log`
+/// if logs are not rotated.
+///
+/// Log lines are of the form `2018-08-27 21:16:05 <c74d> Hello.`, which is suitable for pasting
+/// into `chat`-format quotations for the `quote` module. The bot's own messages are not logged.
+///
+/// [`regex`]: <https://docs.rs/regex/*/regex/#syntax>
+pub fn mk() -> Module {
+    mk_module("channel-log")
+        .raw_handler("channel-log", Box::new(log_msg))
+        .on_load(Box::new(on_load))
+        .on_shutdown(Box::new(on_shutdown))
+        .end()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChannelLogConfig {
+    channels: Regex<rx_cfg::Anchored<rx_cfg::SizeLimit>>,
+
+    #[serde(default = "default_rotation")]
+    rotation: Rotation,
+
+    #[serde(default = "default_flush_interval_secs", rename = "flush interval")]
+    flush_interval_secs: u16,
+}
+
+/// How often a new log file is started
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Rotation {
+    Daily,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl Rotation {
+    /// Returns the name, without extension, of the log file to which a line logged at the given
+    /// time should be written.
+    fn file_stem(self, time: &DateTime<Local>) -> String {
+        match self {
+            Rotation::Daily => time.format("%Y-%m-%d").to_string(),
+            Rotation::Monthly => time.format("%Y-%m").to_string(),
+            Rotation::Yearly => time.format("%Y").to_string(),
+            Rotation::Never => UNROTATED_LOG_FILE_STEM.to_owned(),
+        }
+    }
+}
+
+fn default_rotation() -> Rotation {
+    Rotation::Daily
+}
+
+fn default_flush_interval_secs() -> u16 {
+    DEFAULT_FLUSH_INTERVAL_SECS
+}
+
+fn on_load(state: &State) -> Result<()> {
+    let path = state.module_data_path()?.join(CONFIG_FILE_NAME);
+
+    let new_config = if path.exists() {
+        let config: ChannelLogConfig = serde_yaml::from_str(&fs::read_to_string(&path)?)?;
+
+        if config.flush_interval_secs == 0 {
+            return Err(format!(
+                "The `channel-log` configuration file {:?} sets `flush interval` to zero.",
+                path
+            )
+            .into());
+        }
+
+        Some(config)
+    } else {
+        debug!("No `channel-log` configuration file found; not logging channels.");
+        None
+    };
+
+    *CONFIG.write_clean("the `channel-log` configuration")? = new_config;
+
+    let mut spawn_result = Ok(());
+
+    FLUSHER_START.call_once(|| {
+        spawn_result = thread::Builder::new()
+            .name("channel-log flusher".to_owned())
+            .spawn(run_flusher)
+            .map(|thread::JoinHandle { .. }| ())
+    });
+
+    spawn_result.map_err(|e| ErrorKind::ThreadSpawnFailure(e).into())
+}
+
+/// Periodically writes out and closes all open log files, for as long as the bot runs.
+fn run_flusher() {
+    loop {
+        let interval_secs = match CONFIG.read_clean("the `channel-log` configuration") {
+            Ok(config) => config
+                .as_ref()
+                .map(|config| config.flush_interval_secs)
+                .unwrap_or(DEFAULT_FLUSH_INTERVAL_SECS),
+            Err(e) => {
+                error!("Failed to read the `channel-log` configuration: {}", e);
+                DEFAULT_FLUSH_INTERVAL_SECS
+            }
+        };
+
+        thread::sleep(Duration::from_secs(interval_secs.into()));
+
+        if let Err(e) = flush_log_files() {
+            error!("Failed to flush channel logs: {}", e);
+        }
+    }
+}
+
+/// Writes out any lines not yet written when the bot shuts down.
+fn on_shutdown(_: &State) -> Result<()> {
+    flush_log_files()
+}
+
+/// Writes out and closes all open log files.
+fn flush_log_files() -> Result<()> {
+    let mut log_files = LOG_FILES.lock_clean("the open `channel-log` files")?;
+
+    for (path, writer) in log_files.iter_mut() {
+        if let Err(e) = writer.flush() {
+            error!("Failed to write to the channel log {:?}: {}", path, e);
+        }
+    }
+
+    log_files.clear();
+
+    Ok(())
+}
+
+fn log_msg(state: &State, server_id: ServerId, msg: &Message) -> Result<Reaction> {
+    let config = CONFIG.read_clean("the `channel-log` configuration")?;
+
+    let config = match *config {
+        Some(ref config) => config,
+        None => return Ok(Reaction::None),
+    };
+
+    let now = Local::now();
+
+    let (channel, line) = match format_line(&now, msg) {
+        Some(entry) => entry,
+        None => return Ok(Reaction::None),
+    };
+
+    let channel_id = match state.channel_id(MsgDest {
+        server_id,
+        target: channel,
+    })? {
+        Some(id) => id,
+        None => return Ok(Reaction::None),
+    };
+
+    if !config.channels.is_match(&channel_id.to_string()) {
+        return Ok(Reaction::None);
+    }
+
+    let path = state
+        .module_data_path()?
+        .join(LOG_DIR_NAME)
+        .join(path_component(channel_id.server()))
+        .join(path_component(
+            &channel_id.channel().to_string().to_lowercase(),
+        ))
+        .join(config.rotation.file_stem(&now))
+        .with_extension("log");
+
+    write_line(&path, &line)?;
+
+    Ok(Reaction::None)
+}
+
+/// Appends the given line to the log file at the given path, opening the file if it isn't already
+/// open.
+fn write_line(path: &Path, line: &str) -> Result<()> {
+    let mut log_files = LOG_FILES.lock_clean("the open `channel-log` files")?;
+
+    if !log_files.contains_key(path) {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        log_files.insert(path.to_owned(), BufWriter::new(file));
+    }
+
+    let writer = log_files
+        .get_mut(path)
+        .expect("We just made sure that this log file is open!");
+
+    writeln!(writer, "{}", line)?;
+
+    Ok(())
+}
+
+/// Returns the name of the channel to which the given message pertains and the line with which
+/// the message should be logged, or `None` if the message is not of a kind that is logged.
+fn format_line<'msg>(time: &DateTime<Local>, msg: &'msg Message) -> Option<(&'msg str, String)> {
+    let timestamp = time.format("%Y-%m-%d %H:%M:%S");
+    let nick = msg.source_nickname()?;
+
+    let (channel, entry) = match msg.command {
+        aatxe::Command::PRIVMSG(ref target, ref text) => {
+            if text.starts_with(CTCP_DELIM) {
+                let mut ctcp = text.trim_matches(CTCP_DELIM).splitn(2, ' ');
+
+                match (ctcp.next(), ctcp.next()) {
+                    (Some("ACTION"), Some(action)) => (target, format!("* {} {}", nick, action)),
+                    _ => return None,
+                }
+            } else {
+                (target, format!("<{}> {}", nick, text))
+            }
+        }
+        aatxe::Command::NOTICE(ref target, ref text) => (target, format!("-{}- {}", nick, text)),
+        aatxe::Command::JOIN(ref channel, ..) => (
+            channel,
+            format!("-!- {} [{}] has joined {}", nick, user_host(msg), channel),
+        ),
+        aatxe::Command::PART(ref channel, ref reason) => (
+            channel,
+            format!(
+                "-!- {} [{}] has left {} [{}]",
+                nick,
+                user_host(msg),
+                channel,
+                reason.as_ref().map(String::as_str).unwrap_or_default()
+            ),
+        ),
+        _ => return None,
+    };
+
+    if ChannelName::new(channel.as_str()).is_err() {
+        return None;
+    }
+
+    Some((channel, format!("{} {}", timestamp, entry)))
+}
+
+/// Returns the `user@host` part of the given message's prefix, or an empty string if it has none.
+fn user_host(msg: &Message) -> &str {
+    msg.prefix
+        .as_ref()
+        .and_then(|prefix| prefix.find('!').map(|idx| &prefix[idx + 1..]))
+        .unwrap_or_default()
+}
+
+/// Returns the given string with any characters that might not be safe in a file name, and any
+/// leading `.`, replaced with percent-encoded sequences.
+fn path_component(s: &str) -> String {
+    let mut component = String::with_capacity(s.len());
+
+    for (i, c) in s.chars().enumerate() {
+        if c.is_alphanumeric() || "#&+!-_".contains(c) || (c == '.' && i > 0) {
+            component.push(c);
+        } else {
+            for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                component.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+
+    component
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use chrono::TimeZone;
+
+    fn time() -> DateTime<Local> {
+        Local
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(2018, 8, 27)
+                    .unwrap()
+                    .and_hms_opt(21, 16, 5)
+                    .unwrap(),
+            )
+            .unwrap()
+    }
+
+    fn line(raw: &str) -> Option<(String, String)> {
+        let msg = raw.parse::<Message>().unwrap();
+        format_line(&time(), &msg).map(|(chan, line)| (chan.to_owned(), line))
+    }
+
+    #[test]
+    fn line_formats() {
+        assert_eq!(
+            line(":c74d!c@example.com PRIVMSG #test :Hello, world."),
+            Some((
+                "#test".to_owned(),
+                "2018-08-27 21:16:05 <c74d> Hello, world.".to_owned()
+            ))
+        );
+        assert_eq!(
+            line(":c74d!c@example.com PRIVMSG #test :\x01ACTION waves\x01")
+                .unwrap()
+                .1,
+            "2018-08-27 21:16:05 * c74d waves"
+        );
+        assert_eq!(
+            line(":c74d!c@example.com NOTICE #test :Hi.").unwrap().1,
+            "2018-08-27 21:16:05 -c74d- Hi."
+        );
+        assert_eq!(
+            line(":c74d!c@example.com JOIN #test").unwrap().1,
+            "2018-08-27 21:16:05 -!- c74d [c@example.com] has joined #test"
+        );
+        assert_eq!(
+            line(":c74d!c@example.com PART #test :Bye").unwrap().1,
+            "2018-08-27 21:16:05 -!- c74d [c@example.com] has left #test [Bye]"
+        );
+    }
+
+    #[test]
+    fn unlogged_msgs() {
+        assert_eq!(line(":c74d!c@example.com PRIVMSG bot :Hello."), None);
+        assert_eq!(
+            line(":c74d!c@example.com PRIVMSG #test :\x01VERSION\x01"),
+            None
+        );
+        assert_eq!(line(":c74d!c@example.com NICK c75d"), None);
+        assert_eq!(line("PING :example.com"), None);
+    }
+
+    #[test]
+    fn rotation_file_stems() {
+        assert_eq!(Rotation::Daily.file_stem(&time()), "2018-08-27");
+        assert_eq!(Rotation::Monthly.file_stem(&time()), "2018-08");
+        assert_eq!(Rotation::Yearly.file_stem(&time()), "2018");
+        assert_eq!(Rotation::Never.file_stem(&time()), "all");
+    }
+
+    #[test]
+    fn path_components_are_safe() {
+        assert_eq!(path_component("#rust"), "#rust");
+        assert_eq!(path_component("#a/b"), "#a%2Fb");
+        assert_eq!(path_component("#a\\b:c"), "#a%5Cb%3Ac");
+        assert_eq!(path_component("#ünï"), "#ünï");
+        assert_eq!(path_component("irc.example.com"), "irc.example.com");
+        assert_eq!(path_component(".."), "%2E.");
+    }
+}
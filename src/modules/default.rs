@@ -1,19 +1,38 @@
 use core::BotCmdAuthLvl as Auth;
 use core::*;
+use itertools::Itertools;
+use log;
+use log::LevelFilter;
+use rand::Rng;
 use regex::Captures;
 use std::borrow::Cow;
+use std::iter;
+use std::str::FromStr;
 use try_map::FallibleMapExt;
 use util;
+use util::fmt::FmtDuration;
+use util::irc::NickName;
+use util::regex::config as rx_cfg;
+use util::regex::Regex;
 use util::to_cow_owned;
 use util::yaml::str::YAML_STR_CHAN;
 use util::yaml::str::YAML_STR_CMD;
+use util::yaml::str::YAML_STR_HOST;
 use util::yaml::str::YAML_STR_LIST;
 use util::yaml::str::YAML_STR_MSG;
+use util::yaml::str::YAML_STR_NICK;
+use util::yaml::str::YAML_STR_SEARCH;
+use util::yaml::str::YAML_STR_USER;
 use util::yaml::FW_SYNTAX_CHECK_FAIL;
 use yaml_rust::Yaml;
 
+const MODULE_NAME: &str = "default";
+
+/// The maximum number of commands that the `stats` command lists
+const STATS_LEADERBOARD_LEN: usize = 10;
+
 pub fn mk() -> Module {
-    mk_module("default")
+    mk_module(MODULE_NAME)
         .command(
             "join",
             "<channel>",
@@ -41,6 +60,32 @@ pub fn mk() -> Module {
             Box::new(quit),
             &[],
         )
+        .command(
+            "ignore",
+            "{nick: '[nickname]', user: '[username]', host: '[hostname]'}",
+            "Have the bot ignore messages from users matching all of the given fields, which are \
+             compared case-insensitively. At least one field must be given. The bot's \
+             administrators cannot be ignored.",
+            Auth::Admin,
+            Box::new(ignore),
+            &[],
+        )
+        .command(
+            "unignore",
+            "{nick: '[nickname]', user: '[username]', host: '[hostname]'}",
+            "Remove an entry, given exactly as it was added, from the bot's ignore list.",
+            Auth::Admin,
+            Box::new(unignore),
+            &[],
+        )
+        .command(
+            "ignore-list",
+            "",
+            "Request a list of the entries in the bot's ignore list.",
+            Auth::Admin,
+            Box::new(ignore_list),
+            &[],
+        )
         .command(
             "ping",
             "",
@@ -49,6 +94,68 @@ pub fn mk() -> Module {
             Box::new(ping),
             &[],
         )
+        .command(
+            "uptime",
+            "",
+            "Request how long the bot has been running, and how long it has been connected to each \
+             IRC server.",
+            Auth::Public,
+            Box::new(uptime),
+            &[],
+        )
+        .command(
+            "channels",
+            "",
+            "Request a list of the channels that the bot is in on the current server.",
+            Auth::Admin,
+            Box::new(channels),
+            &[],
+        )
+        .command(
+            "users",
+            "{chan: '[channel]'}",
+            "Request a list of the users whom the bot sees in the given channel (defaults to the \
+             current channel).",
+            Auth::Admin,
+            Box::new(users),
+            &[],
+        )
+        .command(
+            "stats",
+            "",
+            "Request a list of the bot's most-used commands, with how many times each has been \
+             used since the bot started or the counts were reset with `stats-reset`.",
+            Auth::Public,
+            Box::new(stats),
+            &[],
+        )
+        .command(
+            "stats-reset",
+            "",
+            "Reset the counts of how many times each command has been used.",
+            Auth::Admin,
+            Box::new(stats_reset),
+            &[],
+        )
+        .command(
+            "whois",
+            "<nickname>",
+            "Request information about the user with the given nickname, as reported by the IRC \
+             server in reply to a `WHOIS` query.",
+            Auth::Public,
+            Box::new(whois),
+            &[],
+        )
+        .command(
+            "choose",
+            "'...'",
+            "Have the bot choose one of the given options at random. The options may be given \
+             separated by pipes or commas, like `tea | coffee | water`, or as a YAML sequence, \
+             like `[tea, coffee, water]`.",
+            Auth::Public,
+            Box::new(choose),
+            &[],
+        )
         .command(
             "framework-info",
             "",
@@ -58,10 +165,58 @@ pub fn mk() -> Module {
             Box::new(bot_fw_info),
             &[],
         )
+        .command(
+            "modules",
+            "",
+            "Request a list of the bot's loaded modules, along with the commands and triggers that \
+             each provides.",
+            Auth::Admin,
+            Box::new(modules),
+            &[],
+        )
+        .command(
+            "module-disable",
+            "<module>",
+            "Disable the given module, such that the bot ignores its commands and triggers until it \
+             is re-enabled with `module-enable`.",
+            Auth::Admin,
+            Box::new(module_disable),
+            &[],
+        )
+        .command(
+            "module-enable",
+            "<module>",
+            "Re-enable the given module after it has been disabled with `module-disable`, reloading \
+             its configuration and data.",
+            Auth::Admin,
+            Box::new(module_enable),
+            &[],
+        )
+        .command(
+            "config-reload",
+            "",
+            "Have the bot re-read its configuration file, updating which channels can see which \
+             others. Other settings take effect only when the bot is restarted.",
+            Auth::Admin,
+            Box::new(config_reload),
+            &[],
+        )
+        .command(
+            "log-level",
+            "<level>",
+            "Set the greatest level of detail of the bot's log, which may be one of `off`, \
+             `error`, `warn`, `info`, `debug`, and `trace`. The logger of the program running the \
+             bot may further limit which log messages are recorded.",
+            Auth::Admin,
+            Box::new(log_level),
+            &[],
+        )
         .command(
             "help",
-            "{cmd: '[command]', list: '[list name]'}",
-            "Request help with the bot's features, such as commands.",
+            "{cmd: '[command]', list: '[list name]', search: '[regex]'}",
+            "Request help with the bot's features, such as commands. The `search` parameter finds \
+             commands whose names, syntax, or help messages match the given case-insensitive \
+             regular expression.",
             Auth::Public,
             Box::new(help),
             &[],
@@ -137,30 +292,409 @@ fn quit(_: HandlerContext, arg: &Yaml) -> Result<Reaction> {
     Ok(Reaction::Quit(comment))
 }
 
-fn ping(_: HandlerContext, _: &Yaml) -> BotCmdResult {
-    Reaction::Reply("pong".into()).into()
+fn ignore(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let mask = ignore_mask_from_arg(arg)?;
+
+    if mask.is_empty() {
+        return Ok(BotCmdResult::UserErrMsg(
+            "Please give at least one of `nick`, `user`, or `host`.".into(),
+        ));
+    }
+
+    Ok(if state.ignore(mask.clone())? {
+        Reaction::Reply(format!("Now ignoring {}.", mask).into())
+    } else {
+        Reaction::Reply(format!("Already ignoring {}.", mask).into())
+    }
+    .into())
+}
+
+fn unignore(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> Result<Reaction> {
+    let mask = ignore_mask_from_arg(arg)?;
+
+    Ok(if state.unignore(&mask)? {
+        Reaction::Reply(format!("No longer ignoring {}.", mask).into())
+    } else {
+        Reaction::Reply(format!("{} is not in the ignore list.", mask).into())
+    })
+}
+
+fn ignore_list(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    let list = state.ignore_list()?;
+
+    Ok(if list.is_empty() {
+        Reaction::Msg("The ignore list is empty.".into())
+    } else {
+        Reaction::Msg(format!("Ignoring: {}", list.iter().join(", ")).into())
+    })
+}
+
+fn ignore_mask_from_arg(arg: &Yaml) -> Result<IgnoreMask> {
+    let arg = arg.as_hash().expect(FW_SYNTAX_CHECK_FAIL);
+
+    let field = |key: &Yaml, label| {
+        arg.get(key)
+            .try_map(|y| util::yaml::scalar_to_str(y, to_cow_owned, label))
+            .map(|opt| opt.map(Cow::into_owned))
+    };
+
+    Ok(IgnoreMask {
+        nick: field(&YAML_STR_NICK, "the value of the parameter `nick`")?,
+        user: field(&YAML_STR_USER, "the value of the parameter `user`")?,
+        host: field(&YAML_STR_HOST, "the value of the parameter `host`")?,
+    })
+}
+
+fn ping(_: HandlerContext, _: &Yaml) -> Reaction {
+    Reaction::Reply("pong".into())
+}
+
+fn uptime(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    let mut servers = Vec::new();
+
+    for server_id in state.server_ids() {
+        let name = state.server_name(server_id)?;
+
+        servers.push(match state.server_uptime(server_id)? {
+            Some(d) => format!("{} for {}", name, FmtDuration(d)),
+            None => format!("{} (not connected)", name),
+        });
+    }
+
+    Ok(Reaction::Reply(
+        format!(
+            "I've been running for {}. Connected to: {}.",
+            FmtDuration(state.uptime()),
+            servers.join("; ")
+        )
+        .into(),
+    ))
+}
+
+fn channels(ctx: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    let server_id = ctx.request_origin.server_id;
+    let server_name = ctx.state.server_name(server_id)?;
+    let max_len = ctx.state.privmsg_content_max_len(ctx.guess_reply_dest()?)?;
+
+    let (header, mut names) = match ctx.state.joined_channels(server_id)? {
+        Some(names) => (format!("Channels I'm in on {}:", server_name), names),
+        None => (
+            format!(
+                "I'm not tracking which channels I'm in; channels configured for {}:",
+                server_name
+            ),
+            ctx.state
+                .configured_channel_ids()
+                .into_iter()
+                .filter(|id| id.server() == server_name)
+                .map(|id| id.channel().to_string())
+                .collect(),
+        ),
+    };
+
+    if names.is_empty() {
+        return Ok(Reaction::Reply(
+            format!("I'm not in any channels on {}.", server_name).into(),
+        ));
+    }
+
+    names.sort_by(|a, b| util::irc::case_insensitive_str_cmp(a.as_str(), b.as_str()));
+
+    Ok(Reaction::Msgs(
+        pack_into_lines(&header, names.iter().map(AsRef::as_ref), max_len).into(),
+    ))
+}
+
+fn users(ctx: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let MsgDest { server_id, target } = ctx.request_origin;
+    let arg = arg.as_hash().expect(FW_SYNTAX_CHECK_FAIL);
+
+    let chan = arg.get(&YAML_STR_CHAN).try_map(|y| {
+        util::yaml::scalar_to_str(y, Cow::Borrowed, "the value of the parameter `chan`")
+    })?;
+
+    let chan = match (chan, target) {
+        (Some(c), _) => c,
+        (None, t) if ctx.state.is_own_nick(server_id, t)? => {
+            return Ok(BotCmdResult::ArgMissing1To1("channel".into()))
+        }
+        (None, t) => t.into(),
+    };
+
+    let mut nicks = match ctx.state.channel_users(server_id, &chan)? {
+        Some(ref nicks) if nicks.is_empty() => {
+            return Ok(Reaction::Reply(format!("I see no users in {}.", chan).into()).into());
+        }
+        Some(nicks) => nicks,
+        None => {
+            return Ok(Reaction::Reply(format!("I'm not in {}.", chan).into()).into());
+        }
+    };
+
+    nicks.sort_by(|a, b| util::irc::case_insensitive_str_cmp(a.as_str(), b.as_str()));
+
+    let max_len = ctx.state.privmsg_content_max_len(ctx.guess_reply_dest()?)?;
+    let header = format!("Users I see in {} ({}):", chan, nicks.len());
+
+    let lines = pack_into_lines(&header, nicks.iter().map(AsRef::as_ref), max_len);
+
+    Ok(Reaction::Msgs(lines.into()).into())
 }
 
-fn bot_fw_info(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> BotCmdResult {
+fn stats(ctx: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    let usage = ctx.state.command_usage()?;
+
+    if usage.is_empty() {
+        return Ok(Reaction::Reply(
+            "No commands have been used since I started or the counts were reset.".into(),
+        ));
+    }
+
+    let max_len = ctx.state.privmsg_content_max_len(ctx.guess_reply_dest()?)?;
+    let shown = usage.len().min(STATS_LEADERBOARD_LEN);
+    let entries = usage[..shown]
+        .iter()
+        .enumerate()
+        .map(|(i, &(ref name, count))| {
+            let separator = if i + 1 < shown { "," } else { "." };
+            format!("{} ({}){}", name, count, separator)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Reaction::Msgs(
+        pack_into_lines(
+            "Most-used commands:",
+            entries.iter().map(AsRef::as_ref),
+            max_len,
+        )
+        .into(),
+    ))
+}
+
+fn stats_reset(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    state.reset_command_usage()?;
+
+    Ok(Reaction::Reply("Command usage counts reset.".into()))
+}
+
+fn whois(ctx: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let nick = util::yaml::scalar_to_str(arg, Cow::Borrowed, "the nickname")?;
+
+    let nick = match NickName::new(nick.as_ref()) {
+        Ok(nick) => nick,
+        Err(_) => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!("{:?} is not a valid nickname.", nick).into(),
+            ))
+        }
+    };
+
+    let reply_dest = ctx.guess_reply_dest()?;
+    let addressee = if reply_dest.target == ctx.request_origin.target {
+        ctx.invoker.nick.unwrap_or("")
+    } else {
+        ""
+    };
+
+    if ctx
+        .state
+        .request_whois(reply_dest.server_id, &nick, reply_dest.target, addressee)?
+    {
+        Ok(Reaction::RawMsg(format!("WHOIS {}", nick).into()).into())
+    } else {
+        Ok(Reaction::None.into())
+    }
+}
+
+fn choose(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let options = match *arg {
+        Yaml::Array(ref seq) => seq
+            .iter()
+            .map(|y| util::yaml::scalar_to_str(y, to_cow_owned, "each option"))
+            .collect::<util::yaml::Result<Vec<_>>>()?,
+        Yaml::Hash(_) => return Ok(BotCmdResult::SyntaxErr),
+        ref scalar => {
+            let s = util::yaml::scalar_to_str(scalar, Cow::Borrowed, "the options")?;
+            let separator = if s.contains('|') { '|' } else { ',' };
+
+            s.split(separator)
+                .map(|opt| Cow::Owned(opt.to_owned()))
+                .collect()
+        }
+    };
+
+    let options = options
+        .iter()
+        .map(|opt| opt.trim())
+        .filter(|opt| !opt.is_empty())
+        .collect::<Vec<_>>();
+
+    if options.is_empty() {
+        return Ok(BotCmdResult::SyntaxErr);
+    }
+
+    let choice = options[state.rng()?.gen_range(0, options.len())];
+
+    Ok(Reaction::Reply(choice.to_owned().into()).into())
+}
+
+fn bot_fw_info(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> Reaction {
     Reaction::Reply(
         format!(
-            "This bot was built with `{name}.rs`, version {ver}; see <{url}>.",
+            "This bot was built with `{name}.rs`, version {ver} (commit {commit}, built {date}); \
+             see <{url}>.",
             name = state.framework_crate_name(),
             ver = state.framework_version_str(),
+            commit = state.framework_git_commit_str(),
+            date = state.framework_build_date_str(),
             url = state.framework_homepage_url_str(),
         )
         .into(),
     )
-    .into()
 }
 
-fn help(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> BotCmdResult {
+fn modules(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    let features_of_kind = |features: &[ModuleFeatureInfo], kind| {
+        features
+            .iter()
+            .filter(|f| *f.kind() == kind)
+            .map(ModuleFeatureInfo::name)
+            .join(", ")
+    };
+
+    let msgs = state
+        .modules_info()?
+        .into_iter()
+        .map(|(module, features)| {
+            Ok(format!(
+                "- {name}{disabled}: commands: [{cmds}]; triggers: [{triggers}]; raw handlers: \
+                 [{raw_handlers}]",
+                name = module.name(),
+                disabled = if state.module_is_disabled(module.name())? {
+                    " (disabled)"
+                } else {
+                    ""
+                },
+                cmds = features_of_kind(&features, ModuleFeatureKind::Command),
+                triggers = features_of_kind(&features, ModuleFeatureKind::Trigger),
+                raw_handlers = features_of_kind(&features, ModuleFeatureKind::RawHandler),
+            )
+            .into())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Reaction::Msgs(
+        iter::once(format!("= {} loaded module(s):", msgs.len()).into())
+            .chain(msgs)
+            .collect::<Vec<_>>()
+            .into(),
+    ))
+}
+
+fn module_disable(
+    HandlerContext { state, .. }: HandlerContext,
+    arg: &Yaml,
+) -> Result<BotCmdResult> {
+    let name = util::yaml::scalar_to_str(arg, Cow::Borrowed, "the name of the module")?;
+
+    if name == MODULE_NAME {
+        return Ok(BotCmdResult::UserErrMsg(
+            format!(
+                "The module {:?} can't be disabled, as it provides `module-enable`.",
+                name
+            )
+            .into(),
+        ));
+    }
+
+    Ok(match state.disable_module(&name) {
+        Ok(true) => Reaction::Reply(format!("Disabled module {:?}.", name).into()).into(),
+        Ok(false) => {
+            Reaction::Reply(format!("Module {:?} already is disabled.", name).into()).into()
+        }
+        Err(Error(ErrorKind::UnknownModule(_), _)) => {
+            BotCmdResult::UserErrMsg(format!("No module named {:?} is loaded.", name).into())
+        }
+        Err(e) => BotCmdResult::LibErr(e),
+    })
+}
+
+fn module_enable(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let name = util::yaml::scalar_to_str(arg, Cow::Borrowed, "the name of the module")?;
+
+    Ok(match state.enable_module(&name) {
+        Ok(true) => Reaction::Reply(format!("Enabled module {:?}.", name).into()).into(),
+        Ok(false) => {
+            Reaction::Reply(format!("Module {:?} already is enabled.", name).into()).into()
+        }
+        Err(Error(ErrorKind::UnknownModule(_), _)) => {
+            BotCmdResult::UserErrMsg(format!("No module named {:?} is loaded.", name).into())
+        }
+        Err(e) => BotCmdResult::LibErr(e),
+    })
+}
+
+fn config_reload(HandlerContext { state, .. }: HandlerContext, _: &Yaml) -> BotCmdResult {
+    match state.reload_config() {
+        Ok(changed) => Reaction::Reply(
+            format!(
+                "I have reloaded my configuration. The visibility settings of {} {} changed.",
+                changed,
+                if changed == 1 { "channel" } else { "channels" }
+            )
+            .into(),
+        )
+        .into(),
+        Err(e @ Error(ErrorKind::LockPoisoned(_), _)) => BotCmdResult::LibErr(e),
+        Err(e) => {
+            BotCmdResult::UserErrMsg(format!("I could not reload my configuration: {}", e).into())
+        }
+    }
+}
+
+fn log_level(_: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let level_str = util::yaml::scalar_to_str(arg, Cow::Borrowed, "the log level")?;
+
+    let level = match LevelFilter::from_str(level_str.trim()) {
+        Ok(level) => level,
+        Err(_) => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!(
+                    "{:?} is not a log level; try one of `off`, `error`, `warn`, `info`, \
+                     `debug`, and `trace`.",
+                    level_str
+                )
+                .into(),
+            ))
+        }
+    };
+
+    let old_level = log::max_level();
+
+    log::set_max_level(level);
+
+    info!("Changed the log level from {} to {}.", old_level, level);
+
+    Ok(Reaction::Reply(
+        format!(
+            "I have changed my log level from {} to {}.",
+            old_level.to_string().to_lowercase(),
+            level.to_string().to_lowercase()
+        )
+        .into(),
+    )
+    .into())
+}
+
+fn help(ctx: HandlerContext, arg: &Yaml) -> BotCmdResult {
+    let state = ctx.state;
     let arg = arg.as_hash();
 
     let cmd = arg.and_then(|m| m.get(&YAML_STR_CMD));
     let list = arg.and_then(|m| m.get(&YAML_STR_LIST));
+    let search = arg.and_then(|m| m.get(&YAML_STR_SEARCH));
 
-    if [cmd, list].iter().filter(|x| x.is_some()).count() > 1 {
+    if [cmd, list, search].iter().filter(|x| x.is_some()).count() > 1 {
         return Reaction::Msg("Please ask for help with one thing at a time.".into()).into();
     }
 
@@ -194,7 +728,10 @@ fn help(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> BotCmdResul
         let list_names = ["commands", "lists"];
 
         if list_name == "commands" {
-            Reaction::Msg(format!("Available commands: {:?}", state.command_names()).into()).into()
+            match help_list_commands(&ctx) {
+                Ok(r) => r.into(),
+                Err(e) => BotCmdResult::LibErr(e),
+            }
         } else if list_name == "lists" {
             Reaction::Msg(format!("Available lists: {:?}", list_names).into()).into()
         } else {
@@ -211,11 +748,17 @@ fn help(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> BotCmdResul
             )
             .into()
         }
+    } else if let Some(search) = search {
+        match help_search(state, search) {
+            Ok(r) => r,
+            Err(e) => BotCmdResult::LibErr(e),
+        }
     } else {
         Reaction::Msgs(
             vec![
                 "For help with a command named 'foo', try `help cmd: foo`.".into(),
                 "To see a list of all available commands, try `help list: commands`.".into(),
+                "To search for commands related to 'foo', try `help search: foo`.".into(),
                 format!(
                     "For this bot software's documentation, including an introduction to the \
                      command syntax, see <{homepage}>",
@@ -229,6 +772,250 @@ fn help(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> BotCmdResul
     }
 }
 
+fn help_list_commands(ctx: &HandlerContext) -> Result<Reaction> {
+    let max_len = ctx.state.privmsg_content_max_len(ctx.guess_reply_dest()?)?;
+    let names = ctx.state.command_names()?;
+
+    Ok(Reaction::Msgs(
+        pack_into_lines(
+            "Available commands:",
+            names.iter().map(AsRef::as_ref),
+            max_len,
+        )
+        .into(),
+    ))
+}
+
+/// Joins the given header and items with spaces into as few lines as possible, each no longer than
+/// `max_len` bytes, breaking lines only between items. An item too long to fit on a line of its
+/// own is given a line of its own nonetheless.
+fn pack_into_lines<'a, I>(header: &str, items: I, max_len: usize) -> Vec<Cow<'static, str>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut lines = Vec::new();
+    let mut line = header.to_owned();
+
+    for item in items {
+        if !line.is_empty() && line.len() + 1 + item.len() > max_len {
+            lines.push(line.into());
+            line = String::new();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+
+        line.push_str(item);
+    }
+
+    if !line.is_empty() {
+        lines.push(line.into());
+    }
+
+    lines
+}
+
+fn help_search(state: &State, search: &Yaml) -> Result<BotCmdResult> {
+    let search = util::yaml::scalar_to_str(search, Cow::Borrowed, "the search term")?;
+
+    let regex = match Regex::<rx_cfg::SizeLimit<rx_cfg::CaseInsensitive>>::from_str(&search) {
+        Ok(rx) => rx,
+        Err(e) => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!("Failed to parse the search term as a regex: {}", e).into(),
+            ))
+        }
+    };
+
+    let mut matches = Vec::new();
+
+    for name in state.command_names()? {
+        let is_match = match state.command(&name)? {
+            Some(&BotCommand {
+                ref usage_str,
+                ref help_msg,
+                ..
+            }) => [&name, usage_str, help_msg]
+                .iter()
+                .any(|s| regex.is_match(s)),
+            None => false,
+        };
+
+        if is_match {
+            matches.push(name);
+        }
+    }
+
+    Ok(if matches.is_empty() {
+        Reaction::Msg(format!("No commands matched {:?}.", search).into())
+    } else {
+        Reaction::Msg(format!("Matching commands: {}", matches.iter().join(", ")).into())
+    }
+    .into())
+}
+
 fn empty_msg_trigger(_: HandlerContext, _: Captures) -> Reaction {
     Reaction::Msg("Yes?".into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::test_harness::TestBot;
+    use core::test_harness::MINIMAL_CONFIG;
+    use modules;
+    use std::time::Duration;
+
+    #[test]
+    fn choose() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let choose = |arg| {
+            bot.feed(&format!(
+                ":alice!alice@example.org PRIVMSG #test :test-bot: choose {}",
+                arg
+            ))
+        };
+        let syntax_err = ["PRIVMSG #test :Syntax error. Try my `help` command."];
+
+        assert_eq!(choose(""), syntax_err);
+        assert_eq!(choose("[]"), syntax_err);
+        assert_eq!(choose("' , ,'"), syntax_err);
+        assert_eq!(choose("tea"), ["PRIVMSG #test :alice: tea"]);
+        assert_eq!(choose("[tea]"), ["PRIVMSG #test :alice: tea"]);
+        assert_eq!(choose("' tea , '"), ["PRIVMSG #test :alice: tea"]);
+        assert_eq!(choose("tea | tea"), ["PRIVMSG #test :alice: tea"]);
+    }
+
+    #[test]
+    fn module_disabling() {
+        let bot = TestBot::new(
+            "{nickname: test-bot, admins: [{nick: boss}], servers: [{name: test, host: \
+             irc.example.net, port: 6697}]}",
+            vec![modules::default(), modules::karma()],
+        );
+        let boss = |cmd| {
+            bot.feed(&format!(
+                ":boss!boss@example.org PRIVMSG #test :test-bot: {}",
+                cmd
+            ))
+        };
+        let karma = || bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: karma coffee");
+
+        assert_eq!(
+            boss("module-disable karma"),
+            ["PRIVMSG #test :boss: Disabled module \"karma\"."]
+        );
+        assert_eq!(
+            boss("module-disable karma"),
+            ["PRIVMSG #test :boss: Module \"karma\" already is disabled."]
+        );
+
+        // Neither the disabled module's triggers nor its commands are heeded.
+        assert!(bot
+            .feed(":alice!alice@example.org PRIVMSG #test :coffee++")
+            .is_empty());
+        assert!(karma().is_empty());
+
+        assert_eq!(
+            boss("module-enable karma"),
+            ["PRIVMSG #test :boss: Enabled module \"karma\"."]
+        );
+
+        bot.feed(":alice!alice@example.org PRIVMSG #test :coffee++");
+        assert_eq!(
+            karma(),
+            ["PRIVMSG #test :alice: \"coffee\" has karma of 1."]
+        );
+
+        assert_eq!(
+            boss("module-disable default"),
+            [
+                "PRIVMSG #test :User error: The module \"default\" can't be disabled, as it \
+                 provides `module-enable`."
+            ]
+        );
+    }
+
+    #[test]
+    fn help_search() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let search = |term| {
+            bot.feed(&format!(
+                ":alice!alice@example.org PRIVMSG #test :test-bot: help {{search: '{}'}}",
+                term
+            ))
+        };
+
+        // Commands' names, usage strings, and help messages are searched case-insensitively.
+        assert_eq!(
+            search("UPTIME"),
+            ["PRIVMSG #test :Matching commands: uptime"]
+        );
+        assert_eq!(
+            search("pipes"),
+            ["PRIVMSG #test :Matching commands: choose"]
+        );
+        assert_eq!(
+            search("^module-"),
+            ["PRIVMSG #test :Matching commands: module-disable, module-enable"]
+        );
+        assert_eq!(
+            search("zzz"),
+            ["PRIVMSG #test :No commands matched \"zzz\"."]
+        );
+        assert!(search("(")[0]
+            .starts_with("PRIVMSG #test :User error: Failed to parse the search term as a regex"));
+    }
+
+    #[test]
+    fn uptime() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default()]);
+        let uptime = || bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: uptime");
+
+        let start_uptime = bot.state().uptime();
+        assert!(start_uptime < Duration::from_secs(60));
+
+        let reply = uptime();
+        assert_eq!(reply.len(), 1);
+        assert!(reply[0].starts_with("PRIVMSG #test :alice: I've been running for "));
+        assert!(reply[0].ends_with("s. Connected to: test (not connected)."));
+
+        assert!(bot.state().uptime() > start_uptime);
+        assert_eq!(bot.state().server_uptime(bot.server_id()).unwrap(), None);
+
+        // The connection time is taken from the server's welcome.
+        bot.feed(":irc.example.net 001 test-bot :Welcome to the test network");
+
+        let server_uptime = bot.state().server_uptime(bot.server_id()).unwrap();
+        assert!(server_uptime.unwrap() < bot.state().uptime());
+
+        let reply = uptime();
+        assert_eq!(reply.len(), 1);
+        assert!(reply[0].contains(". Connected to: test for "));
+        assert!(!reply[0].contains("not connected"));
+    }
+
+    #[test]
+    fn line_packing() {
+        let pack = |header, items: &[&'static str], max_len| {
+            pack_into_lines(header, items.iter().cloned(), max_len)
+        };
+
+        // "Users (3): a bb" is exactly 15 bytes long.
+        assert_eq!(
+            pack("Users (3):", &["a", "bb", "ccc"], 15),
+            ["Users (3): a bb", "ccc"]
+        );
+        assert_eq!(
+            pack("Users (3):", &["a", "bb", "ccc"], 14),
+            ["Users (3): a", "bb ccc"]
+        );
+        assert_eq!(
+            pack("Users:", &["a", "supercalifragilistic", "b"], 10),
+            ["Users: a", "supercalifragilistic", "b"]
+        );
+        assert_eq!(pack("", &["a", "b"], 10), ["a b"]);
+        assert_eq!(pack("Users (0):", &[], 10), ["Users (0):"]);
+    }
+}
@@ -0,0 +1,192 @@
+use core::BotCmdAuthLvl as Auth;
+use core::*;
+use itertools::Itertools;
+use rand::Rng;
+use std::borrow::Cow;
+use std::ops::DerefMut;
+use util;
+use yaml_rust::Yaml;
+
+/// The greatest number of dice that may be rolled at once.
+const MAX_DICE_COUNT: u32 = 100;
+
+/// The greatest number of sides that a die may have.
+const MAX_DIE_SIDES: u32 = 1_000_000;
+
+/// The greatest magnitude of a modifier that may be added to a roll.
+const MAX_ROLL_MODIFIER: i64 = 1_000_000;
+
+pub fn mk() -> Module {
+    mk_module("games")
+        .command(
+            "roll",
+            "<dice>",
+            "Have the bot roll dice, given in the standard notation `NdS+M`, for `N` dice each \
+             with `S` sides, plus an optional modifier `M` (which may be negative) — e.g., `2d6`, \
+             `d20`, or `3d8-2`.",
+            Auth::Public,
+            Box::new(roll),
+            &[],
+        )
+        .end()
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct DiceSpec {
+    count: u32,
+    sides: u32,
+    modifier: i64,
+}
+
+fn roll(HandlerContext { state, .. }: HandlerContext, arg: &Yaml) -> Result<BotCmdResult> {
+    let spec_str =
+        util::yaml::scalar_to_str(arg, Cow::Borrowed, "the argument to the command `roll`")?;
+
+    let spec = match parse_dice(&spec_str) {
+        Some(spec) => spec,
+        None => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!(
+                    "I couldn't understand the dice {:?}. Please give them as, e.g., `2d6` or \
+                     `3d8-2`.",
+                    spec_str
+                )
+                .into(),
+            ))
+        }
+    };
+
+    if spec.count == 0 || spec.count > MAX_DICE_COUNT {
+        return Ok(BotCmdResult::UserErrMsg(
+            format!("I can roll from 1 to {} dice at once.", MAX_DICE_COUNT).into(),
+        ));
+    }
+
+    if spec.sides == 0 || spec.sides > MAX_DIE_SIDES {
+        return Ok(BotCmdResult::UserErrMsg(
+            format!("Dice may have from 1 to {} sides.", MAX_DIE_SIDES).into(),
+        ));
+    }
+
+    if spec.modifier.abs() > MAX_ROLL_MODIFIER {
+        return Ok(BotCmdResult::UserErrMsg(
+            format!(
+                "The modifier may be at most {} in magnitude.",
+                MAX_ROLL_MODIFIER
+            )
+            .into(),
+        ));
+    }
+
+    let rolls = {
+        let mut rng = state.rng()?;
+        let rng = rng.deref_mut();
+
+        (0..spec.count)
+            .map(|_| rng.gen_range(1, u64::from(spec.sides) + 1))
+            .collect::<Vec<_>>()
+    };
+
+    // This can't overflow, given the above limits.
+    let total = rolls.iter().sum::<u64>() as i64 + spec.modifier;
+
+    let modifier_str = match spec.modifier {
+        0 => String::new(),
+        m if m > 0 => format!(" + {}", m),
+        m => format!(" - {}", -m),
+    };
+
+    Ok(Reaction::Reply(
+        format!(
+            "{}d{}: [{}]{} = {}",
+            spec.count,
+            spec.sides,
+            rolls.iter().join(", "),
+            modifier_str,
+            total
+        )
+        .into(),
+    )
+    .into())
+}
+
+/// Parses dice notation of the form `NdS+M` or `NdS-M`, where `N` and the modifier are optional.
+/// Returns `None` if the string is malformed or any of its numbers are too large to represent.
+fn parse_dice(s: &str) -> Option<DiceSpec> {
+    let s = s.trim();
+
+    let d_idx = s.find(&['d', 'D'][..])?;
+    let (count_str, rest) = (&s[..d_idx], &s[d_idx + 1..]);
+
+    let (sides_str, modifier) = match rest.find(&['+', '-'][..]) {
+        Some(idx) => {
+            let modifier_digits = &rest[idx + 1..];
+
+            if !is_ascii_digits(modifier_digits) {
+                return None;
+            }
+
+            let magnitude = modifier_digits.parse::<i64>().ok()?;
+
+            (
+                &rest[..idx],
+                if rest[idx..].starts_with('-') {
+                    -magnitude
+                } else {
+                    magnitude
+                },
+            )
+        }
+        None => (rest, 0),
+    };
+
+    let count = match count_str {
+        "" => 1,
+        s if is_ascii_digits(s) => s.parse().ok()?,
+        _ => return None,
+    };
+
+    if !is_ascii_digits(sides_str) {
+        return None;
+    }
+
+    Some(DiceSpec {
+        count,
+        sides: sides_str.parse().ok()?,
+        modifier,
+    })
+}
+
+fn is_ascii_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(count: u32, sides: u32, modifier: i64) -> Option<DiceSpec> {
+        Some(DiceSpec {
+            count,
+            sides,
+            modifier,
+        })
+    }
+
+    #[test]
+    fn parse_dice_examples() {
+        assert_eq!(parse_dice("2d6"), spec(2, 6, 0));
+        assert_eq!(parse_dice("d20"), spec(1, 20, 0));
+        assert_eq!(parse_dice("2d6+1"), spec(2, 6, 1));
+        assert_eq!(parse_dice("3D8-2"), spec(3, 8, -2));
+        assert_eq!(parse_dice(" 1000000d100 "), spec(1_000_000, 100, 0));
+        assert_eq!(parse_dice(""), None);
+        assert_eq!(parse_dice("6"), None);
+        assert_eq!(parse_dice("2d"), None);
+        assert_eq!(parse_dice("2d6+"), None);
+        assert_eq!(parse_dice("2d6+-1"), None);
+        assert_eq!(parse_dice("-2d6"), None);
+        assert_eq!(parse_dice("2x6"), None);
+        assert_eq!(parse_dice("99999999999d6"), None);
+    }
+}
@@ -0,0 +1,205 @@
+use core::BotCmdAuthLvl as Auth;
+use core::*;
+use regex::Captures;
+use std::borrow::Cow;
+use util::irc::NickName;
+use util::yaml::scalar_to_str;
+use yaml_rust::Yaml;
+
+/// The regex that recognizes a change to a term's karma, such as `coffee++`
+const KARMA_CHANGE_REGEX_STR: &str =
+    r"(?:^|[[:space:]])(?P<term>[^[:space:]]+?)(?P<op>\+\+|--)(?:[[:space:]]|$)";
+
+/// The greatest length, in characters, of a term whose karma is tracked
+const MAX_TERM_LEN: usize = 64;
+
+/// The greatest number of terms whose karma is tracked at once, as the whole store is rewritten on
+/// each change
+const MAX_TERMS: usize = 2000;
+
+/// This module tracks the "karma" of arbitrary terms, which users can increment by saying, e.g.,
+/// `coffee++`, or decrement by saying, e.g., `coffee--`, in any channel that the bot is in, whether
+/// or not they address the bot. It also provides the command `karma`, which reports a term's
+/// karma.
+///
+/// Terms are compared case-insensitively. Only the first such increment or decrement in each
+/// message is counted, and users can't change the karma of their own nicknames. Karma given in
+/// one-to-one messaging with the bot is ignored. At most 2000 terms' karma is tracked; a term whose
+/// karma returns to zero is forgotten, making room for another.
+///
+/// Karma is kept in the module's key–value store (see `ModuleStore`), so it persists across
+/// restarts of the bot.
+pub fn mk() -> Module {
+    mk_module("karma")
+        .command(
+            "karma",
+            "<term>",
+            "Show the karma of the given term, which users can change by saying, e.g., `term++` \
+             or `term--`.",
+            Auth::Public,
+            Box::new(karma),
+            &[],
+        )
+        .trigger(
+            "karma-change",
+            KARMA_CHANGE_REGEX_STR,
+            "Increment or decrement the karma of a term followed by `++` or `--`.",
+            TriggerPriority::Medium,
+            Box::new(change_karma),
+            &[TriggerAttr::AlwaysWatching],
+        )
+        .end()
+}
+
+fn karma(ctx: HandlerContext, arg: &Yaml) -> Result<Reaction> {
+    let term = scalar_to_str(arg, Cow::Borrowed, "the term")?;
+    let term = term.trim();
+
+    let karma = ctx
+        .module_store()
+        .get::<i64>(&normalize_term(term))?
+        .unwrap_or(0);
+
+    Ok(Reaction::Reply(
+        format!("{:?} has karma of {}.", term, karma).into(),
+    ))
+}
+
+fn change_karma(ctx: HandlerContext, args: Captures) -> Result<Reaction> {
+    if ctx.state.channel_id(ctx.request_origin)?.is_none() {
+        return Ok(Reaction::None);
+    }
+
+    let term = &args["term"];
+
+    if term.chars().count() > MAX_TERM_LEN {
+        return Ok(Reaction::None);
+    }
+
+    if is_self_karma(term, ctx.invoker.nick) {
+        return Ok(Reaction::Reply("You can't change your own karma.".into()));
+    }
+
+    let delta = match &args["op"] {
+        "++" => 1,
+        _ => -1,
+    };
+
+    let store = ctx.module_store();
+    let key = normalize_term(term);
+
+    if store.get::<i64>(&key)?.is_none() && store.keys()?.len() >= MAX_TERMS {
+        return Ok(Reaction::Silent(
+            format!(
+                "Not tracking the karma of {:?}, as too many terms' karma is tracked already.",
+                term
+            )
+            .into(),
+        ));
+    }
+
+    let karma = store.update(&key, |karma: Option<i64>| {
+        karma.unwrap_or(0).saturating_add(delta)
+    })?;
+
+    if karma == 0 {
+        store.remove(&key)?;
+    }
+
+    Ok(Reaction::Silent(
+        format!(
+            "{:?} changed the karma of {:?} to {}.",
+            ctx.invoker.nick.unwrap_or(""),
+            term,
+            karma
+        )
+        .into(),
+    ))
+}
+
+/// Returns whether the given term is the given nickname of the user who is trying to change the
+/// term's karma, comparing them as nicknames, if both are valid as such.
+fn is_self_karma(term: &str, invoker_nick: Option<&str>) -> bool {
+    match (NickName::new(term), invoker_nick.map(NickName::new)) {
+        (Ok(term), Some(Ok(nick))) => term == nick,
+        _ => false,
+    }
+}
+
+fn normalize_term(term: &str) -> String {
+    term.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::test_harness::TestBot;
+    use core::test_harness::MINIMAL_CONFIG;
+    use modules;
+    use util::regex::IntoRegexCI;
+
+    #[test]
+    fn karma_changes() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default(), modules::karma()]);
+        let karma_keys = || bot.state().module_store("karma").unwrap().keys().unwrap();
+
+        assert!(bot
+            .feed(":alice!alice@example.org PRIVMSG #test :coffee++")
+            .is_empty());
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: karma Coffee"),
+            ["PRIVMSG #test :alice: \"Coffee\" has karma of 1."]
+        );
+        assert_eq!(karma_keys(), ["coffee"]);
+
+        // A term whose karma returns to zero is forgotten.
+        assert!(bot
+            .feed(":bob!bob@example.org PRIVMSG #test :coffee--")
+            .is_empty());
+        assert_eq!(
+            bot.feed(":alice!alice@example.org PRIVMSG #test :test-bot: karma coffee"),
+            ["PRIVMSG #test :alice: \"coffee\" has karma of 0."]
+        );
+        assert!(karma_keys().is_empty());
+    }
+
+    fn parse(text: &str) -> Option<(String, String)> {
+        let regex = KARMA_CHANGE_REGEX_STR.into_regex_ci().unwrap();
+
+        regex
+            .captures(text)
+            .map(|caps| (caps["term"].to_owned(), caps["op"].to_owned()))
+    }
+
+    #[test]
+    fn karma_syntax() {
+        assert_eq!(parse("coffee++"), Some(("coffee".into(), "++".into())));
+        assert_eq!(
+            parse("I think tea-- really"),
+            Some(("tea".into(), "--".into()))
+        );
+        assert_eq!(parse("bot: rust++"), Some(("rust".into(), "++".into())));
+        assert_eq!(parse("a-b--"), Some(("a-b".into(), "--".into())));
+        assert_eq!(parse("x+++"), Some(("x+".into(), "++".into())));
+        assert_eq!(parse("c++ is a language"), Some(("c".into(), "++".into())));
+        assert_eq!(parse("i++;"), None);
+        assert_eq!(parse("++"), None);
+        assert_eq!(parse("coffee ++"), None);
+        assert_eq!(parse("no karma here"), None);
+    }
+
+    #[test]
+    fn self_karma() {
+        assert!(is_self_karma("c74d", Some("c74d")));
+        assert!(is_self_karma("C74D", Some("c74d")));
+        assert!(is_self_karma("foo[]", Some("FOO{}")));
+        assert!(!is_self_karma("coffee", Some("c74d")));
+        assert!(!is_self_karma("c74d", None));
+        assert!(!is_self_karma("#channel", Some("c74d")));
+    }
+
+    #[test]
+    fn terms_are_case_insensitive() {
+        assert_eq!(normalize_term("Coffee"), normalize_term("cOFFEE"));
+    }
+}
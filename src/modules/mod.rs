@@ -1,13 +1,35 @@
+pub use self::channel_log::mk as channel_log;
 pub use self::default::mk as default;
+pub use self::games::mk as games;
+pub use self::karma::mk as karma;
 pub use self::quote::mk as quote;
+pub use self::remind::mk as remind;
+pub use self::tell::mk as tell;
 pub use self::test::mk as test;
+pub use self::url_title::mk as url_title;
 use core::Module;
 
+mod channel_log;
 mod default;
+mod games;
+mod karma;
 mod quote;
+mod remind;
+mod tell;
 mod test;
+mod url_title;
 
 /// A list of all bot modules provided by this library, suitable for passing to [`run`].
 ///
 /// [`run`]: <../fn.run.html>
-pub const ALL: &[fn() -> Module] = &[default, quote, test];
+pub const ALL: &[fn() -> Module] = &[
+    channel_log,
+    default,
+    games,
+    karma,
+    quote,
+    remind,
+    tell,
+    test,
+    url_title,
+];
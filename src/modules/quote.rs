@@ -7,27 +7,47 @@ use core::*;
 use inlinable_string::InlinableString;
 use irc::client::data::User as AatxeUser;
 use irc::client::prelude::Client as AatxeClient;
+use itertools::Either;
 use itertools::Itertools;
 use quantiles::ckms::CKMS;
+use rand::Rng;
 use rando::Rando;
 use ref_slice::ref_slice;
 use regex;
+use serde::Serialize;
 use serde_yaml;
 use smallbitvec::SmallBitVec;
 use smallvec::SmallVec;
 use std;
 use std::borrow::Cow;
 use std::cell::Cell;
+use std::cmp;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fmt;
+use std::fs;
 use std::fs::File;
+use std::fs::OpenOptions;
 use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
 use std::iter;
 use std::mem;
 use std::num::ParseIntError;
 use std::ops::Deref;
+use std::ops::DerefMut;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::str;
+use std::sync::Mutex;
 use std::sync::RwLock;
 use std::sync::RwLockReadGuard;
+use std::sync::RwLockWriteGuard;
+use std::time::Duration;
+use std::time::SystemTime;
 use string_cache::DefaultAtom;
 use strum::IntoEnumIterator;
 use try_map::FallibleMapExt;
@@ -35,24 +55,35 @@ use try_map::FlipResultExt;
 use url::Url;
 use url_serde::SerdeUrl;
 use util;
+use util::irc::case_insensitive_str_cmp;
+use util::lock::MutexExt;
 use util::regex::config as rx_cfg;
 use util::regex::Regex;
 use util::yaml::any_to_str;
+use util::yaml::get_arg_as;
 use util::yaml::get_arg_by_short_or_long_key;
 use util::yaml::iter_as_seq;
 use util::yaml::scalar_to_str;
+use util::yaml::str::YAML_STR_BY;
+use util::yaml::str::YAML_STR_CHANNELS;
 use util::yaml::str::YAML_STR_CMD;
+use util::yaml::str::YAML_STR_FILE;
+use util::yaml::str::YAML_STR_FORMAT;
+use util::yaml::str::YAML_STR_FUZZY;
 use util::yaml::str::YAML_STR_ID;
 use util::yaml::str::YAML_STR_R;
 use util::yaml::str::YAML_STR_REGEX;
 use util::yaml::str::YAML_STR_S;
 use util::yaml::str::YAML_STR_STRING;
 use util::yaml::str::YAML_STR_TAG;
+use util::yaml::str::YAML_STR_TAGS;
+use util::yaml::str::YAML_STR_TEXT;
 use util::yaml::FW_SYNTAX_CHECK_FAIL;
 use util::MustUse;
 use util::STATIC_REGEX_PARSE_ERR_MSG;
 use walkdir::WalkDir;
 use yaml_rust;
+use yaml_rust::parser::Event;
 use yaml_rust::yaml::Hash as YamlHash;
 use yaml_rust::Yaml;
 
@@ -101,16 +132,48 @@ use url_serde::Serde;
 /// strings will be matched case-sensitively. This parameter is optional. This parameter's key may
 /// be abbreviated as `s`.
 ///
+/// - `fuzzy` — The value of this parameter should be a Boolean value (`true` or `false`). If it
+/// is `true`, the search terms given in the parameter `string` will be matched approximately
+/// rather than exactly, such that a quotation will be displayed if it contains text that can be
+/// turned into each such search term by at most one insertion, deletion, or substitution of a
+/// character per four characters in the search term (so search terms shorter than four characters
+/// still must be matched exactly). This allows small typographical errors in search terms to be
+/// tolerated. Fuzzy matching is considerably slower than exact matching, as its cost grows with the
+/// product of the length of each search term and the length of the text of each quotation searched,
+/// which in turn may be the whole database; therefore, it is not the default. This parameter is
+/// optional and defaults to `false`.
+///
 /// - `tag` — The value of this parameter may be a string or a sequence of strings. Each string so
 /// provided will be interpreted as a quotation _tag_ (see below). A quotation will be displayed
 /// only if it has all tags so provided. These tags will be matched case-sensitively. Note that
 /// searching by `regex` or `string` also searches tags as well as quotations' text. This parameter
 /// is optional.
 ///
+/// - `by` — The value of this parameter may be a string or a sequence of strings. Each string so
+/// provided will be interpreted as an IRC nickname. A quotation will be displayed only if it is in
+/// the `chat` format (see below) and each nickname so provided is that of the speaker of at least
+/// one of its lines, i.e., the nickname in angle brackets (`<nick>`) or after an asterisk
+/// (`* nick`) at the start of the line. Nicknames are compared case-insensitively, and any
+/// channel status sigil (such as `@` or `+`) before a speaker's nickname is ignored. This
+/// parameter is optional.
+///
+/// - `not-regex`, `not-string`, and `not-tag` — The values of these parameters are interpreted as
+/// those of the parameters `regex`, `string`, and `tag`, respectively, except that a quotation
+/// will be displayed only if it contains _no_ match of any regular expression given in
+/// `not-regex`, _no_ occurrence of any string given in `not-string`, and _none_ of the tags given
+/// in `not-tag`. As with `regex` and `string`, matches found in a quotation's tags count as
+/// matches found in the quotation, and `fuzzy` applies to the strings given in `not-string` as it
+/// does to those given in `string`. A quotation must satisfy all the other search parameters as
+/// well. These parameters are optional.
+///
 /// - `id` — The value of this parameter should be a string. This parameter requests the quotation
 /// whose ID, when displayed as described in the section "Output" above, is the value of this
 /// parameter. This parameter is optional.
 ///
+/// - `file` — The value of this parameter should be a string naming a quotation file, as listed by
+/// the command `quote-database-info`. A quotation will be displayed only if it is from that file.
+/// This parameter is optional.
+///
 /// - `anti-ping tactic` — The value of this parameter should be a string. This parameter overrides
 /// the fields of the same name in the quotation database (see below). This parameter may be used
 /// only by administrators of the bot. This parameter is optional.
@@ -125,6 +188,23 @@ use url_serde::Serde;
 ///
 /// Request a pseudo-random quotation that contains the text "rabbit".
 ///
+/// ### `quote s: funnny, fuzzy: true`
+///
+/// Request a pseudo-random quotation that contains the text "funnny" or some text differing from
+/// it by a single character, such as "funny".
+///
+/// ### `quote s: rabbit, not-string: carrot`
+///
+/// Request a pseudo-random quotation that contains the text "rabbit" but not the text "carrot".
+///
+/// ### `quote file: classics.yaml`
+///
+/// Request a pseudo-random quotation from the quotation file `classics.yaml`.
+///
+/// ### `quote by: c74d`
+///
+/// Request a pseudo-random quotation in which the user with the nickname "c74d" speaks.
+///
 /// ### `quote r: 'blue ?berr(y|ies)'`
 ///
 /// Request a pseudo-random quotation that contains at least one of the following sequences of
@@ -142,6 +222,27 @@ use url_serde::Serde;
 ///
 /// - `quote-database-info`
 ///
+/// - `quote-formats` — List the valid values of the `format` and `anti-ping tactic` fields of
+/// quotation files and records (see below).
+///
+/// - `quote-last` — Show again the quotation that the bot most recently displayed in the current
+/// channel (or one-to-one conversation) in response to the `quote` command.
+///
+/// - `quote-add` — Add a quotation to the database. This command takes as argument a YAML mapping
+/// with the parameters `text` (**required**), `tags`, and `format`, which are interpreted as the
+/// fields of the same names in a quotation record (see below), and `channels`, which is
+/// interpreted as the field of the same name in a quotation file. The quotation is appended to the
+/// quotation file `added.yaml`, which is created if it does not exist yet; the parameter
+/// `channels` may be given only when this file is created, and defaults to the identifier of the
+/// channel in which the command is used. The quotation database then is reloaded. This command
+/// may be used only by administrators of the bot.
+///
+/// - `quote-remove` — Remove a quotation from the database. This command takes as argument a YAML
+/// mapping with the parameter `id` (**required**), interpreted as in the `quote` command. The
+/// quotation's record is removed from its quotation file, which must not be read-only, leaving the
+/// rest of the file as it was, and the quotation database then is reloaded. This command may be
+/// used only by administrators of the bot.
+///
 /// For a full list of commands available, use the bot's `help` command.
 ///
 ///
@@ -156,10 +257,15 @@ use url_serde::Serde;
 /// - `channels` — The value of this field should be a string, which will be parsed as a regular
 /// expression using the Rust [`regex`] library and [its particular syntax][`regex` syntax].
 /// Quotations from this file will be shown only in channels whose names (including any leading
-/// `#`) match this regular expression, unless an administrator of the bot chooses to override this
-/// restriction. This regular expression will be prefixed with the anchor meta-character `^` and
-/// suffixed with the anchor meta-character `$`, such that the regular expression must match the
-/// whole of a channel name rather than only part of it. This field is **required**.
+/// `#`) or whose server-qualified identifiers (such as `freenode/##rust`, where `freenode` is the
+/// name given to the server in the bot's configuration) match this regular expression, unless an
+/// administrator of the bot chooses to override this restriction. This regular expression will be
+/// prefixed with the anchor meta-character `^` and suffixed with the anchor meta-character `$`,
+/// such that the regular expression must match the whole of a channel name or identifier rather
+/// than only part of it. Quotations from this file also will be shown in any channel that _can
+/// see_, per the per-channel settings `can see` and `seen by` documented in [`Config`], a channel
+/// listed in the bot's configuration whose name or identifier matches this regular expression.
+/// This field is **required**.
 ///
 /// - `format` — The value of this field should be a string indicating the manner in which the
 /// texts of the quotations in this file generally are formatted. This field is optional and
@@ -188,9 +294,27 @@ use url_serde::Serde;
 ///   - `eschew` — Simply forbid the bot from posting a quotation to a channel while one or more
 ///   users who would be expected to be pinged by the quotation are in the channel.
 ///
+/// - `weight` — The value of this field should be a finite, non-negative number indicating how
+/// likely the quotations in this file generally are to be chosen (see the quotation record field
+/// of the same name below). This field is optional and defaults to `1`.
+///
 /// - `quotations` — The value of this field should be a sequence of _quotation records_. This
 /// field is optional and defaults to an empty sequence.
 ///
+/// - `lazy` — The value of this field should be a Boolean value (`true` or `false`). If it is
+/// `true`, the texts of the quotations in this file will not be kept in memory; rather, only the
+/// other fields of the quotations and the locations of their records in the file will be, and each
+/// quotation's text will be read from the file whenever it is needed. This greatly reduces the
+/// memory used for a large quotation file, at the cost of a disk read for each quotation that the
+/// `quote` command considers, which, for a query that few or no quotations match, may be every
+/// quotation in the file. This does not make loading the quotation database any faster, as the
+/// file still is read and parsed in full, texts and all, whenever the quotation database is
+/// loaded; only the memory used once it has been loaded is reduced. For this to be allowed, the
+/// `quotations` field must be a block sequence (i.e., not enclosed in square brackets) in which
+/// each quotation record starts on a line of its own. If the file is modified, the quotation
+/// database must be reloaded before quotations can be read from the file again. This field is
+/// optional and defaults to `false`.
+///
 /// Each _quotation record_ should be a mapping with the following fields:
 ///
 /// - `format` — This field is optional and may be provided to override the file-level default set
@@ -200,6 +324,12 @@ use url_serde::Serde;
 /// - `text` — The value of this field should be the text of the quotation. This field is
 /// **required**.
 ///
+/// - `variants` — The value of this field should be a sequence of strings, each of which is an
+/// alternative text of the quotation, such as a translation or a differently abridged version.
+/// When the quotation is to be displayed, one of its texts (the `text` field or any of its
+/// `variants`) that satisfies the parameters of the `quote` command will be chosen at random. The
+/// quotation's other fields apply to all of its texts alike. This field is optional.
+///
 /// - `URL` — The value of this field should be a string whose text forms a valid Uniform Resource
 /// Locator (URL) that can be parsed as such by the Rust [`url`] library. If such a URL is
 /// provided, it will be taken as a reference to a copy of the text of the quotation, such as in a
@@ -220,6 +350,14 @@ use url_serde::Serde;
 /// default set in the quotation file's `anti-ping tactic` field (see above), which itself defaults
 /// to `munge`. This field allows the same values as the corresponding file-level field.
 ///
+/// - `weight` — The value of this field should be a finite, non-negative number. When the `quote`
+/// command chooses among several quotations that satisfy its query parameters, each quotation's
+/// chance of being chosen will be proportional to its weight; e.g., a quotation of weight `3` will
+/// be chosen three times as often as one of weight `1`. A quotation of weight `0` will be shown
+/// only if it is requested by its ID. This field is optional and may be provided to override the
+/// file-level default set in the quotation file's `weight` field (see above), which itself
+/// defaults to `1`.
+///
 /// ## Quotation formats
 ///
 /// The following are the supported _quotation formats_:
@@ -268,6 +406,7 @@ use url_serde::Serde;
 /// ["succ"]: <https://github.com/edef1c>
 /// ["ubsan"]: <https://github.com/ubsan>
 /// [YAML]: <http://yaml.org>
+/// [`Config`]: <../core/struct.Config.html>
 /// [`regex` flag]: <https://docs.rs/regex/*/regex/#grouping-and-flags>
 /// [`regex` syntax]: <https://docs.rs/regex/*/regex/#syntax>
 /// [`regex`]: <https://docs.rs/regex/*/regex/>
@@ -277,13 +416,26 @@ pub fn mk() -> Module {
         .on_load(Box::new(on_load))
         .command(
             "quote",
-            "{regex: '[...]', string: '[...]', tag: '[...]', id: '[ID]'}",
+            "{regex: '[...]', string: '[...]', fuzzy: '[true]', tag: '[...]', by: '[...]', id: '[ID]', \
+             file: '[file name]', not-regex: '[...]', not-string: '[...]', not-tag: '[...]'}",
             "Request a quotation from the bot's database of quotations. For usage instructions, \
              see the full documentation: \
              <https://docs.rs/irc-bot/*/irc_bot/modules/fn.quote.html>.",
             Auth::Public,
             Box::new(quote),
-            &[],
+            &[BotCmdAttr::Cooldown(Duration::from_secs(
+                QUOTE_COOLDOWN_SECS,
+            ))],
+        )
+        .command(
+            "quote-last",
+            "",
+            "Request that the bot show again the quotation it most recently showed here.",
+            Auth::Public,
+            Box::new(quote_last),
+            &[BotCmdAttr::Cooldown(Duration::from_secs(
+                QUOTE_COOLDOWN_SECS,
+            ))],
         )
         .command(
             "quote-database-info",
@@ -294,6 +446,15 @@ pub fn mk() -> Module {
             Box::new(show_qdb_info),
             &[],
         )
+        .command(
+            "quote-formats",
+            "",
+            "Request a list of the valid values of the `format` and `anti-ping tactic` fields and \
+             parameters.",
+            Auth::Public,
+            Box::new(quote_formats),
+            &[],
+        )
         .command(
             "quote-database-reload",
             "",
@@ -302,12 +463,68 @@ pub fn mk() -> Module {
             Box::new(reload_qdb),
             &[],
         )
+        .command(
+            "quote-add",
+            "{text: '...', tags: '[...]', format: '[format]', channels: '[regex]'}",
+            "Add a quotation to the bot's database of quotations, in the file `added.yaml`.",
+            Auth::Admin,
+            Box::new(quote_add),
+            &[],
+        )
+        .command(
+            "quote-remove",
+            "{id: ID}",
+            "Remove the quotation with the given ID from the bot's database of quotations, and from \
+             the file in which it is stored.",
+            Auth::Admin,
+            Box::new(quote_remove),
+            &[],
+        )
         .end()
 }
 
+/// How long each user must wait between invocations of the `quote` command.
+const QUOTE_COOLDOWN_SECS: u64 = 5;
+
+/// With the `quote` command's `fuzzy` parameter, the number of characters in a search term per
+/// character insertion, deletion, or substitution allowed when matching it.
+const FUZZY_MATCH_CHARS_PER_EDIT: usize = 4;
+
+/// The number of message destinations for which the most recently shown quotation is remembered,
+/// for the `quote-last` command.
+const LAST_QUOTATIONS_CAPACITY: usize = 256;
+
+/// The maximum number of message destinations for which to remember which quotation files they are
+/// allowed to see.
+const FILE_PERMISSIONS_CACHE_CAPACITY: usize = 256;
+
+/// The weight of a quotation for which no weight is specified.
+const DEFAULT_QUOTATION_WEIGHT: f64 = 1.0;
+
+/// The name of the quotation file to which the `quote-add` command appends quotations.
+const ADDED_QUOTATIONS_FILE_NAME: &str = "added.yaml";
+
+/// The ID of a quotation that has been shown, with the ID of the variant of its text that was
+/// shown, if its text was shown
+type ShownQuotation = (QuotationId, Option<usize>);
+
+/// The message destination for which quotation file permissions were computed, with the
+/// generation of the bot's configuration with which they were computed
+type FilePermissionsKey = (ServerId, String, usize);
+
 lazy_static! {
     static ref QDB: RwLock<QuotationDatabase> = RwLock::new(QuotationDatabase::new());
+    static ref LAST_QUOTATIONS: Mutex<ClockProCache<(ServerId, String), ShownQuotation>> =
+        Mutex::new(
+            ClockProCache::new(LAST_QUOTATIONS_CAPACITY)
+                .expect("`LAST_QUOTATIONS_CAPACITY` is too small?!")
+        );
+    static ref FILE_PERMISSIONS_CACHE: Mutex<ClockProCache<FilePermissionsKey, SmallBitVec>> =
+        Mutex::new(new_file_permissions_cache());
     static ref YAML_STR_ANTI_PING_TACTIC: Yaml = util::yaml::mk_str("anti-ping tactic");
+    static ref YAML_STR_NOT_REGEX: Yaml = util::yaml::mk_str("not-regex");
+    static ref YAML_STR_NOT_STRING: Yaml = util::yaml::mk_str("not-string");
+    static ref YAML_STR_NOT_TAG: Yaml = util::yaml::mk_str("not-tag");
 }
 
 #[derive(Debug)]
@@ -315,6 +532,10 @@ struct QuotationDatabase {
     files: SmallVec<[QuotationFileMetadata; 8]>,
 
     quotations: Vec<Quotation>,
+
+    /// Whether any quotation has a weight other than the default, such that quotations are to be
+    /// chosen by weighted rather than uniform random selection
+    weighted: bool,
 }
 
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
@@ -339,14 +560,29 @@ struct QuotationFileIR {
     #[serde(rename = "anti-ping tactic")]
     anti_ping_tactic: AntiPingTactic,
 
+    #[serde(default = "default_quotation_weight_for_serde")]
+    weight: f64,
+
+    /// This is `None` if the field is given without a value, as it may be once `quote-remove` has
+    /// removed the last of a file's quotations.
     #[serde(default)]
-    quotations: Vec<QuotationIR>,
+    quotations: Option<Vec<QuotationIR>>,
+
+    #[serde(default)]
+    lazy: bool,
 }
 
 #[derive(Debug)]
 struct QuotationFileMetadata {
     name: String,
 
+    path: PathBuf,
+
+    /// The time at which the file was last modified when it was loaded, recorded if the file's
+    /// quotations' texts are to be loaded lazily, so that it can be detected if the file has
+    /// changed since then
+    modified: Option<SystemTime>,
+
     file_id: QuotationFileId,
 
     channels_regex: Regex<rx_cfg::Anchored<rx_cfg::SizeLimit<rx_cfg::CaseInsensitive>>>,
@@ -354,29 +590,40 @@ struct QuotationFileMetadata {
     quotation_count: usize,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "kebab-case")]
 struct QuotationIR {
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     format: Option<QuotationFormat>,
 
     text: String,
 
     #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    variants: Vec<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "SmallVec::is_empty")]
     tags: SmallVec<[DefaultAtom; 2]>,
 
     #[serde(default)]
     #[serde(rename = "URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     url: Option<SerdeUrl>,
 
     #[serde(default)]
     #[serde(rename = "anti-ping tactic")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     anti_ping_tactic: Option<AntiPingTactic>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<f64>,
 }
 
-#[cfg_attr(test, derive(Clone))]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Quotation {
     id: QuotationId,
 
@@ -386,14 +633,26 @@ struct Quotation {
 
     text: String,
 
+    /// Alternative texts of the quotation
+    variants: Vec<String>,
+
     tags: SmallVec<[DefaultAtom; 2]>,
 
     url: Option<SerdeUrl>,
 
     anti_ping_tactic: AntiPingTactic,
+
+    /// The relative likelihood of the quotation's being chosen when it is one of several
+    /// quotations that satisfy a request, which is a finite, non-negative number
+    weight: f64,
+
+    /// If the quotation's text is to be loaded lazily, this is the byte range of the quotation's
+    /// record in its file, from which its text is to be read when it is needed, and `text` is
+    /// empty.
+    text_location: Option<Range<usize>>,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, EnumIter, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Deserialize, EnumIter, Eq, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "kebab-case")]
 enum QuotationFormat {
@@ -405,7 +664,7 @@ fn default_quotation_format_for_serde() -> QuotationFormat {
     QuotationFormat::Chat
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, EnumIter, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Deserialize, EnumIter, Eq, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "kebab-case")]
 enum AntiPingTactic {
@@ -418,13 +677,19 @@ fn default_anti_ping_tactic_for_serde() -> AntiPingTactic {
     AntiPingTactic::Munge
 }
 
+fn default_quotation_weight_for_serde() -> f64 {
+    DEFAULT_QUOTATION_WEIGHT
+}
+
 #[derive(Debug)]
 enum QuotationChoice<'q> {
     /// Reply with the text of the quotation.
     Text {
-        quotation: &'q Quotation,
-        // variant_id: usize,
-        // TODO: ^
+        /// The quotation, with its text replaced by that of the chosen variant, boxed so that a
+        /// `QuotationChoice` is small enough to pass around cheaply
+        quotation: Box<Cow<'q, Quotation>>,
+
+        variant_id: usize,
     },
 
     /// Reply with the URL of the quotation.
@@ -439,6 +704,7 @@ impl QuotationDatabase {
         QuotationDatabase {
             files: Default::default(),
             quotations: Default::default(),
+            weighted: false,
         }
     }
 
@@ -449,40 +715,123 @@ impl QuotationDatabase {
     fn get_quotation_by_id(&self, id: QuotationId) -> Option<&Quotation> {
         self.quotations.get(id.array_index())
     }
+
+    /// Returns the given quotation with its text, which is read from the quotation's file if it is
+    /// to be loaded lazily.
+    fn with_text<'q>(&self, quotation: &'q Quotation) -> Result<Cow<'q, Quotation>> {
+        let range = match quotation.text_location {
+            Some(ref range) => range.clone(),
+            None => return Ok(Cow::Borrowed(quotation)),
+        };
+
+        let file = self
+            .get_file_metadata_by_id(quotation.file_id)
+            .expect("A quotation's file should be in the quotation database.");
+
+        let QuotationIR { text, variants, .. } = read_lazy_quotation_record(file, range)?;
+
+        Ok(Cow::Owned(Quotation {
+            text,
+            variants,
+            text_location: None,
+            ..quotation.clone()
+        }))
+    }
 }
 
 fn quote(ctx: HandlerContext, arg: &Yaml) -> std::result::Result<Reaction, BotCmdResult> {
-    let state = ctx.state;
-    let request_metadata = ctx.request_metadata();
     let params = prepare_quote_params(&ctx, arg)?;
+    let qdb = read_qdb()?;
+
+    quote_with_params(&ctx, &params, &qdb)
+}
+
+fn quote_last(ctx: HandlerContext, _: &Yaml) -> std::result::Result<Reaction, BotCmdResult> {
     let reply_dest = ctx.guess_reply_dest()?;
     let qdb = read_qdb()?;
+
+    let (id, variant) = match LAST_QUOTATIONS
+        .lock_clean("the record of recently shown quotations")?
+        .get(&(reply_dest.server_id, reply_dest.target.to_owned()))
+    {
+        Some(&last) => last,
+        None => {
+            return Ok(Reaction::Reply(
+                "I have not shown a quotation here recently.".into(),
+            ))
+        }
+    };
+
+    if qdb.get_quotation_by_id(id).is_none() {
+        return Ok(Reaction::Reply(
+            "The quotation I most recently showed here is no longer in my quotation database."
+                .into(),
+        ));
+    }
+
+    let params = QuoteParams {
+        id: Some(id.to_string().into()),
+        variant,
+        ..Default::default()
+    };
+
+    quote_with_params(&ctx, &params, &qdb)
+}
+
+fn quote_with_params(
+    ctx: &HandlerContext,
+    params: &QuoteParams,
+    qdb: &QuotationDatabase,
+) -> std::result::Result<Reaction, BotCmdResult> {
+    let state = ctx.state;
+    let reply_dest = ctx.guess_reply_dest()?;
     let channel_users = state.with_aatxe_client(reply_dest.server_id, |aatxe_client| {
         Ok(aatxe_client
             .list_users(reply_dest.target)
             .unwrap_or_default())
     })?;
 
-    let output_text = match pick_quotation(&ctx, &params, reply_dest, &qdb, &channel_users) {
-        Ok(QuotationChoice::Text { quotation }) => {
-            render_quotation(&params, quotation, &channel_users)?.into()
+    let choice = pick_quotation(ctx, params, reply_dest, qdb, &channel_users)?;
+
+    let shown = match choice {
+        QuotationChoice::Text {
+            ref quotation,
+            variant_id,
+        } => (quotation.id, Some(variant_id)),
+        QuotationChoice::Url { quotation_id, .. } => (quotation_id, None),
+    };
+
+    let output_text = match choice {
+        QuotationChoice::Text { quotation, .. } => {
+            render_quotation(params, &quotation, &channel_users)?.into()
         }
-        Ok(QuotationChoice::Url { quotation_id, url }) => {
+        QuotationChoice::Url { quotation_id, url } => {
             format!("[{id}] <{url}>", id = quotation_id, url = url).into()
         }
-        Err(msg) => return Err(msg),
     };
 
+    LAST_QUOTATIONS
+        .lock_clean("the record of recently shown quotations")?
+        .insert((reply_dest.server_id, reply_dest.target.to_owned()), shown);
+
     Ok(Reaction::Msg(output_text))
 }
 
 #[derive(Debug, Default)]
 struct QuoteParams<'a> {
-    // TODO: Use `RegexSet`.
-    regexes: SmallVec<[Regex; 8]>,
+    regexes: regex::RegexSet,
     literals: SmallVec<[Cow<'a, str>; 8]>,
+    fuzzy: bool,
     tags: SmallVec<[Cow<'a, str>; 4]>,
+    speakers: SmallVec<[Cow<'a, str>; 2]>,
+    excluded_regexes: regex::RegexSet,
+    excluded_literals: SmallVec<[Cow<'a, str>; 4]>,
+    excluded_tags: SmallVec<[Cow<'a, str>; 4]>,
     id: Option<Cow<'a, str>>,
+    file: Option<Cow<'a, str>>,
+    /// The variant of the requested quotation to show, which cannot be specified by users but is
+    /// used by `quote-last`
+    variant: Option<usize>,
     anti_ping_tactic: Option<AntiPingTactic>,
 }
 
@@ -504,21 +853,21 @@ fn prepare_quote_params<'arg>(
         }
     }
 
-    let regexes = iter_as_seq(get_arg_by_short_or_long_key(
-        arg,
-        &YAML_STR_R,
-        &YAML_STR_REGEX,
-    )?)
-    .map(|y| {
-        scalar_to_str(
-            y,
-            Cow::Borrowed,
-            "a search term given in the argument `regex`",
-        )
-        .map_err(Into::into)
-    })
-    .map_results(|s| s.as_ref().parse().map_err(Into::into))
-    .collect::<Result<Result<_>>>()??;
+    let regexes = regex::RegexSet::new(
+        iter_as_seq(get_arg_by_short_or_long_key(
+            arg,
+            &YAML_STR_R,
+            &YAML_STR_REGEX,
+        )?)
+        .map(|y| {
+            scalar_to_str(
+                y,
+                Cow::Borrowed,
+                "a search term given in the argument `regex`",
+            )
+        })
+        .collect::<util::yaml::Result<SmallVec<[_; 8]>>>()?,
+    )?;
 
     let literals = iter_as_seq(get_arg_by_short_or_long_key(
         arg,
@@ -535,6 +884,8 @@ fn prepare_quote_params<'arg>(
     })
     .collect::<Result<_>>()?;
 
+    let fuzzy = get_arg_as(arg, &YAML_STR_FUZZY)?.unwrap_or(false);
+
     let tags = iter_as_seq(arg.get(&YAML_STR_TAG))
         .map(|y| {
             scalar_to_str(
@@ -546,25 +897,73 @@ fn prepare_quote_params<'arg>(
         })
         .collect::<Result<_>>()?;
 
+    let speakers = iter_as_seq(arg.get(&YAML_STR_BY))
+        .map(|y| {
+            scalar_to_str(y, Cow::Borrowed, "a nickname given in the argument `by`")
+                .map_err(Into::into)
+        })
+        .collect::<Result<_>>()?;
+
+    let excluded_regexes = regex::RegexSet::new(
+        iter_as_seq(arg.get(&YAML_STR_NOT_REGEX))
+            .map(|y| {
+                scalar_to_str(
+                    y,
+                    Cow::Borrowed,
+                    "a search term given in the argument `not-regex`",
+                )
+            })
+            .collect::<util::yaml::Result<SmallVec<[_; 4]>>>()?,
+    )?;
+
+    let excluded_literals = iter_as_seq(arg.get(&YAML_STR_NOT_STRING))
+        .map(|y| {
+            scalar_to_str(
+                y,
+                Cow::Borrowed,
+                "a search term given in the argument `not-string`",
+            )
+            .map_err(Into::into)
+        })
+        .collect::<Result<_>>()?;
+
+    let excluded_tags = iter_as_seq(arg.get(&YAML_STR_NOT_TAG))
+        .map(|y| {
+            scalar_to_str(
+                y,
+                Cow::Borrowed,
+                "a search term given in the argument `not-tag`",
+            )
+            .map_err(Into::into)
+        })
+        .collect::<Result<_>>()?;
+
     let id = arg
         .get(&YAML_STR_ID)
         .try_map(|y| scalar_to_str(y, Cow::Borrowed, "the argument `id`"))?;
 
-    let anti_ping_tactic = arg
-        .get(&YAML_STR_ANTI_PING_TACTIC)
-        .try_map(|y| scalar_to_str(y, Cow::Borrowed, "the argument `anti-ping tactic`"))?
-        .try_map(|s: Cow<'arg, str>| serde_yaml::from_str(&s))?;
+    let file = arg
+        .get(&YAML_STR_FILE)
+        .try_map(|y| scalar_to_str(y, Cow::Borrowed, "the argument `file`"))?;
+
+    let anti_ping_tactic = get_arg_as(arg, &YAML_STR_ANTI_PING_TACTIC)?;
 
     Ok(QuoteParams {
         regexes,
         literals,
+        fuzzy,
         tags,
+        speakers,
+        excluded_regexes,
+        excluded_literals,
+        excluded_tags,
         id,
+        file,
+        variant: None,
         anti_ping_tactic,
     })
 }
 
-// TODO: Probabilities
 fn pick_quotation<'q>(
     ctx: &HandlerContext,
     arg: &QuoteParams,
@@ -583,31 +982,84 @@ fn pick_quotation<'q>(
         None => &qdb.quotations,
     };
 
-    let file_permissions = check_file_permissions(qdb, reply_dest);
+    let file_permissions = check_file_permissions(state, qdb, reply_dest)?;
+
+    let requested_file_id = match arg.file {
+        Some(ref requested_file_name) => Some(get_file_id_by_user_specified_name(
+            qdb,
+            &file_permissions,
+            requested_file_name,
+        )?),
+        None => None,
+    };
 
     let mut rejected_a_quotation_for_length = false;
 
-    quotations
-        .rand_iter()
+    // Consider the candidate quotations in a random order, such that the first to satisfy the
+    // query parameters is chosen uniformly at random from those that do, or, if any quotations
+    // have non-default weights, is chosen with probability proportional to its weight.
+    let candidates = if qdb.weighted && arg.id.is_none() {
+        Either::Left(weighted_random_order(quotations, state.rng()?.deref_mut()).into_iter())
+    } else {
+        Either::Right(quotations.rand_iter())
+    };
+
+    candidates
         .filter_map(
             |quotation: &'q Quotation| -> Option<Result<QuotationChoice>> {
                 match (|quotation: &'q Quotation| -> Result<Option<QuotationChoice>> {
-                    if !quotation_matches_query_params(arg, quotation)? {
+                    if file_permissions.get(quotation.file_id.array_index()) != Some(true) {
                         return Ok(None);
                     }
 
-                    if file_permissions.get(quotation.file_id.array_index()) != Some(true) {
+                    if requested_file_id.is_some() && requested_file_id != Some(quotation.file_id) {
                         return Ok(None);
                     }
 
-                    // TODO: Pick a random variant that satisfies query parameters
+                    // Check the file permissions, which is cheap, before reading the quotation's
+                    // text if it is to be loaded lazily.
+                    let with_text = qdb.with_text(quotation)?;
+
+                    let variant_ids = (0..=with_text.variants.len())
+                        .filter(|&id| arg.variant.is_none() || arg.variant == Some(id))
+                        .collect::<SmallVec<[usize; 4]>>();
+
+                    let mut a_variant_was_too_long = false;
+
+                    // Try the quotation's variants in a random order, choosing the first that
+                    // satisfies the query parameters and can be posted here.
+                    for &variant_id in variant_ids.rand_iter() {
+                        let variant = quotation_variant(&with_text, variant_id);
+
+                        if !quotation_matches_query_params(arg, &variant)? {
+                            continue;
+                        }
+
+                        if rendered_quotation_byte_len(&variant) > reply_content_max_len {
+                            a_variant_was_too_long = true;
+                            continue;
+                        }
+
+                        if arg.anti_ping_tactic.unwrap_or(quotation.anti_ping_tactic)
+                            == AntiPingTactic::Eschew
+                            && quotation_text_contains_any_nick(&variant, channel_users)
+                        {
+                            continue;
+                        }
+
+                        return Ok(Some(QuotationChoice::Text {
+                            quotation: Box::new(variant),
+                            variant_id,
+                        }));
+                    }
 
-                    // If the quotation is too long to post to this channel in a single `PRIVMSG`,
-                    // post its URL if it has one, or try a different quotation otherwise.
+                    // If the quotation matches the query parameters but is too long to post to
+                    // this channel in a single `PRIVMSG`, post its URL if it has one, or try a
+                    // different quotation otherwise.
                     //
                     // Now, it's possible that even the URL wouldn't fit in one `PRIVMSG`. Perhaps
                     // something should be done about that.
-                    if rendered_quotation_byte_len(quotation) > reply_content_max_len {
+                    if a_variant_was_too_long {
                         return match quotation.url {
                             Some(ref url) => Ok(Some(QuotationChoice::Url {
                                 quotation_id: quotation.id,
@@ -622,14 +1074,7 @@ fn pick_quotation<'q>(
                         };
                     }
 
-                    if arg.anti_ping_tactic.unwrap_or(quotation.anti_ping_tactic)
-                        == AntiPingTactic::Eschew
-                        && quotation_text_contains_any_nick(quotation, channel_users)
-                    {
-                        return Ok(None);
-                    }
-
-                    Ok(Some(QuotationChoice::Text { quotation }))
+                    Ok(None)
                 })(quotation)
                 {
                     Ok(Some(q)) => Some(Ok(q)),
@@ -656,6 +1101,22 @@ fn pick_quotation<'q>(
         })
 }
 
+/// Returns the given quotation with its text replaced by that of the variant with the given ID,
+/// where the quotation's `text` is variant 0 and its `variants` are variants 1 and onward.
+///
+/// # Panics
+///
+/// This function panics if the quotation has no variant with the given ID.
+fn quotation_variant<'q>(quotation: &Cow<'q, Quotation>, variant_id: usize) -> Cow<'q, Quotation> {
+    match variant_id {
+        0 => quotation.clone(),
+        _ => Cow::Owned(Quotation {
+            text: quotation.variants[variant_id - 1].clone(),
+            ..(**quotation).clone()
+        }),
+    }
+}
+
 fn render_quotation(
     arg: &QuoteParams,
     quotation: &Quotation,
@@ -681,6 +1142,33 @@ fn render_quotation(
     ))
 }
 
+/// Returns references to the given quotations in a random order in which each quotation's
+/// probability of coming before any other is proportional to its weight. Consequently, the first
+/// of the returned quotations that satisfies any given criterion is chosen from among all the
+/// quotations that satisfy it with probability proportional to its weight. Quotations whose weight
+/// is zero are omitted.
+///
+/// This is done by assigning each quotation an exponentially distributed random key with rate
+/// equal to its weight, and sorting the quotations by their keys.
+fn weighted_random_order<'q, R>(quotations: &'q [Quotation], rng: &mut R) -> Vec<&'q Quotation>
+where
+    R: Rng,
+{
+    let mut keyed = quotations
+        .iter()
+        .filter(|quotation| quotation.weight > 0.0)
+        .map(|quotation| {
+            // `gen` returns a number in the interval [0, 1), so the logarithm here is finite.
+            let key = -(1.0 - rng.gen::<f64>()).ln() / quotation.weight;
+            (key, quotation)
+        })
+        .collect::<Vec<_>>();
+
+    keyed.sort_unstable_by(|&(a, _), &(b, _)| a.partial_cmp(&b).unwrap_or(Ordering::Equal));
+
+    keyed.into_iter().map(|(_, quotation)| quotation).collect()
+}
+
 /// Appends the pieces of the given quotation's text to `buf`, applying anti-ping tactics, and
 /// returns whether the quotation is considered to have been abridged in the process.
 ///
@@ -784,7 +1272,7 @@ where
 //     fn next(&mut self) -> Option<&'q str> {}
 // }
 
-fn munge_user_nicks<'a, 'u>(s: &'a str, users: &'u [AatxeUser]) -> util::Munge<'a> {
+fn munge_user_nicks<'a>(s: &'a str, users: &[AatxeUser]) -> util::Munge<'a> {
     util::zwsp_munge(s, users.iter().map(|user| user.get_nickname()))
 }
 
@@ -822,6 +1310,26 @@ fn strip_chat_metadata(line: &str) -> Option<&str> {
         .map(|line| line.trim_start_matches(">"))
 }
 
+/// Returns the nickname of the speaker of the given line of a `chat`-format quotation, which
+/// should already have been stripped of metadata by `strip_chat_metadata`, or `None` if the line
+/// does not begin with `<nick>` or `* nick`. Any channel status sigils before the nickname are
+/// omitted.
+fn chat_line_speaker(line: &str) -> Option<&str> {
+    let nick = match line.as_bytes().first() {
+        Some(b'<') => &line[1..line.find('>')?],
+        Some(b'*') => line[1..].split_whitespace().next()?,
+        _ => return None,
+    };
+
+    let nick = nick.trim().trim_start_matches(|c| "~&@%+".contains(c));
+
+    if nick.is_empty() {
+        None
+    } else {
+        Some(nick)
+    }
+}
+
 fn strip_quotation_lines<F>(
     quotation: &Quotation,
     filter_map: F,
@@ -866,8 +1374,15 @@ fn quotation_matches_query_params(
     QuoteParams {
         ref regexes,
         ref literals,
+        fuzzy,
         ref tags,
+        ref speakers,
+        ref excluded_regexes,
+        ref excluded_literals,
+        ref excluded_tags,
         id: _,
+        file: _,
+        variant: _,
         anti_ping_tactic: _,
     }: &QuoteParams,
     quotation: &Quotation,
@@ -888,6 +1403,52 @@ fn quotation_matches_query_params(
         return Ok(false);
     }
 
+    // Make sure that each requested speaker speaks in the quotation.
+    if !speakers.iter().all(|speaker| {
+        quotation.format == QuotationFormat::Chat
+            && chat_lines_stripped(quotation)
+                .filter_map(chat_line_speaker)
+                .any(|nick| case_insensitive_str_cmp(nick, speaker.as_ref()) == Ordering::Equal)
+    }) {
+        return Ok(false);
+    }
+
+    // Make sure that the quotation has none of the excluded tags.
+    if excluded_tags.iter().any(|tag_excluded| {
+        quotation
+            .tags
+            .iter()
+            .any(|tag_found| tag_found == tag_excluded.as_ref())
+    }) {
+        return Ok(false);
+    }
+
+    // Make sure that neither the quotation's text nor its tags contain any of the excluded search
+    // terms.
+    if !excluded_regexes.is_empty() || !excluded_literals.is_empty() {
+        let contains_excluded_term = |haystack: &str| {
+            excluded_regexes.is_match(haystack)
+                || excluded_literals.iter().any(|literal| {
+                    if *fuzzy {
+                        fuzzy_contains(haystack, literal)
+                    } else {
+                        haystack.contains(literal.as_ref())
+                    }
+                })
+        };
+
+        let text_contains_excluded_term = match quotation.format {
+            QuotationFormat::Chat => chat_lines_stripped(quotation).any(&contains_excluded_term),
+            QuotationFormat::Plain => contains_excluded_term(&quotation.text),
+        };
+
+        if text_contains_excluded_term
+            || quotation.tags.iter().any(|tag| contains_excluded_term(tag))
+        {
+            return Ok(false);
+        }
+    }
+
     // These bit vectors record whether a match for each search term has been found in the
     // quotation's text.
     let mut regexes_matched = SmallBitVec::from_elem(regexes.len(), false);
@@ -897,11 +1458,19 @@ fn quotation_matches_query_params(
     // given text, marks any it finds as matched, and returns whether all the search terms have
     // been matched.
     let mut check_all_search_terms = |haystack| {
-        check_search_terms(regexes, &mut regexes_matched, |regex| {
-            regex.is_match(haystack)
-        });
+        // Match all the regexes against the haystack in a single pass, unless they all have been
+        // matched already.
+        if !regexes_matched.all_true() {
+            for index in regexes.matches(haystack).into_iter() {
+                regexes_matched.set(index, true);
+            }
+        }
         check_search_terms(literals, &mut literals_matched, |literal| {
-            haystack.contains(literal.as_ref())
+            if *fuzzy {
+                fuzzy_contains(haystack, literal)
+            } else {
+                haystack.contains(literal.as_ref())
+            }
         });
 
         if regexes_matched.all_true() && literals_matched.all_true() {
@@ -953,6 +1522,43 @@ fn quotation_matches_query_params(
     Ok(false)
 }
 
+/// Returns whether `haystack` contains a substring whose edit distance (Levenshtein distance) from
+/// `needle` is at most one per `FUZZY_MATCH_CHARS_PER_EDIT` characters of `needle`.
+///
+/// This takes time proportional to the product of the lengths of `haystack` and `needle`.
+fn fuzzy_contains(haystack: &str, needle: &str) -> bool {
+    let needle = needle.chars().collect::<SmallVec<[char; 32]>>();
+    let max_distance = needle.len() / FUZZY_MATCH_CHARS_PER_EDIT;
+
+    // `distances[i]` is the least edit distance between the first `i` characters of `needle` and
+    // any substring of `haystack` ending at the current position therein. As a match may start
+    // anywhere in `haystack`, `distances[0]` always is zero.
+    let mut distances = (0..=needle.len()).collect::<SmallVec<[usize; 32]>>();
+
+    if distances[needle.len()] <= max_distance {
+        return true;
+    }
+
+    for c in haystack.chars() {
+        let mut diagonal = distances[0];
+
+        for (i, &needle_char) in needle.iter().enumerate() {
+            let substitution = diagonal + if needle_char == c { 0 } else { 1 };
+            let insertion = distances[i + 1] + 1;
+            let deletion = distances[i] + 1;
+
+            diagonal = distances[i + 1];
+            distances[i + 1] = cmp::min(substitution, cmp::min(insertion, deletion));
+        }
+
+        if distances[needle.len()] <= max_distance {
+            return true;
+        }
+    }
+
+    false
+}
+
 fn quotation_byte_len(quotation: &Quotation) -> usize {
     match quotation.format {
         QuotationFormat::Chat => {
@@ -987,26 +1593,100 @@ fn rendered_quotation_byte_len(quotation: &Quotation) -> usize {
 /// quotation files.
 ///
 /// This function's return value is such that, with `file: QuotationFileMetadata`,
-/// `check_file_permissions(qdb, msg_dest).get(file.array_index())` is `Some(true)` if and only if
-/// the message destination `msg_dest` is allowed to see `file`'s quotations. In actual usage, this
-/// function's return value should be saved and not recomputed for each quotation file.
+/// `check_file_permissions(state, qdb, msg_dest)?.get(file.array_index())` is `Some(true)` if and
+/// only if the message destination `msg_dest` is allowed to see `file`'s quotations.
+///
+/// A file's `channels` regex is matched against the server-qualified identifier of the message
+/// destination (e.g., `freenode/##rust`) and, for compatibility with quotation files written
+/// before channel identifiers were introduced, against the bare channel name (e.g., `##rust`). A
+/// file is also visible in a channel that, per the `can see` and `seen by` per-channel settings in
+/// the bot's configuration, can see a configured channel that the file's `channels` regex matches
+/// by identifier or by name.
 ///
 /// It is assumed that checking permissions for each file is more efficient than doing so for each
 /// candidate quotation, as there are expected to be few files and many quotations.
+///
+/// The result is cached per message destination until the quotation database is next loaded or
+/// the bot's configuration is reloaded.
 fn check_file_permissions(
+    state: &State,
+    qdb: &QuotationDatabase,
+    dest: MsgDest,
+) -> Result<SmallBitVec> {
+    let key = (
+        dest.server_id,
+        dest.target.to_owned(),
+        state.config_generation(),
+    );
+
+    if let Some(permissions) = FILE_PERMISSIONS_CACHE
+        .lock_clean("the cache of quotation file permissions")?
+        .get(&key)
+    {
+        return Ok(permissions.clone());
+    }
+
+    let permissions = compute_file_permissions(state, qdb, dest)?;
+
+    FILE_PERMISSIONS_CACHE
+        .lock_clean("the cache of quotation file permissions")?
+        .insert(key, permissions.clone());
+
+    Ok(permissions)
+}
+
+fn compute_file_permissions(
+    state: &State,
     QuotationDatabase { files, .. }: &QuotationDatabase,
-    MsgDest { server_id, target }: MsgDest,
-) -> SmallBitVec {
-    // TODO: Account for the server as well as the channel, with a `servers` field in the quotation
-    // files.
+    dest: MsgDest,
+) -> Result<SmallBitVec> {
+    let mut visible_channels = Vec::new();
+
+    if let Some(dest_id) = state.channel_id(dest)? {
+        visible_channels.push(dest_id.to_string());
+
+        for id in state.configured_channel_ids() {
+            if id != dest_id && state.channel_can_see(&dest_id, &id)? {
+                visible_channels.push(id.to_string());
+                visible_channels.push(id.channel().to_string());
+            }
+        }
+    }
 
     let mut result = SmallBitVec::from_elem(files.len(), false);
 
     for (index, file) in files.iter().enumerate() {
-        result.set(index, file.channels_regex.is_match(target));
+        let regex = &file.channels_regex;
+        let visible =
+            regex.is_match(dest.target) || visible_channels.iter().any(|chan| regex.is_match(chan));
+        result.set(index, visible);
     }
 
-    result
+    Ok(result)
+}
+
+/// Returns the ID of the quotation file with the given name, if there is such a file whose
+/// quotations may be shown where `file_permissions` were checked.
+fn get_file_id_by_user_specified_name(
+    qdb: &QuotationDatabase,
+    file_permissions: &SmallBitVec,
+    requested_file_name: &str,
+) -> std::result::Result<QuotationFileId, BotCmdResult> {
+    match qdb.files.iter().find(|file| {
+        file.name == requested_file_name
+            && file_permissions.get(file.file_id.array_index()) == Some(true)
+    }) {
+        Some(file) => Ok(file.file_id),
+        None => Err(BotCmdResult::UserErrMsg(
+            format!(
+                "The given value of the parameter `file`, {input:?}, was not recognized as the \
+                 name of a quotation file from which I may quote here. Try my \
+                 `quote-database-info` command for a list of such files.",
+                input = requested_file_name,
+            )
+            .into(),
+        )),
+    }
 }
 
 fn get_quotation_by_user_specified_id<'q, 'arg>(
@@ -1041,7 +1721,7 @@ fn get_quotation_by_user_specified_id<'q, 'arg>(
 fn show_qdb_info(ctx: HandlerContext, _: &Yaml) -> Result<Reaction> {
     let qdb = read_qdb()?;
     let reply_dest = ctx.guess_reply_dest()?;
-    let file_permissions = check_file_permissions(&qdb, reply_dest);
+    let file_permissions = check_file_permissions(ctx.state, &qdb, reply_dest)?;
     let any_files_are_visible = !file_permissions.is_empty() && !file_permissions.all_false();
 
     Ok(Reaction::Msgs(
@@ -1068,40 +1748,577 @@ fn show_qdb_info(ctx: HandlerContext, _: &Yaml) -> Result<Reaction> {
     ))
 }
 
+fn quote_formats(_: HandlerContext, _: &Yaml) -> Result<Reaction> {
+    Ok(Reaction::Reply(
+        format!(
+            "Quotation formats: {formats}. Anti-ping tactics: {tactics}.",
+            formats = serde_names(QuotationFormat::iter())?.join(", "),
+            tactics = serde_names(AntiPingTactic::iter())?.join(", "),
+        )
+        .into(),
+    ))
+}
+
+/// Returns the names by which the given unit enum variants are written in quotation files.
+fn serde_names<I>(values: I) -> Result<Vec<String>>
+where
+    I: IntoIterator,
+    I::Item: Serialize + fmt::Debug,
+{
+    values
+        .into_iter()
+        .map(|value| match serde_yaml::to_value(&value)? {
+            serde_yaml::Value::String(name) => Ok(name),
+            _ => Err(format!("{:?} is not serialized as a string.", value).into()),
+        })
+        .collect()
+}
+
 fn reload_qdb(ctx: HandlerContext, _: &Yaml) -> Result<Reaction> {
     on_load(ctx.state)?;
 
     let qdb = read_qdb()?;
 
-    let chat_text_pieces_5ns = {
-        let mut quantiles = CKMS::new(0.0001);
-        for quotation in &qdb.quotations {
-            if quotation.format == QuotationFormat::Chat {
+    // Quotations whose texts are to be loaded lazily are left out of these summaries, so as not to
+    // read them all.
+    let loaded_quotations = || {
+        qdb.quotations
+            .iter()
+            .filter(|quotation| quotation.text_location.is_none())
+    };
+
+    let chat_text_pieces_5ns = five_number_summary(
+        loaded_quotations()
+            .filter(|quotation| quotation.format == QuotationFormat::Chat)
+            .map(|quotation| {
                 let mut text_piece_qty: u32 = 0;
                 for_each_quotation_text_piece(&Default::default(), quotation, &[], |_| {
                     text_piece_qty = text_piece_qty.saturating_add(1)
                 });
-                quantiles.insert(text_piece_qty)
-            }
-        }
-        [0.0, 0.25, 0.5, 0.75, 1.0]
-            .iter()
-            .filter_map(|&q| quantiles.query(q).map(|(_, r)| r))
-            .collect::<SmallVec<[_; 5]>>()
-    };
+                text_piece_qty
+            }),
+    );
+
+    // Lengths too great for a `u32` are counted as the greatest `u32`.
+    let byte_len_5ns = five_number_summary(loaded_quotations().map(|quotation| {
+        u32::try_from(rendered_quotation_byte_len(quotation)).unwrap_or(!0)
+    }));
 
-    // TODO: Also report a 5NS for the byte-lengths of quotations.
     Ok(Reaction::Msg(
         format!(
             "I have reloaded my quotation database. The five-number summary of the numbers of \
              pieces into which chat-format quotations' texts get broken, assuming no anti-ping \
-             munging, is {chat_text_pieces_5ns:?}.",
+             munging, is {chat_text_pieces_5ns:?}. The five-number summary of the lengths in \
+             bytes of quotations as they would be posted, assuming no abridgement or anti-ping \
+             munging, is {byte_len_5ns:?}.",
             chat_text_pieces_5ns = chat_text_pieces_5ns,
+            byte_len_5ns = byte_len_5ns,
+        )
+        .into(),
+    ))
+}
+
+/// Returns the five-number summary (the minimum, lower quartile, median, upper quartile, and
+/// maximum) of the given values, or an empty list if there are none.
+fn five_number_summary<I>(values: I) -> SmallVec<[u32; 5]>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut quantiles = CKMS::new(0.0001);
+
+    for value in values {
+        quantiles.insert(value)
+    }
+
+    [0.0, 0.25, 0.5, 0.75, 1.0]
+        .iter()
+        .filter_map(|&q| quantiles.query(q).map(|(_, r)| r))
+        .collect()
+}
+
+fn quote_add(ctx: HandlerContext, arg: &Yaml) -> std::result::Result<Reaction, BotCmdResult> {
+    let arg = arg.as_hash().expect(FW_SYNTAX_CHECK_FAIL);
+
+    let text = scalar_to_str(
+        arg.get(&YAML_STR_TEXT).expect(FW_SYNTAX_CHECK_FAIL),
+        Cow::Borrowed,
+        "the argument `text`",
+    )?;
+
+    let tags = iter_as_seq(arg.get(&YAML_STR_TAGS))
+        .map(|y| {
+            scalar_to_str(y, Cow::Borrowed, "a tag given in the argument `tags`")
+                .map(|s| DefaultAtom::from(&*s))
+                .map_err(Into::into)
+        })
+        .collect::<Result<_>>()?;
+
+    let format = get_arg_as(arg, &YAML_STR_FORMAT)?;
+
+    let channels = arg
+        .get(&YAML_STR_CHANNELS)
+        .try_map(|y| scalar_to_str(y, Cow::Borrowed, "the argument `channels`"))?;
+
+    let new_quotation = QuotationIR {
+        format,
+        text: text.into_owned(),
+        tags,
+        variants: Vec::new(),
+        url: None,
+        anti_ping_tactic: None,
+        weight: None,
+    };
+
+    let data_path = ctx.state.module_data_path()?.join("quote");
+    let path = data_path.join(ADDED_QUOTATIONS_FILE_NAME);
+
+    // Hold the lock on the quotation database until the new quotation has been written and the
+    // database reloaded, so that concurrent additions don't interfere with one another.
+    let mut qdb = write_qdb()?;
+
+    let (mut new_text, old_len) = if path.exists() {
+        if channels.is_some() {
+            return Err(BotCmdResult::UserErrMsg(
+                format!(
+                    "The quotation file {name:?} already exists, so the parameter `channels` \
+                     cannot be used; its `channels` field can be changed only by editing the \
+                     file.",
+                    name = ADDED_QUOTATIONS_FILE_NAME,
+                )
+                .into(),
+            ));
+        }
+
+        let old_len = fs::metadata(&path)?.len();
+        let mut new_text = String::new();
+
+        if old_len > 0 && !fs::read_to_string(&path)?.ends_with('\n') {
+            new_text.push('\n');
+        }
+
+        (new_text, Some(old_len))
+    } else {
+        let channels = match channels {
+            Some(channels) => channels.into_owned(),
+            None => {
+                let reply_dest = ctx.guess_reply_dest()?;
+                let channel = match ctx.state.channel_id(reply_dest)? {
+                    Some(id) => id.to_string(),
+                    None => reply_dest.target.to_owned(),
+                };
+                regex::escape(&channel)
+            }
+        };
+
+        let mut header = BTreeMap::new();
+        header.insert("channels", channels);
+
+        let mut new_text = to_yaml_fragment(&header)?;
+        new_text.push_str("quotations:\n");
+
+        fs::create_dir_all(&data_path)?;
+
+        (new_text, None)
+    };
+
+    new_text.push_str(&to_yaml_fragment(&[new_quotation])?);
+
+    let old_quotation_count = file_quotation_count(&qdb, ADDED_QUOTATIONS_FILE_NAME);
+
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)?
+        .write_all(new_text.as_bytes())?;
+
+    let load_result = load_qdb(ctx.state, &mut qdb);
+    let new_quotation_count = file_quotation_count(&qdb, ADDED_QUOTATIONS_FILE_NAME);
+
+    if load_result.is_err() || new_quotation_count != Some(old_quotation_count.unwrap_or(0) + 1) {
+        // Undo the addition, so as not to leave behind a quotation file that fails to load or
+        // has been corrupted.
+        match old_len {
+            Some(old_len) => OpenOptions::new()
+                .write(true)
+                .open(&path)?
+                .set_len(old_len)?,
+            None => fs::remove_file(&path)?,
+        }
+        load_qdb(ctx.state, &mut qdb)?;
+        load_result?;
+
+        return Err(BotCmdResult::UserErrMsg(
+            format!(
+                "Appending the quotation to the quotation file {name:?} did not add it to my \
+                 quotation database, so I have left the file as it was. Please make sure that \
+                 the file's `quotations` field is a block sequence at the end of the file.",
+                name = ADDED_QUOTATIONS_FILE_NAME,
+            )
+            .into(),
+        ));
+    }
+
+    let id = qdb
+        .files
+        .iter()
+        .find(|file| file.name == ADDED_QUOTATIONS_FILE_NAME)
+        .and_then(|file| {
+            qdb.quotations
+                .iter()
+                .rev()
+                .find(|quotation| quotation.file_id == file.file_id)
+        })
+        .map(|quotation| quotation.id)
+        .expect("The new quotation should have been loaded; we checked above.");
+
+    Ok(Reaction::Reply(
+        format!("I have added the quotation [{id}].", id = id).into(),
+    ))
+}
+
+/// Returns the number of quotations loaded from the quotation file with the given name, or `None`
+/// if no such file has been loaded.
+fn file_quotation_count(qdb: &QuotationDatabase, name: &str) -> Option<usize> {
+    qdb.files
+        .iter()
+        .find(|file| file.name == name)
+        .map(|file| file.quotation_count)
+}
+
+/// Serializes the given value as YAML text that can be appended to a quotation file, i.e., without
+/// the document start marker and with a trailing line-break.
+fn to_yaml_fragment<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let yaml = serde_yaml::to_string(value)?;
+    let mut fragment = yaml
+        .trim_start_matches("---")
+        .trim_start_matches('\n')
+        .to_owned();
+
+    if !fragment.ends_with('\n') {
+        fragment.push('\n');
+    }
+
+    Ok(fragment)
+}
+
+fn quote_remove(ctx: HandlerContext, arg: &Yaml) -> std::result::Result<Reaction, BotCmdResult> {
+    let arg = arg.as_hash().expect(FW_SYNTAX_CHECK_FAIL);
+
+    let requested_id = scalar_to_str(
+        arg.get(&YAML_STR_ID).expect(FW_SYNTAX_CHECK_FAIL),
+        Cow::Borrowed,
+        "the argument `id`",
+    )?;
+
+    // Hold the lock on the quotation database until the quotation has been removed and the
+    // database reloaded, so that concurrent changes don't interfere with one another.
+    let mut qdb = write_qdb()?;
+
+    let (id, file_id) = {
+        let quotation = get_quotation_by_user_specified_id(&qdb, &requested_id)?;
+        (quotation.id, quotation.file_id)
+    };
+
+    let file_name = qdb
+        .get_file_metadata_by_id(file_id)
+        .map(|file| file.name.clone())
+        .expect("A quotation's file should be in the quotation database.");
+
+    // The index of the quotation's record in its file's `quotations` sequence.
+    let index_in_file = qdb
+        .quotations
+        .iter()
+        .filter(|quotation| quotation.file_id == file_id)
+        .position(|quotation| quotation.id == id)
+        .expect("A quotation should be among the quotations loaded from its file.");
+
+    let path = ctx.state.module_data_path()?.join("quote").join(&file_name);
+
+    if fs::metadata(&path)?.permissions().readonly() {
+        return Err(BotCmdResult::UserErrMsg(
+            format!(
+                "The quotation [{id}] is in the quotation file {name:?}, which is read-only, so I \
+                 cannot remove it.",
+                id = id,
+                name = file_name,
+            )
+            .into(),
+        ));
+    }
+
+    let old_text = fs::read_to_string(&path)?;
+
+    let record_range = quotation_record_byte_ranges(&old_text)?
+        .and_then(|ranges| ranges.get(index_in_file).cloned());
+
+    let new_text = match record_range {
+        Some(range) => [&old_text[..range.start], &old_text[range.end..]].concat(),
+        None => {
+            return Err(BotCmdResult::UserErrMsg(
+                format!(
+                    "I cannot remove the quotation [{id}] from the quotation file {name:?} without \
+                     disturbing the file's formatting, because the file's `quotations` field is \
+                     not a block sequence with each quotation record starting on a line of its \
+                     own.",
+                    id = id,
+                    name = file_name,
+                )
+                .into(),
+            ));
+        }
+    };
+
+    // Make sure that exactly the intended quotation record has been removed before writing
+    // anything to the file.
+    let quotation_texts = |text: &str| -> Result<Vec<String>> {
+        let file: QuotationFileIR = serde_yaml::from_str(text)?;
+        Ok(file
+            .quotations
+            .unwrap_or_default()
+            .into_iter()
+            .map(|quotation| quotation.text)
+            .collect())
+    };
+
+    let mut expected_texts = quotation_texts(&old_text)?;
+    expected_texts.remove(index_in_file);
+
+    if quotation_texts(&new_text).ok() != Some(expected_texts) {
+        return Err(BotCmdResult::UserErrMsg(
+            format!(
+                "I failed to work out how to remove the quotation [{id}] from the quotation file \
+                 {name:?} without disturbing the file's other contents, so I have left the file \
+                 as it was.",
+                id = id,
+                name = file_name,
+            )
+            .into(),
+        ));
+    }
+
+    fs::write(&path, new_text)?;
+
+    if let Err(e) = load_qdb(ctx.state, &mut qdb) {
+        // Put the file back as it was, so as not to leave the quotation database broken.
+        fs::write(&path, old_text)?;
+        load_qdb(ctx.state, &mut qdb)?;
+        return Err(e.into());
+    }
+
+    Ok(Reaction::Reply(
+        format!(
+            "I have removed the quotation [{id}] from the quotation file {name:?}. Note that the \
+             identifiers of other quotations may have changed.",
+            id = id,
+            name = file_name,
         )
         .into(),
     ))
 }
 
+/// Finds the records of the quotations in the `quotations` sequence of the given text of a
+/// quotation file, and returns the byte ranges of the whole lines that each occupies, including
+/// any comments or blank lines that precede it, as such comments are taken to be about the record
+/// that they precede.
+///
+/// Returns `None` if the quotation records cannot be delimited by lines, as if the `quotations`
+/// field is not a block sequence.
+fn quotation_record_byte_ranges(text: &str) -> Result<Option<Vec<Range<usize>>>> {
+    let mut parser = yaml_rust::parser::Parser::new(text.chars());
+    let mut depth = 0usize;
+    let mut next_top_level_node_is_key = true;
+    let mut quotations_value_is_next = false;
+    let mut in_quotations = false;
+    let mut record_starts = Vec::new();
+    let mut record_start_may_be_later = false;
+    let mut quotations_end = None;
+
+    loop {
+        let (event, mark) = parser.next().map_err(util::yaml::Error::from)?;
+
+        // The parser marks the start of a block mapping where its first value starts, rather than
+        // where its first key does, so use the earlier of that mark and the mark of the mapping's
+        // first key.
+        if record_start_may_be_later {
+            record_start_may_be_later = false;
+            if let Some(start) = record_starts.last_mut() {
+                *start = cmp::min(*start, mark.index());
+            }
+        }
+
+        let (is_node_start, is_collection_start) = match event {
+            Event::StreamEnd => break,
+            Event::Scalar(..) | Event::Alias(_) => (true, false),
+            Event::MappingStart(_) | Event::SequenceStart(_) => (true, true),
+            Event::MappingEnd | Event::SequenceEnd => {
+                depth = depth.saturating_sub(1);
+                if in_quotations && depth == 1 {
+                    in_quotations = false;
+                    quotations_end = Some(mark.index());
+                }
+                (false, false)
+            }
+            _ => (false, false),
+        };
+
+        if is_node_start {
+            match depth {
+                // This node is a key or value in the file's top-level mapping.
+                1 => {
+                    let is_key = next_top_level_node_is_key;
+                    next_top_level_node_is_key = !is_key;
+
+                    if is_key {
+                        quotations_value_is_next = match event {
+                            Event::Scalar(ref key, ..) => key == "quotations",
+                            _ => false,
+                        };
+                    } else if let Event::SequenceStart(_) = event {
+                        in_quotations = quotations_value_is_next;
+                    }
+                }
+                // This node is an element of the `quotations` sequence.
+                2 if in_quotations => {
+                    record_starts.push(mark.index());
+                    record_start_may_be_later = is_collection_start;
+                }
+                _ => {}
+            }
+
+            if is_collection_start {
+                depth += 1;
+            }
+        }
+    }
+
+    // `Marker`s count characters rather than bytes. This converts character indices to byte
+    // indices in a single pass through the text, so it must be given them in ascending order.
+    let mut char_byte_indices = text
+        .char_indices()
+        .map(|(byte_index, _)| byte_index)
+        .chain(iter::once(text.len()))
+        .enumerate();
+    let mut byte_index = |char_index: usize| {
+        char_byte_indices
+            .find(|&(i, _)| i == char_index)
+            .map(|(_, byte_index)| byte_index)
+            .unwrap_or_else(|| text.len())
+    };
+
+    let mut line_starts = Vec::with_capacity(record_starts.len() + 1);
+    let mut entry_indent = 0;
+
+    for &record_start in &record_starts {
+        // Find the line holding the record's block sequence entry indicator (`-`), which must be
+        // preceded on its line only by indentation.
+        let before = text[..byte_index(record_start)].trim_end();
+
+        if !before.ends_with('-') {
+            return Ok(None);
+        }
+
+        let before = &before[..before.len() - 1];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        if !before[line_start..].trim().is_empty() {
+            return Ok(None);
+        }
+
+        entry_indent = before.len() - line_start;
+        line_starts.push(preceding_comments_start(text, line_start, entry_indent));
+    }
+
+    if let Some(end) = quotations_end {
+        let end = byte_index(end);
+
+        let line_start = if end >= text.len() {
+            text.len()
+        } else {
+            text[..end].rfind('\n').map(|i| i + 1).unwrap_or(0)
+        };
+
+        line_starts.push(preceding_comments_start(text, line_start, entry_indent));
+    }
+
+    let ranges = line_starts
+        .windows(2)
+        .map(|window| window[0]..window[1])
+        .collect::<Vec<_>>();
+
+    if ranges.iter().any(|range| range.start >= range.end) {
+        return Ok(None);
+    }
+
+    Ok(Some(ranges))
+}
+
+/// Returns the start of the run of comment lines and blank lines that directly precedes the line
+/// starting at the given byte index of the given text, or that index itself if there is no such
+/// run.
+///
+/// Comments indented more than `max_indent` are taken to belong to the node above them, and so
+/// end the run.
+fn preceding_comments_start(text: &str, mut line_start: usize, max_indent: usize) -> usize {
+    while text[..line_start].ends_with('\n') {
+        let prev_line_start = text[..line_start - 1]
+            .rfind('\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let prev_line = &text[prev_line_start..line_start];
+        let content = prev_line.trim_start();
+        let indent = prev_line.len() - content.len();
+
+        if content.is_empty() || (content.starts_with('#') && indent <= max_indent) {
+            line_start = prev_line_start;
+        } else {
+            break;
+        }
+    }
+
+    line_start
+}
+
+/// Reads the record of a quotation whose text is to be loaded lazily from the given byte range of
+/// its quotation file.
+fn read_lazy_quotation_record(
+    file: &QuotationFileMetadata,
+    range: Range<usize>,
+) -> Result<QuotationIR> {
+    let mut reader = File::open(&file.path)?;
+
+    if reader.metadata()?.modified().ok() != file.modified {
+        return Err(format!(
+            "The quotation file {:?} has been modified since it was loaded, so I cannot read \
+             quotations from it until the quotation database has been reloaded.",
+            file.name,
+        )
+        .into());
+    }
+
+    let mut record = String::with_capacity(range.len());
+
+    reader.seek(SeekFrom::Start(range.start as u64))?;
+    reader
+        .take(range.len() as u64)
+        .read_to_string(&mut record)?;
+
+    // The record is an element of a block sequence, so, on its own, it forms a sequence of one.
+    let mut records: SmallVec<[QuotationIR; 1]> = serde_yaml::from_str(&record)?;
+
+    match (records.pop(), records.is_empty()) {
+        (Some(record), true) => Ok(record),
+        _ => Err(format!(
+            "Bytes {}–{} of the quotation file {:?} were expected to hold a single quotation \
+             record, but they do not.",
+            range.start, range.end, file.name,
+        )
+        .into()),
+    }
+}
+
 fn read_qdb() -> Result<impl Deref<Target = QuotationDatabase>> {
     match QDB.read() {
         Ok(guard) => Ok(guard),
@@ -1109,7 +2326,25 @@ fn read_qdb() -> Result<impl Deref<Target = QuotationDatabase>> {
     }
 }
 
+fn write_qdb() -> Result<RwLockWriteGuard<'static, QuotationDatabase>> {
+    match QDB.write() {
+        Ok(guard) => Ok(guard),
+        Err(_guard) => Err(ErrorKind::LockPoisoned("quotation database".into()).into()),
+    }
+}
+
+fn new_file_permissions_cache() -> ClockProCache<FilePermissionsKey, SmallBitVec> {
+    ClockProCache::new(FILE_PERMISSIONS_CACHE_CAPACITY)
+        .expect("`FILE_PERMISSIONS_CACHE_CAPACITY` is too small?!")
+}
+
 fn on_load(state: &State) -> Result<()> {
+    load_qdb(state, &mut *write_qdb()?)
+}
+
+/// Loads the quotation database from the module data directory into `old_qdb`, which should be
+/// the contents of `QDB`, locked for writing by the caller.
+fn load_qdb(state: &State, old_qdb: &mut QuotationDatabase) -> Result<()> {
     let data_path = state.module_data_path()?.join("quote");
 
     if !data_path.exists() {
@@ -1117,10 +2352,6 @@ fn on_load(state: &State) -> Result<()> {
         return Ok(());
     }
 
-    let mut old_qdb = match QDB.write() {
-        Ok(guard) => guard,
-        Err(_guard) => return Err(ErrorKind::LockPoisoned("quotation database".into()).into()),
-    };
     let mut new_qdb = QuotationDatabase::new();
 
     // Reuse any memory already allocated for an old quotation database.
@@ -1144,17 +2375,73 @@ fn on_load(state: &State) -> Result<()> {
         let path = entry.path();
         trace!("Loading quotation file: {}", path.display());
 
+        let file_text = fs::read_to_string(path)?;
+
         let QuotationFileIR {
             channels: file_channels_regex,
             format: file_default_format,
             anti_ping_tactic: file_default_anti_ping_tactic,
+            weight: file_default_weight,
             quotations: deserialized_quotations,
-        } = serde_yaml::from_reader(BufReader::new(File::open(path)?))?;
+            lazy,
+        } = serde_yaml::from_str(&file_text)?;
+        let deserialized_quotations = deserialized_quotations.unwrap_or_default();
+
+        let weights = iter::once(file_default_weight).chain(
+            deserialized_quotations
+                .iter()
+                .filter_map(|quotation| quotation.weight),
+        );
+
+        for weight in weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!(
+                    "The quotation file {:?} specifies the weight {}, but weights must be finite \
+                     and non-negative.",
+                    entry.file_name(),
+                    weight,
+                )
+                .into());
+            }
+
+            if weight != DEFAULT_QUOTATION_WEIGHT {
+                new_qdb.weighted = true;
+            }
+        }
+
+        // If the file's quotations' texts are to be loaded lazily, find where in the file each
+        // quotation's record is, so that its text can be read from there when needed.
+        let text_locations = if lazy {
+            match quotation_record_byte_ranges(&file_text)? {
+                Some(ref ranges) if ranges.len() == deserialized_quotations.len() => {
+                    Some(ranges.clone())
+                }
+                _ => {
+                    return Err(format!(
+                        "The quotations in the quotation file {:?} cannot be loaded lazily, \
+                         because its `quotations` field is not a block sequence with each \
+                         quotation record starting on a line of its own.",
+                        entry.file_name(),
+                    )
+                    .into())
+                }
+            }
+        } else {
+            None
+        };
+
+        drop(file_text);
 
         let file_id = QuotationFileId(new_qdb.files.len());
 
         let file_metadata = QuotationFileMetadata {
             name: entry.file_name().to_string_lossy().into_owned(),
+            path: path.to_owned(),
+            modified: if lazy {
+                fs::metadata(path)?.modified().ok()
+            } else {
+                None
+            },
             file_id,
             channels_regex: file_channels_regex,
             quotation_count: deserialized_quotations.len(),
@@ -1178,42 +2465,60 @@ fn on_load(state: &State) -> Result<()> {
 
         new_qdb
             .quotations
-            .extend(
-                deserialized_quotations
-                    .into_iter()
-                    .map(|deserialized_quotation| {
-                        let QuotationIR {
-                            format,
-                            text,
-                            mut tags,
-                            url,
-                            anti_ping_tactic,
-                        } = deserialized_quotation;
-
-                        Quotation {
-                            id: {
-                                let id = next_quotation_id;
-                                // We already have checked for possible overflow, above.
-                                next_quotation_id += 1;
-                                QuotationId(id)
-                            },
-                            file_id,
-                            format: format.unwrap_or(file_default_format),
-                            text,
-                            tags: {
-                                tags.sort_unstable();
-                                tags
-                            },
-                            url,
-                            anti_ping_tactic: anti_ping_tactic
-                                .unwrap_or(file_default_anti_ping_tactic),
-                        }
-                    }),
-            );
+            .extend(deserialized_quotations.into_iter().enumerate().map(
+                |(index_in_file, deserialized_quotation)| {
+                    let QuotationIR {
+                        format,
+                        text,
+                        variants,
+                        mut tags,
+                        url,
+                        anti_ping_tactic,
+                        weight,
+                    } = deserialized_quotation;
+
+                    let text_location = text_locations
+                        .as_ref()
+                        .map(|ranges| ranges[index_in_file].clone());
+
+                    Quotation {
+                        id: {
+                            let id = next_quotation_id;
+                            // We already have checked for possible overflow, above.
+                            next_quotation_id += 1;
+                            QuotationId(id)
+                        },
+                        file_id,
+                        format: format.unwrap_or(file_default_format),
+                        text: if text_location.is_some() {
+                            String::new()
+                        } else {
+                            text
+                        },
+                        variants: if text_location.is_some() {
+                            Vec::new()
+                        } else {
+                            variants
+                        },
+                        tags: {
+                            tags.sort_unstable();
+                            tags
+                        },
+                        url,
+                        anti_ping_tactic: anti_ping_tactic.unwrap_or(file_default_anti_ping_tactic),
+                        weight: weight.unwrap_or(file_default_weight),
+                        text_location,
+                    }
+                },
+            ));
     }
 
     *old_qdb = new_qdb;
 
+    // The cached file permissions are indexed by the old database's files, so discard them.
+    *FILE_PERMISSIONS_CACHE.lock_clean("the cache of quotation file permissions")? =
+        new_file_permissions_cache();
+
     debug!("Finished loading quotation database.");
 
     Ok(())
@@ -1273,6 +2578,9 @@ impl qc::Arbitrary for Quotation {
                 .ok()
                 .map(Serde),
             anti_ping_tactic: qc::Arbitrary::arbitrary(g),
+            variants: Vec::new(),
+            weight: DEFAULT_QUOTATION_WEIGHT,
+            text_location: None,
         }
     }
 
@@ -1358,6 +2666,26 @@ impl qc::Arbitrary for AntiPingTactic {
 mod tests {
     use super::*;
     use quickcheck::TestResult;
+    use rand;
+    use std::env;
+    use std::process;
+
+    /// Returns a quotation with the given format and text, and otherwise with ID 0 and default
+    /// fields.
+    fn quotation(format: QuotationFormat, text: &str) -> Quotation {
+        Quotation {
+            id: QuotationId(0),
+            file_id: QuotationFileId(0),
+            format,
+            text: text.into(),
+            tags: Default::default(),
+            url: Default::default(),
+            anti_ping_tactic: AntiPingTactic::None,
+            variants: Vec::new(),
+            weight: DEFAULT_QUOTATION_WEIGHT,
+            text_location: None,
+        }
+    }
 
     // To run rustfmt on this code, temporarily change the `quickcheck! {...}` to `mod qc {...}`.
     // Beware, however, of rustfmt adding trailing commas, which `quickcheck!` doesn't accept.
@@ -1398,6 +2726,9 @@ mod tests {
                 tags: tags.into_iter().map(Into::into).collect(),
                 url: Default::default(),
                 anti_ping_tactic,
+                variants: Vec::new(),
+                weight: DEFAULT_QUOTATION_WEIGHT,
+                text_location: None,
             };
             let left_angle_bracket_qty_after_trimming: usize = chat_lines_stripped(&quotation)
                 .map(|s| s.matches('<').count())
@@ -1425,6 +2756,9 @@ mod tests {
                 tags: tags.into_iter().map(Into::into).collect(),
                 url: Default::default(),
                 anti_ping_tactic,
+                variants: Vec::new(),
+                weight: DEFAULT_QUOTATION_WEIGHT,
+                text_location: None,
             };
             let arg = Default::default();
             let mut actual_len = 0;
@@ -1455,6 +2789,9 @@ mod tests {
                 tags: tags.into_iter().map(Into::into).collect(),
                 url: Default::default(),
                 anti_ping_tactic,
+                variants: Vec::new(),
+                weight: DEFAULT_QUOTATION_WEIGHT,
+                text_location: None,
             };
             let rendered_text = match render_quotation(&Default::default(), &quotation, &[]) {
                 Ok(s) => s,
@@ -1492,6 +2829,9 @@ mod tests {
                 url: Default::default(),
                 anti_ping_tactic,
                 text,
+                variants: Vec::new(),
+                weight: DEFAULT_QUOTATION_WEIGHT,
+                text_location: None,
             };
 
             let mut lines = chat_lines_stripped(&quotation);
@@ -1556,6 +2896,9 @@ mod tests {
                 url: Default::default(),
                 anti_ping_tactic,
                 text,
+                variants: Vec::new(),
+                weight: DEFAULT_QUOTATION_WEIGHT,
+                text_location: None,
             };
 
             let mut lines = chat_lines_stripped(&quotation);
@@ -1604,6 +2947,9 @@ mod tests {
                 url: Default::default(),
                 anti_ping_tactic,
                 text,
+                variants: Vec::new(),
+                weight: DEFAULT_QUOTATION_WEIGHT,
+                text_location: None,
             };
 
             let rendered_text = match render_quotation(&Default::default(), &quotation, &[]) {
@@ -1624,4 +2970,306 @@ mod tests {
             TestResult::passed()
         }
     }
+
+    #[test]
+    fn appended_quotations_load() {
+        let mut header = BTreeMap::new();
+        header.insert("channels", "freenode/#bots");
+
+        let mut text = to_yaml_fragment(&header).unwrap();
+        text.push_str("quotations:\n");
+
+        for (quotation_text, format) in &[
+            ("<c74d> hello\n<c74d> 'bye: now\n", None),
+            ("# not a comment", Some(QuotationFormat::Plain)),
+        ] {
+            let quotation = QuotationIR {
+                format: *format,
+                text: quotation_text.to_string(),
+                tags: vec!["greeting".into()].into(),
+                variants: Vec::new(),
+                url: None,
+                anti_ping_tactic: None,
+                weight: None,
+            };
+            text.push_str(&to_yaml_fragment(&[quotation]).unwrap());
+        }
+
+        let file: QuotationFileIR = serde_yaml::from_str(&text).unwrap();
+        let quotations = file.quotations.unwrap();
+
+        assert!(file.channels.is_match("freenode/#bots"));
+        assert_eq!(quotations.len(), 2);
+        assert_eq!(quotations[0].text, "<c74d> hello\n<c74d> 'bye: now\n");
+        assert_eq!(quotations[0].format, None);
+        assert_eq!(&*quotations[0].tags, &[DefaultAtom::from("greeting")]);
+        assert_eq!(quotations[1].text, "# not a comment");
+        assert_eq!(quotations[1].format, Some(QuotationFormat::Plain));
+    }
+
+    #[test]
+    fn quotation_record_removal() {
+        let text = "channels: '#bots'\n\
+                    quotations:\n\
+                    - text: one\n\
+                    \x20 tags: [a]\n\
+                    \x20 # A comment within the first quotation.\n\
+                    # A comment about the second quotation.\n\
+                    -\n\
+                    \x20 text: |\n\
+                    \x20   two\n\
+                    \x20   lines\n\
+                    - {text: three}\n\
+                    format: plain\n";
+
+        let ranges = quotation_record_byte_ranges(text).unwrap().unwrap();
+        let remove = |index: usize| {
+            ranges
+                .get(index)
+                .map(|range| [&text[..range.start], &text[range.end..]].concat())
+        };
+
+        assert_eq!(
+            remove(0).unwrap(),
+            "channels: '#bots'\n\
+             quotations:\n\
+             # A comment about the second quotation.\n\
+             -\n\
+             \x20 text: |\n\
+             \x20   two\n\
+             \x20   lines\n\
+             - {text: three}\n\
+             format: plain\n"
+        );
+        assert_eq!(
+            remove(1).unwrap(),
+            "channels: '#bots'\n\
+             quotations:\n\
+             - text: one\n\
+             \x20 tags: [a]\n\
+             \x20 # A comment within the first quotation.\n\
+             - {text: three}\n\
+             format: plain\n"
+        );
+        assert_eq!(
+            remove(2).unwrap(),
+            "channels: '#bots'\n\
+             quotations:\n\
+             - text: one\n\
+             \x20 tags: [a]\n\
+             \x20 # A comment within the first quotation.\n\
+             # A comment about the second quotation.\n\
+             -\n\
+             \x20 text: |\n\
+             \x20   two\n\
+             \x20   lines\n\
+             format: plain\n"
+        );
+        assert_eq!(remove(3), None);
+
+        let flow_text = "channels: '#bots'\nquotations: [{text: one}, {text: two}]\n";
+
+        assert_eq!(quotation_record_byte_ranges(flow_text).unwrap(), None);
+    }
+
+    #[test]
+    fn lazy_quotation_texts() {
+        let text = "channels: '#bots'\n\
+                    lazy: true\n\
+                    quotations:\n\
+                    \x20 - text: “one”\n\
+                    \x20 - text: |\n\
+                    \x20     two\n\
+                    \x20   tags: [b]\n\
+                    \x20 - text: three\n";
+        let path = env::temp_dir().join(format!("irc-bot-quote-test-{}.yaml", process::id()));
+
+        fs::write(&path, text).unwrap();
+
+        let file_ir: QuotationFileIR = serde_yaml::from_str(text).unwrap();
+        let ranges = quotation_record_byte_ranges(text).unwrap().unwrap();
+        let file = QuotationFileMetadata {
+            name: "test".into(),
+            path: path.clone(),
+            modified: fs::metadata(&path).unwrap().modified().ok(),
+            file_id: QuotationFileId(0),
+            channels_regex: file_ir.channels,
+            quotation_count: ranges.len(),
+        };
+        let texts = ranges
+            .into_iter()
+            .map(|range| read_lazy_quotation_record(&file, range).map(|record| record.text))
+            .collect::<Result<Vec<_>>>();
+
+        fs::remove_file(&path).unwrap();
+
+        assert!(file_ir.lazy);
+        assert_eq!(texts.unwrap(), ["“one”", "two\n", "three"]);
+    }
+
+    #[test]
+    fn file_lookup_by_name() {
+        let mut qdb = QuotationDatabase::new();
+
+        for (idx, name) in ["classics.yaml", "secret.yaml"].iter().enumerate() {
+            let file_ir: QuotationFileIR =
+                serde_yaml::from_str("{channels: '#bots', quotations: []}").unwrap();
+
+            qdb.files.push(QuotationFileMetadata {
+                name: name.to_string(),
+                path: name.into(),
+                modified: None,
+                file_id: QuotationFileId(idx),
+                channels_regex: file_ir.channels,
+                quotation_count: 0,
+            });
+        }
+
+        let mut file_permissions = SmallBitVec::from_elem(2, true);
+        file_permissions.set(1, false);
+
+        let lookup = |name| get_file_id_by_user_specified_name(&qdb, &file_permissions, name);
+
+        assert_eq!(lookup("classics.yaml").unwrap(), QuotationFileId(0));
+        assert!(lookup("secret.yaml").is_err());
+        assert!(lookup("missing.yaml").is_err());
+    }
+
+    #[test]
+    fn each_regex_must_match() {
+        let quotation = Quotation {
+            tags: vec!["fruit".into()].into(),
+            ..quotation(QuotationFormat::Chat, "<a> blue\n<b> berries\n")
+        };
+        let matches = |regexes: &[&str]| {
+            let params = QuoteParams {
+                regexes: regex::RegexSet::new(regexes).unwrap(),
+                ..Default::default()
+            };
+            quotation_matches_query_params(&params, &quotation).unwrap()
+        };
+
+        assert!(matches(&[]));
+        assert!(matches(&["blue", "berr(y|ies)"]));
+        assert!(matches(&["^<b>", "fru"]));
+        assert!(!matches(&["blue", "grape"]));
+        assert!(!matches(&["blue berries"]));
+    }
+
+    #[test]
+    fn excluded_search_terms() {
+        let quotation = Quotation {
+            tags: vec!["vegetable".into()].into(),
+            ..quotation(QuotationFormat::Chat, "<a> a rabbit\n<b> with a carrot\n")
+        };
+        let matches = |regexes: &[&str], literals: &[&'static str], tags: &[&'static str]| {
+            let params = QuoteParams {
+                literals: vec!["rabbit".into()].into(),
+                excluded_regexes: regex::RegexSet::new(regexes).unwrap(),
+                excluded_literals: literals.iter().map(|&s| s.into()).collect(),
+                excluded_tags: tags.iter().map(|&s| s.into()).collect(),
+                ..Default::default()
+            };
+            quotation_matches_query_params(&params, &quotation).unwrap()
+        };
+
+        assert!(matches(&[], &[], &[]));
+        assert!(matches(&["^<c>"], &["Carrot", "potato"], &["fruit"]));
+        assert!(!matches(&[], &["carrot"], &[]));
+        assert!(!matches(&[], &["veg"], &[]));
+        assert!(!matches(&["c.rrot"], &[], &[]));
+        assert!(!matches(&[], &[], &["fruit", "vegetable"]));
+    }
+
+    #[test]
+    fn variant_selection() {
+        let quotation = Quotation {
+            variants: vec!["bonjour".into(), "hallo".into()],
+            ..quotation(QuotationFormat::Plain, "hello")
+        };
+        let quotation = Cow::Borrowed(&quotation);
+        let matching_variants = |string: &str| {
+            let params = QuoteParams {
+                literals: iter::once(string.into()).collect(),
+                ..Default::default()
+            };
+            (0..=quotation.variants.len())
+                .filter(|&id| {
+                    quotation_matches_query_params(&params, &quotation_variant(&quotation, id))
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(quotation_variant(&quotation, 2).text, "hallo");
+        assert_eq!(quotation_variant(&quotation, 2).id, QuotationId(0));
+        assert_eq!(matching_variants("hello"), [0]);
+        assert_eq!(matching_variants("jour"), [1]);
+        assert_eq!(matching_variants("ll"), [0, 2]);
+        assert!(matching_variants("hola").is_empty());
+    }
+
+    #[test]
+    fn weighted_selection() {
+        let quotations = [0.0, 1.0, 9.0]
+            .iter()
+            .enumerate()
+            .map(|(index, &weight)| Quotation {
+                id: QuotationId(index),
+                weight,
+                ..quotation(QuotationFormat::Plain, "")
+            })
+            .collect::<Vec<_>>();
+        let mut rng = rand::thread_rng();
+        let mut first_counts = [0; 3];
+
+        for _ in 0..10000 {
+            let order = weighted_random_order(&quotations, &mut rng);
+            assert_eq!(order.len(), 2);
+            first_counts[order[0].id.array_index()] += 1;
+        }
+
+        assert_eq!(first_counts[0], 0);
+        assert!(first_counts[1] > 700 && first_counts[1] < 1300);
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_contains("That was funny.", "funnny"));
+        assert!(fuzzy_contains("That was funny.", "funy"));
+        assert!(fuzzy_contains("That was funny.", "fonny"));
+        assert!(fuzzy_contains("That was funny.", "was fnuny"));
+        assert!(!fuzzy_contains("That was funny.", "fnuny"));
+        assert!(!fuzzy_contains("That was funny.", "fun!!"));
+        assert!(!fuzzy_contains("That was funny.", "fon"));
+        assert!(fuzzy_contains("anything", ""));
+    }
+
+    #[test]
+    fn format_and_tactic_names() {
+        assert_eq!(
+            serde_names(QuotationFormat::iter()).unwrap(),
+            ["chat", "plain"]
+        );
+        assert_eq!(
+            serde_names(AntiPingTactic::iter()).unwrap(),
+            ["munge", "eschew", "none"]
+        );
+    }
+
+    #[test]
+    fn chat_line_speakers() {
+        let text = "12:00 <c74d> hello\n\
+                    12:01 <@Op> hi\n\
+                    12:02 * c74d waves\n\
+                    12:03 -!- someone has joined\n";
+        let quotation = quotation(QuotationFormat::Chat, text);
+
+        assert_eq!(
+            chat_lines_stripped(&quotation)
+                .filter_map(chat_line_speaker)
+                .collect::<Vec<_>>(),
+            ["c74d", "Op", "c74d"]
+        );
+    }
 }
@@ -0,0 +1,244 @@
+use core::BotCmdAuthLvl as Auth;
+use core::*;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use util::irc::NickName;
+use util::lock::MutexExt;
+use util::to_cow_owned;
+use util::yaml::scalar_to_str;
+use util::yaml::str::YAML_STR_IN;
+use util::yaml::str::YAML_STR_MSG;
+use util::yaml::FW_SYNTAX_CHECK_FAIL;
+use yaml_rust::Yaml;
+
+/// The longest delay after which a reminder may be requested. Reminders are not saved across
+/// restarts of the bot, so longer delays would be unlikely to be honored anyway.
+const MAX_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// The greatest number of reminders that any one user may have pending
+const MAX_REMINDERS_PER_USER: usize = 5;
+
+/// The greatest number of reminders that may be pending at once, across all servers
+const MAX_REMINDERS: usize = 1000;
+
+lazy_static! {
+    /// The times at which pending reminders are due, by the server and nickname of the user who
+    /// requested them
+    static ref DUE_TIMES: Mutex<BTreeMap<(ServerId, NickName), Vec<Instant>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// This module provides the command `remind`, with which a user may have the bot send the user a
+/// message after a given delay.
+///
+/// The delay is given as a sequence of one or more numbers each followed by a unit, which may be
+/// `s` (seconds), `m` (minutes), `h` (hours), `d` (days), or `w` (weeks) — e.g., `90s`, `10m`, or
+/// `1h30m`. The reminder will be sent where it was requested, i.e., in the same channel or in
+/// one-to-one messaging. At most five reminders may be pending for any one user. Reminders are held
+/// only in memory and will be lost if the bot is restarted or loses its connection to the relevant
+/// server.
+pub fn mk() -> Module {
+    mk_module("remind")
+        .command(
+            "remind",
+            "{in: duration, msg: message}",
+            "Have the bot send you the given message after the given delay, such as `10m` or \
+             `1h30m`.",
+            Auth::Public,
+            Box::new(remind),
+            &[],
+        )
+        .end()
+}
+
+fn remind(
+    HandlerContext {
+        state,
+        request_origin,
+        invoker,
+        ..
+    }: HandlerContext,
+    arg: &Yaml,
+) -> Result<BotCmdResult> {
+    let arg = arg.as_hash().expect(FW_SYNTAX_CHECK_FAIL);
+
+    let delay_str = scalar_to_str(
+        arg.get(&YAML_STR_IN).expect(FW_SYNTAX_CHECK_FAIL),
+        Cow::Borrowed,
+        "the value of the parameter `in`",
+    )?;
+
+    let msg = scalar_to_str(
+        arg.get(&YAML_STR_MSG).expect(FW_SYNTAX_CHECK_FAIL),
+        to_cow_owned,
+        "the value of the parameter `msg`",
+    )?;
+
+    let delay = match parse_delay(&delay_str) {
+        Some(d) if d.as_secs() <= MAX_DELAY_SECS => d,
+        Some(_) => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!(
+                    "I can't set reminders for more than {} days from now.",
+                    MAX_DELAY_SECS / (24 * 60 * 60)
+                )
+                .into(),
+            ));
+        }
+        None => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!(
+                    "I couldn't understand the delay {:?}. Please give it as numbers each \
+                     followed by one of the units `s`, `m`, `h`, `d`, or `w`, such as `1h30m`.",
+                    delay_str
+                )
+                .into(),
+            ));
+        }
+    };
+
+    // Check now that the reminder will have somewhere to go, rather than finding out only once
+    // it's due.
+    let dest = state.guess_reply_dest(&MsgMetadata {
+        dest: request_origin,
+        prefix: invoker,
+    })?;
+
+    let requester = match invoker.nick.map(NickName::new) {
+        Some(Ok(nick)) => nick,
+        _ => {
+            return Ok(BotCmdResult::UserErrMsg(
+                "I can't tell who you are, so I can't remind you of anything.".into(),
+            ))
+        }
+    };
+
+    {
+        let mut due_times = DUE_TIMES.lock_clean("the due times of pending reminders")?;
+        let now = Instant::now();
+
+        // Forget the reminders that have come due.
+        for times in due_times.values_mut() {
+            times.retain(|&time| time > now);
+        }
+        due_times.retain(|_, times| !times.is_empty());
+
+        if due_times.values().map(Vec::len).sum::<usize>() >= MAX_REMINDERS {
+            return Ok(BotCmdResult::UserErrMsg(
+                "I'm holding too many reminders already; please try again later.".into(),
+            ));
+        }
+
+        let times = due_times
+            .entry((request_origin.server_id, requester))
+            .or_insert_with(Vec::new);
+
+        if times.len() >= MAX_REMINDERS_PER_USER {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!(
+                    "You already have {} reminders pending, which is as many as I can hold for \
+                     you.",
+                    times.len()
+                )
+                .into(),
+            ));
+        }
+
+        times.push(now + delay);
+    }
+
+    debug!(
+        "Scheduling reminder for {:?} to {} in {:?}: {:?}",
+        invoker,
+        state.msg_dest_dbg_string(dest),
+        delay,
+        msg
+    );
+
+    Ok(Reaction::Delay(delay, Box::new(Reaction::Reply(msg))).into())
+}
+
+/// Parses a delay such as `10m` or `1h30m`, returning `None` if the string is malformed or the
+/// delay it describes is too large to represent.
+fn parse_delay(s: &str) -> Option<Duration> {
+    let mut total_secs = 0u64;
+    let mut rest = s.trim();
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let digits_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+
+        if digits_len == 0 {
+            return None;
+        }
+
+        let n: u64 = rest[..digits_len].parse().ok()?;
+        rest = &rest[digits_len..];
+
+        let unit_secs = match rest.chars().next()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        rest = &rest[1..];
+
+        total_secs = total_secs.checked_add(n.checked_mul(unit_secs)?)?;
+    }
+
+    Some(Duration::from_secs(total_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::test_harness::TestBot;
+    use core::test_harness::MINIMAL_CONFIG;
+    use modules;
+
+    #[test]
+    fn reminders_per_user() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default(), modules::remind()]);
+        let remind = || {
+            bot.feed(":carol!carol@example.org PRIVMSG #test :test-bot: remind {in: 1h, msg: x}")
+        };
+
+        for _ in 0..MAX_REMINDERS_PER_USER {
+            assert!(remind().is_empty());
+        }
+
+        assert_eq!(
+            remind(),
+            [
+                "PRIVMSG #test :User error: You already have 5 reminders pending, which is as \
+                 many as I can hold for you."
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_delay_examples() {
+        assert_eq!(parse_delay("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_delay("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_delay("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_delay(" 2d "), Some(Duration::from_secs(172_800)));
+        assert_eq!(parse_delay("1w"), Some(Duration::from_secs(604_800)));
+        assert_eq!(parse_delay(""), None);
+        assert_eq!(parse_delay("5"), None);
+        assert_eq!(parse_delay("m"), None);
+        assert_eq!(parse_delay("5x"), None);
+        assert_eq!(parse_delay("1h 30m"), None);
+        assert_eq!(parse_delay("99999999999999999999s"), None);
+        assert_eq!(parse_delay("18446744073709551615w"), None);
+    }
+}
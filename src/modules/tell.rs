@@ -0,0 +1,186 @@
+use core::BotCmdAuthLvl as Auth;
+use core::*;
+use regex::Captures;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use util::fmt::FmtDuration;
+use util::irc::NickName;
+use util::lock::MutexExt;
+use util::yaml::scalar_to_str;
+use yaml_rust::Yaml;
+
+/// The greatest number of messages that may be waiting for any one user
+const MAX_TELLS_PER_RECIPIENT: usize = 5;
+
+/// The greatest number of users for whom messages may be waiting at once, across all servers
+const MAX_RECIPIENTS: usize = 1000;
+
+lazy_static! {
+    static ref PENDING_TELLS: Mutex<BTreeMap<(ServerId, NickName), Vec<Tell>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// This module provides the command `tell`, with which a user may leave a message for another
+/// user, which the bot will deliver when the recipient next speaks in a channel that the bot is in
+/// or to the bot directly, on the same server.
+///
+/// The message is delivered where the recipient speaks, addressed to the recipient, noting who left
+/// it and how long ago. At most five messages may be waiting for any one user. Messages are held
+/// only in memory and will be lost if the bot is restarted.
+pub fn mk() -> Module {
+    mk_module("tell")
+        .command(
+            "tell",
+            "<nickname> <message>",
+            "Have the bot pass the given message on to the user with the given nickname when that \
+             user next speaks.",
+            Auth::Public,
+            Box::new(tell),
+            &[],
+        )
+        .trigger(
+            "tell-delivery",
+            "(?s).*",
+            "Deliver any messages left with `tell` for the user who is speaking.",
+            TriggerPriority::Maximum,
+            Box::new(deliver),
+            &[TriggerAttr::AlwaysWatching],
+        )
+        .end()
+}
+
+#[derive(Debug)]
+struct Tell {
+    sender: String,
+
+    msg: String,
+
+    time: Instant,
+}
+
+fn tell(
+    HandlerContext {
+        state,
+        request_origin,
+        invoker,
+        ..
+    }: HandlerContext,
+    arg: &Yaml,
+) -> Result<BotCmdResult> {
+    let arg = scalar_to_str(arg, Cow::Borrowed, "the argument")?;
+
+    let (recipient, msg) = match arg.trim().find(char::is_whitespace) {
+        Some(idx) => (&arg.trim()[..idx], arg.trim()[idx..].trim()),
+        None => return Ok(BotCmdResult::SyntaxErr),
+    };
+
+    let recipient = match NickName::new(recipient) {
+        Ok(nick) => nick,
+        Err(_) => {
+            return Ok(BotCmdResult::UserErrMsg(
+                format!("{:?} is not a valid nickname.", recipient).into(),
+            ))
+        }
+    };
+
+    let sender = invoker.nick.unwrap_or("someone");
+
+    if state.is_own_nick(request_origin.server_id, &recipient)? {
+        return Ok(BotCmdResult::UserErrMsg("I'm right here.".into()));
+    }
+
+    if NickName::new(sender).ok().as_ref() == Some(&recipient) {
+        return Ok(BotCmdResult::UserErrMsg(
+            "You'll have to tell yourself that.".into(),
+        ));
+    }
+
+    let mut pending = PENDING_TELLS.lock_clean("the pending `tell` messages")?;
+    let key = (request_origin.server_id, recipient);
+
+    if !pending.contains_key(&key) && pending.len() >= MAX_RECIPIENTS {
+        return Ok(BotCmdResult::UserErrMsg(
+            "I'm holding too many messages already; please try again later.".into(),
+        ));
+    }
+
+    let tells = pending.entry(key).or_insert_with(Vec::new);
+
+    if tells.len() >= MAX_TELLS_PER_RECIPIENT {
+        return Ok(BotCmdResult::UserErrMsg(
+            "That user already has as many messages waiting as I can hold.".into(),
+        ));
+    }
+
+    tells.push(Tell {
+        sender: sender.to_owned(),
+        msg: msg.to_owned(),
+        time: Instant::now(),
+    });
+
+    Ok(Reaction::Reply("I'll pass that on when they next speak.".into()).into())
+}
+
+fn deliver(
+    HandlerContext {
+        request_origin,
+        invoker,
+        ..
+    }: HandlerContext,
+    _: Captures,
+) -> Result<Reaction> {
+    let recipient = match invoker.nick.map(NickName::new) {
+        Some(Ok(nick)) => nick,
+        _ => return Ok(Reaction::None),
+    };
+
+    let tells = match PENDING_TELLS
+        .lock_clean("the pending `tell` messages")?
+        .remove(&(request_origin.server_id, recipient))
+    {
+        Some(tells) => tells,
+        None => return Ok(Reaction::None),
+    };
+
+    Ok(Reaction::Replies(
+        tells
+            .into_iter()
+            .map(|tell| {
+                format!(
+                    "{} left a message for you {} ago: {}",
+                    tell.sender,
+                    FmtDuration(tell.time.elapsed()),
+                    tell.msg
+                )
+                .into()
+            })
+            .collect::<Vec<_>>()
+            .into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::test_harness::TestBot;
+    use core::test_harness::MINIMAL_CONFIG;
+    use modules;
+
+    #[test]
+    fn tell_is_delivered_when_recipient_speaks() {
+        let bot = TestBot::new(MINIMAL_CONFIG, vec![modules::default(), modules::tell()]);
+
+        assert_eq!(
+            bot.feed(":erin!erin@example.org PRIVMSG #test :test-bot: tell Frank the kettle's on"),
+            ["PRIVMSG #test :erin: I'll pass that on when they next speak."]
+        );
+        assert_eq!(
+            bot.feed(":frank!frank@example.org PRIVMSG #test :morning"),
+            ["PRIVMSG #test :frank: erin left a message for you 0s ago: the kettle's on"]
+        );
+        assert!(bot
+            .feed(":frank!frank@example.org PRIVMSG #test :morning again")
+            .is_empty());
+    }
+}
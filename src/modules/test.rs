@@ -1,6 +1,10 @@
 use core::BotCmdAuthLvl as Auth;
 use core::*;
+use std::borrow::Cow;
 use std::mem;
+use std::thread;
+use std::time::Duration;
+use util::yaml::any_to_str;
 use yaml_rust::Yaml;
 
 pub fn mk() -> Module {
@@ -9,6 +13,16 @@ pub fn mk() -> Module {
             trace!("Hello from the `test` module's `on_load` function!");
             Ok(())
         }))
+        .command(
+            "echo-args",
+            "'...'",
+            "Have the bot reply with the argument given to this command, as the bot parsed it, \
+             re-serialized as YAML. This can be useful for seeing how the bot understands a \
+             command's argument.",
+            Auth::Public,
+            Box::new(echo_args),
+            &[],
+        )
         .command(
             "test-line-wrap",
             "",
@@ -17,6 +31,15 @@ pub fn mk() -> Module {
             Box::new(test_line_wrap),
             &[],
         )
+        .command(
+            "test-defer",
+            "",
+            "Have the bot acknowledge this command at once and reply again a few seconds later, to \
+             test the bot framework's handling of deferred command results.",
+            Auth::Admin,
+            Box::new(test_defer),
+            &[],
+        )
         .command(
             "test-error-handling",
             "",
@@ -62,10 +85,26 @@ const LOREM_IPSUM_TEXT: &'static str =
      sagittis in. Mauris a lectus nec ligula eleifend rutrum. Class aptent taciti sociosqu ad \
      litora torquent per conubia massa nunc.";
 
+fn echo_args(_: HandlerContext, arg: &Yaml) -> Result<Reaction> {
+    Ok(Reaction::Reply(
+        any_to_str(arg, Cow::Borrowed)?.into_owned().into(),
+    ))
+}
+
 fn test_line_wrap(_: HandlerContext, _: &Yaml) -> BotCmdResult {
     BotCmdResult::Ok(Reaction::Reply(LOREM_IPSUM_TEXT.into()))
 }
 
+fn test_defer(_: HandlerContext, _: &Yaml) -> BotCmdResult {
+    BotCmdResult::Defer(
+        Reaction::Reply("Working on it....".into()),
+        Box::new(|_: &State| {
+            thread::sleep(Duration::from_secs(3));
+            Reaction::Reply("Done.".into())
+        }),
+    )
+}
+
 fn test_error_handling(_: HandlerContext, _: &Yaml) -> BotCmdResult {
     BotCmdResult::BotErrMsg("An error for testing purposes.".into())
 }
@@ -0,0 +1,733 @@
+use core::*;
+use crossbeam_channel;
+use irc::client::data::User as AatxeUser;
+use irc::client::prelude::Client as AatxeClient;
+use native_tls::TlsConnector;
+use regex;
+use regex::Captures;
+use serde_yaml;
+use std::borrow::Cow;
+use std::fs;
+use std::io::ErrorKind as IoErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use url::Position;
+use url::Url;
+use util;
+use util::lock::ReadLockExt;
+use util::lock::WriteLockExt;
+use util::regex::config as rx_cfg;
+use util::regex::Regex;
+use util::STATIC_REGEX_PARSE_ERR_MSG;
+
+/// The name of the module's configuration file, in the module data directory
+const CONFIG_FILE_NAME: &str = "url-title.yaml";
+
+/// The greatest number of URLs in a single message whose titles are fetched
+const MAX_URLS_PER_MSG: usize = 3;
+
+/// The greatest number of HTTP redirections followed in fetching a single URL
+const MAX_REDIRECTS: usize = 3;
+
+/// The greatest number of messages whose linked pages' titles may be being fetched at once; the
+/// URLs in further messages are ignored until one of those fetches has finished.
+const MAX_CONCURRENT_FETCHES: usize = 4;
+
+/// The greatest length, in characters, of a title posted by the bot; longer titles are truncated.
+const MAX_TITLE_LEN: usize = 200;
+
+/// The size of the buffer into which a response is read
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Characters that are taken to be punctuation of the surrounding text, rather than part of a URL,
+/// if they come at the end of the URL
+const URL_TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', '\'', '"', ')'];
+
+const DEFAULT_TIMEOUT_SECS: u16 = 5;
+
+const DEFAULT_MAX_SIZE: u32 = 256 * 1024;
+
+/// The number of messages whose linked pages' titles are being fetched
+static ACTIVE_FETCH_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref CONFIG: RwLock<Option<UrlTitleConfig>> = RwLock::new(None);
+    static ref URL_REGEX: regex::Regex =
+        regex::Regex::new(r"\bhttps?://[^[:space:]<>]+").expect(STATIC_REGEX_PARSE_ERR_MSG);
+}
+
+/// This module provides a trigger that watches all messages in the channels in which it is enabled
+/// and, when a message contains URLs of Web pages, fetches the pages and posts their titles.
+///
+/// The module is configured with the file `url-title.yaml` in the bot's module data directory,
+/// which should constitute a YAML mapping with the following fields:
+///
+/// - `channels` — The value of this field should be a string, which is to be parsed as a [`regex`]
+/// and matched against the channel identifiers (as defined in the documentation of the bot's
+/// configuration) of the channels in which the bot sees a message, to determine whether titles
+/// should be posted in that channel. Titles are never posted in one-to-one messaging.
+///
+/// - `allowed hosts` — The value of this field, if specified, should be a sequence of strings,
+/// which are hostnames, such as `example.com`. If this field is specified, only pages from these
+/// hosts and their subdomains are fetched. This field is optional.
+///
+/// - `denied hosts` — The value of this field, if specified, should be a sequence of hostnames,
+/// pages from which, or from whose subdomains, are never fetched, even if they are allowed by
+/// `allowed hosts`. This field is optional.
+///
+/// - `timeout` — The value of this field, if specified, should be a positive integer, which is to
+/// be used as the number of seconds after which the bot should give up on fetching the pages
+/// linked in a message, including resolving their hosts' addresses and following redirections.
+/// This field is optional; its value defaults to 5.
+///
+/// - `max size` — The value of this field, if specified, should be a positive integer, which is to
+/// be used as the greatest number of bytes of a page that the bot should download in search of
+/// its title. This field is optional; its value defaults to 262144 (256 KiB).
+///
+/// If the file does not exist, the trigger does nothing. The file is read when the module is
+/// loaded or reloaded.
+///
+/// Pages are fetched only from hosts that resolve to public IP addresses, so that the bot cannot
+/// be used to probe its own host or local network. At most three URLs are handled per message, and
+/// titles are shown with the nicknames of the channel's users munged so as not to ping them. Pages
+/// are fetched in the background, and, if the pages linked in several other messages are already
+/// being fetched, a message's URLs are ignored.
+///
+/// [`regex`]: <https://docs.rs/regex/*/regex/#syntax>
+pub fn mk() -> Module {
+    mk_module("url-title")
+        .trigger(
+            "url-title",
+            r"(?s).*\bhttps?://[^[:space:]<>].*",
+            "Post the titles of Web pages linked in channels in which this is enabled.",
+            TriggerPriority::Medium,
+            Box::new(url_title_trigger),
+            &[TriggerAttr::AlwaysWatching],
+        )
+        .on_load(Box::new(on_load))
+        .end()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UrlTitleConfig {
+    channels: Regex<rx_cfg::Anchored<rx_cfg::SizeLimit>>,
+
+    #[serde(default, rename = "allowed hosts")]
+    allowed_hosts: Option<Vec<String>>,
+
+    #[serde(default, rename = "denied hosts")]
+    denied_hosts: Vec<String>,
+
+    #[serde(default = "default_timeout_secs", rename = "timeout")]
+    timeout_secs: u16,
+
+    #[serde(default = "default_max_size", rename = "max size")]
+    max_size: u32,
+}
+
+/// The outcome of a single HTTP request
+#[derive(Debug)]
+enum Response {
+    Page(Vec<u8>),
+
+    Redirect(String),
+
+    Other,
+}
+
+/// A claim on one of the `MAX_CONCURRENT_FETCHES` slots for fetching pages, which is released when
+/// this is dropped
+struct FetchSlot(());
+
+impl FetchSlot {
+    fn claim() -> Option<Self> {
+        if ACTIVE_FETCH_COUNT.fetch_add(1, Ordering::SeqCst) < MAX_CONCURRENT_FETCHES {
+            Some(FetchSlot(()))
+        } else {
+            ACTIVE_FETCH_COUNT.fetch_sub(1, Ordering::SeqCst);
+            None
+        }
+    }
+}
+
+impl Drop for FetchSlot {
+    fn drop(&mut self) {
+        ACTIVE_FETCH_COUNT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn default_timeout_secs() -> u16 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_max_size() -> u32 {
+    DEFAULT_MAX_SIZE
+}
+
+fn on_load(state: &State) -> Result<()> {
+    let path = state.module_data_path()?.join(CONFIG_FILE_NAME);
+
+    let new_config = if path.exists() {
+        let config: UrlTitleConfig = serde_yaml::from_str(&fs::read_to_string(&path)?)?;
+
+        if config.timeout_secs == 0 || config.max_size == 0 {
+            return Err(format!(
+                "The `url-title` configuration file {:?} sets `timeout` or `max size` to zero.",
+                path
+            )
+            .into());
+        }
+
+        Some(config)
+    } else {
+        debug!("No `url-title` configuration file found; not posting URL titles.");
+        None
+    };
+
+    *CONFIG.write_clean("the `url-title` configuration")? = new_config;
+
+    Ok(())
+}
+
+fn url_title_trigger(
+    HandlerContext {
+        state,
+        request_origin,
+        ..
+    }: HandlerContext,
+    args: Captures,
+) -> Result<BotCmdResult> {
+    let config = match *CONFIG.read_clean("the `url-title` configuration")? {
+        Some(ref config) => config.clone(),
+        None => return Ok(Reaction::None.into()),
+    };
+
+    match state.channel_id(request_origin)? {
+        Some(ref id) if config.channels.is_match(&id.to_string()) => {}
+        _ => return Ok(Reaction::None.into()),
+    }
+
+    let urls = URL_REGEX
+        .find_iter(&args[0])
+        .filter_map(|m| Url::parse(m.as_str().trim_end_matches(URL_TRAILING_PUNCTUATION)).ok())
+        .take(MAX_URLS_PER_MSG)
+        .collect::<Vec<_>>();
+
+    if urls.is_empty() {
+        return Ok(Reaction::None.into());
+    }
+
+    let fetch_slot = match FetchSlot::claim() {
+        Some(slot) => slot,
+        None => {
+            debug!(
+                "Too many pages are being fetched; ignoring the URLs {:?}.",
+                urls
+            );
+            return Ok(Reaction::None.into());
+        }
+    };
+
+    let channel_users = state.with_aatxe_client(request_origin.server_id, |aatxe_client| {
+        Ok(aatxe_client
+            .list_users(request_origin.target)
+            .unwrap_or_default())
+    })?;
+
+    let deadline = Instant::now() + Duration::from_secs(config.timeout_secs.into());
+
+    // Fetching pages can take a while, so do it on a thread of its own, rather than holding up
+    // the handling of the message.
+    Ok(BotCmdResult::Defer(
+        Reaction::None,
+        Box::new(move |_: &State| {
+            let _fetch_slot = fetch_slot;
+
+            let titles = urls
+                .into_iter()
+                .filter_map(|url| match fetch_title(&config, url.clone(), deadline) {
+                    Ok(title) => title,
+                    Err(e) => {
+                        debug!("Failed to fetch the title of <{}>: {}", url, e);
+                        None
+                    }
+                })
+                .map(|title| {
+                    Cow::Owned(format!(
+                        "Title: {}",
+                        munge_user_nicks(&title, &channel_users)
+                    ))
+                })
+                .collect::<Vec<_>>();
+
+            Reaction::Msgs(titles.into())
+        }),
+    ))
+}
+
+fn munge_user_nicks(s: &str, users: &[AatxeUser]) -> String {
+    util::zwsp_munge(s, users.iter().map(|user| user.get_nickname())).collect()
+}
+
+/// Fetches the Web page at the given URL, following redirections, and returns its title, or
+/// `None` if it is not an HTML page or has no title, giving up once the given deadline has passed.
+fn fetch_title(config: &UrlTitleConfig, url: Url, deadline: Instant) -> Result<Option<String>> {
+    let mut url = url;
+
+    for _ in 0..=MAX_REDIRECTS {
+        match fetch(config, &url, deadline)? {
+            Response::Page(body) => return Ok(extract_title(&body)),
+            Response::Redirect(location) => {
+                url = url
+                    .join(&location)
+                    .map_err(|e| format!("Bad redirection to {:?}: {}", location, e))?;
+            }
+            Response::Other => return Ok(None),
+        }
+    }
+
+    debug!("Gave up on fetching <{}> after too many redirections.", url);
+
+    Ok(None)
+}
+
+/// Sends an HTTP request for the given URL and reads the response, up to the configured size
+/// limit and until the given deadline.
+fn fetch(config: &UrlTitleConfig, url: &Url, deadline: Instant) -> Result<Response> {
+    let host = match url.host_str() {
+        Some(host) if url.scheme() == "http" || url.scheme() == "https" => host,
+        _ => return Ok(Response::Other),
+    };
+
+    if !host_is_permitted(config, host) {
+        debug!("Not fetching <{}>, whose host is not permitted.", url);
+        return Ok(Response::Other);
+    }
+
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addr = resolve(host, port, deadline)?
+        .into_iter()
+        .find(|addr| ip_is_public(addr.ip()))
+        .ok_or_else(|| format!("The host {:?} has no public IP address.", host))?;
+
+    let stream = TcpStream::connect_timeout(&addr, time_until(deadline)?)?;
+    stream.set_read_timeout(Some(time_until(deadline)?))?;
+    stream.set_write_timeout(Some(time_until(deadline)?))?;
+
+    // This shares the socket of `stream`, so that `exchange` can adjust its timeouts even once
+    // `stream` has been wrapped in a TLS stream.
+    let socket = stream.try_clone()?;
+
+    // HTTP/1.0 is used so that the response will not use chunked transfer encoding.
+    let request = format!(
+        "GET {path} HTTP/1.0\r\n\
+         Host: {host}\r\n\
+         User-Agent: {name}/{version}\r\n\
+         Accept: text/html, application/xhtml+xml\r\n\
+         Connection: close\r\n\
+         \r\n",
+        path = &url[Position::BeforePath..Position::AfterQuery],
+        host = &url[Position::BeforeHost..Position::AfterPort],
+        name = env!("CARGO_PKG_NAME"),
+        version = env!("CARGO_PKG_VERSION"),
+    );
+
+    let response = if url.scheme() == "https" {
+        let connector = TlsConnector::new().map_err(|e| format!("TLS error: {}", e))?;
+        let stream = connector
+            .connect(host, stream)
+            .map_err(|e| format!("TLS error: {}", e))?;
+        exchange(config, stream, &socket, &request, deadline)?
+    } else {
+        exchange(config, stream, &socket, &request, deadline)?
+    };
+
+    Ok(parse_response(&response))
+}
+
+/// Writes the given request to the given stream and reads the response until the stream ends,
+/// the configured size limit is reached, the end of the page's title has been read, or the given
+/// deadline passes. `socket` should be the TCP stream underlying `stream`, whose read timeout is
+/// shortened before each read so that no read outlasts the deadline.
+fn exchange<S>(
+    config: &UrlTitleConfig,
+    mut stream: S,
+    socket: &TcpStream,
+    request: &str,
+    deadline: Instant,
+) -> Result<Vec<u8>>
+where
+    S: Read + Write,
+{
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let max_size = config.max_size as usize;
+    let mut response = Vec::new();
+    let mut chunk = [0; READ_CHUNK_SIZE];
+
+    while response.len() < max_size {
+        let now = Instant::now();
+
+        if now >= deadline {
+            break;
+        }
+
+        socket.set_read_timeout(Some(deadline - now))?;
+
+        let len = match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == IoErrorKind::Interrupted => continue,
+            // Whatever was read before the error may suffice.
+            Err(_) if !response.is_empty() => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        response.extend_from_slice(&chunk[..len.min(max_size - response.len())]);
+
+        if find_ascii_case_insensitive(&response, b"</title").is_some() {
+            break;
+        }
+    }
+
+    Ok(response)
+}
+
+/// Resolves the given host to socket addresses with the given port, giving up once the given
+/// deadline has passed. The system's resolver offers no timeout of its own, so it is run on a
+/// thread of its own, which is left to finish on its own if the deadline passes.
+fn resolve(host: &str, port: u16, deadline: Instant) -> Result<Vec<SocketAddr>> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    let owned_host = host.to_owned();
+
+    thread::Builder::new()
+        .name("url-title DNS resolution".into())
+        .spawn(move || {
+            let addrs = (owned_host.as_str(), port)
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            let _ = sender.send(addrs);
+        })
+        .map_err(ErrorKind::ThreadSpawnFailure)?;
+
+    match receiver.recv_timeout(time_until(deadline)?) {
+        Ok(addrs) => Ok(addrs?),
+        Err(_) => Err(format!("Timed out resolving the host {:?}.", host).into()),
+    }
+}
+
+/// Returns the time left until the given deadline, or an error if it has passed.
+fn time_until(deadline: Instant) -> Result<Duration> {
+    let now = Instant::now();
+
+    if now < deadline {
+        Ok(deadline - now)
+    } else {
+        Err("Timed out.".into())
+    }
+}
+
+/// Parses an HTTP response, returning the body if the response is a successful one with HTML
+/// content.
+fn parse_response(response: &[u8]) -> Response {
+    let (head, body) = match find_ascii_case_insensitive(response, b"\r\n\r\n") {
+        Some(idx) => (&response[..idx], &response[idx + 4..]),
+        None => (response, &[][..]),
+    };
+
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.lines();
+
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .unwrap_or(0);
+
+    let header = |name: &str| {
+        head.lines().skip(1).find_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(n), Some(value)) if n.trim().eq_ignore_ascii_case(name) => {
+                    Some(value.trim().to_owned())
+                }
+                _ => None,
+            }
+        })
+    };
+
+    match status {
+        301 | 302 | 303 | 307 | 308 => match header("Location") {
+            Some(location) => Response::Redirect(location),
+            None => Response::Other,
+        },
+        200..=299 => match header("Content-Type") {
+            Some(ref content_type) if content_type.to_ascii_lowercase().contains("html") => {
+                Response::Page(body.to_owned())
+            }
+            _ => Response::Other,
+        },
+        _ => Response::Other,
+    }
+}
+
+/// Returns the text of the `title` element of the given HTML document, with HTML character
+/// references decoded and whitespace collapsed, or `None` if it has no non-empty title.
+fn extract_title(html: &[u8]) -> Option<String> {
+    let start = find_ascii_case_insensitive(html, b"<title")?;
+    let start = start + html[start..].iter().position(|&b| b == b'>')? + 1;
+    let end = start + find_ascii_case_insensitive(&html[start..], b"</title")?;
+
+    let title = decode_char_refs(&String::from_utf8_lossy(&html[start..end]))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if title.is_empty() {
+        return None;
+    }
+
+    Some(match title.char_indices().nth(MAX_TITLE_LEN) {
+        Some((idx, _)) => format!("{}…", &title[..idx]),
+        None => title,
+    })
+}
+
+/// Decodes the HTML character references in the given text, other than named references beyond
+/// the few commonly found in titles, which are left as they are.
+fn decode_char_refs(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp_idx) = rest.find('&') {
+        output.push_str(&rest[..amp_idx]);
+        rest = &rest[amp_idx..];
+
+        let decoded = rest.find(';').and_then(|semi_idx| {
+            let name = &rest[1..semi_idx];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if name.starts_with("#x") || name.starts_with("#X") => {
+                    u32::from_str_radix(&name[2..], 16)
+                        .ok()
+                        .and_then(::std::char::from_u32)
+                }
+                _ if name.starts_with('#') => {
+                    name[1..].parse().ok().and_then(::std::char::from_u32)
+                }
+                _ => None,
+            };
+            c.map(|c| (c, semi_idx))
+        });
+
+        match decoded {
+            Some((c, semi_idx)) => {
+                output.push(c);
+                rest = &rest[semi_idx + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn find_ascii_case_insensitive(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Returns whether pages may be fetched from the given host under the given configuration.
+fn host_is_permitted(config: &UrlTitleConfig, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    let matches = |pattern: &String| {
+        let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+        host == pattern || host.ends_with(&format!(".{}", pattern))
+    };
+
+    if config.denied_hosts.iter().any(&matches) {
+        return false;
+    }
+
+    match config.allowed_hosts {
+        Some(ref allowed_hosts) => allowed_hosts.iter().any(&matches),
+        None => true,
+    }
+}
+
+/// Returns whether the given IP address is one on the public Internet, rather than, e.g., a
+/// loopback, private-network, or link-local address.
+fn ip_is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // "This network" (RFC 1122)
+                || ip.octets()[0] == 0
+                // Shared address space (RFC 6598)
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xC0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            if ip.is_loopback() || ip.is_unspecified() {
+                return false;
+            }
+
+            if let Some(ip) = ip.to_ipv4() {
+                return ip_is_public(IpAddr::V4(ip));
+            }
+
+            let first_segment = ip.segments()[0];
+
+            !(ip.is_multicast()
+                // Unique local addresses (RFC 4193)
+                || first_segment & 0xFE00 == 0xFC00
+                // Link-local addresses
+                || first_segment & 0xFFC0 == 0xFE80)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allowed_hosts: Option<&[&str]>, denied_hosts: &[&str]) -> UrlTitleConfig {
+        let to_strings = |hosts: &[&str]| hosts.iter().map(|&h| h.to_owned()).collect();
+
+        UrlTitleConfig {
+            channels: ".*".parse().unwrap(),
+            allowed_hosts: allowed_hosts.map(to_strings),
+            denied_hosts: to_strings(denied_hosts),
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    #[test]
+    fn title_extraction() {
+        assert_eq!(
+            extract_title(
+                b"<html><head><TITLE lang=en>\n  Fish &amp; Chips &#8212; &#x41;\n</title>"
+            )
+            .unwrap(),
+            "Fish & Chips \u{2014} A"
+        );
+        assert_eq!(
+            extract_title(b"<title>Q&A &bogus; &</title>").unwrap(),
+            "Q&A &bogus; &"
+        );
+        assert_eq!(extract_title(b"<title> </title>"), None);
+        assert_eq!(extract_title(b"<title>Unterminated"), None);
+        assert_eq!(extract_title(b"<p>No title</p>"), None);
+
+        let long_title = format!("<title>{}</title>", "é".repeat(MAX_TITLE_LEN + 1));
+        let title = extract_title(long_title.as_bytes()).unwrap();
+        assert_eq!(title.chars().count(), MAX_TITLE_LEN + 1);
+        assert!(title.ends_with('…'));
+    }
+
+    #[test]
+    fn response_parsing() {
+        match parse_response(
+            b"HTTP/1.1 200 OK\r\ncontent-type: text/html; charset=utf-8\r\n\r\n<title>x</title>",
+        ) {
+            Response::Page(body) => assert_eq!(body, b"<title>x</title>"),
+            r => panic!("unexpected response: {:?}", r),
+        }
+
+        match parse_response(b"HTTP/1.0 301 Moved\r\nLocation: /elsewhere\r\n\r\n") {
+            Response::Redirect(location) => assert_eq!(location, "/elsewhere"),
+            r => panic!("unexpected response: {:?}", r),
+        }
+
+        match parse_response(b"HTTP/1.0 200 OK\r\nContent-Type: image/png\r\n\r\n<title>") {
+            Response::Other => {}
+            r => panic!("unexpected response: {:?}", r),
+        }
+
+        match parse_response(b"HTTP/1.0 404 Not Found\r\nContent-Type: text/html\r\n\r\n") {
+            Response::Other => {}
+            r => panic!("unexpected response: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn fetch_deadline() {
+        let start = Instant::now();
+
+        assert!(fetch_title(
+            &config(None, &[]),
+            Url::parse("http://example.com/").unwrap(),
+            start
+        )
+        .is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn host_permissions() {
+        let cfg = config(None, &["bad.example"]);
+        assert!(host_is_permitted(&cfg, "example.com"));
+        assert!(!host_is_permitted(&cfg, "bad.example"));
+        assert!(!host_is_permitted(&cfg, "www.BAD.example."));
+        assert!(host_is_permitted(&cfg, "notbad.example"));
+
+        let cfg = config(Some(&["example.com"]), &["private.example.com"]);
+        assert!(host_is_permitted(&cfg, "example.com"));
+        assert!(host_is_permitted(&cfg, "www.example.com"));
+        assert!(!host_is_permitted(&cfg, "private.example.com"));
+        assert!(!host_is_permitted(&cfg, "example.org"));
+    }
+
+    #[test]
+    fn public_ips() {
+        for ip in &["93.184.216.34", "2606:2800:220:1::248"] {
+            assert!(ip_is_public(ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in &[
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "172.16.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::ffff:127.0.0.1",
+            "fd00::1",
+            "fe80::1",
+        ] {
+            assert!(!ip_is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
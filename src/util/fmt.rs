@@ -2,6 +2,7 @@ use core;
 use std::any::Any;
 use std::borrow::Cow;
 use std::fmt;
+use std::time::Duration;
 use uuid::Uuid;
 
 pub(crate) struct FmtAny<'a>(pub(crate) &'a Any);
@@ -41,6 +42,32 @@ impl_fmt!(fmt::Debug {
     core::Error;
 });
 
+/// Displays a `Duration` in a human-readable form, such as `2d 3h 0m 5s`, omitting leading zero
+/// units and any fraction of a second.
+pub(crate) struct FmtDuration(pub(crate) Duration);
+
+impl fmt::Display for FmtDuration {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (days, hours, mins, secs) = (
+            secs / 86400,
+            secs % 86400 / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+        );
+
+        if days > 0 {
+            write!(formatter, "{}d {}h {}m {}s", days, hours, mins, secs)
+        } else if hours > 0 {
+            write!(formatter, "{}h {}m {}s", hours, mins, secs)
+        } else if mins > 0 {
+            write!(formatter, "{}m {}s", mins, secs)
+        } else {
+            write!(formatter, "{}s", secs)
+        }
+    }
+}
+
 pub(crate) fn debug_uuid(uuid: &Uuid, formatter: &mut fmt::Formatter) -> fmt::Result {
     write!(formatter, "{}", uuid.hyphenated())
 }
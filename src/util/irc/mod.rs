@@ -2,8 +2,11 @@ use inlinable_string::InlinableString;
 use serde;
 use serde::Deserialize;
 use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
 use smallvec::SmallVec;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::ops::Deref;
 use std::result::Result as StdResult;
@@ -18,6 +21,11 @@ error_chain! {
             description("encountered invalid IRC channel name"),
             display("Invalid IRC channel name: {:?}", input.as_ref()),
         }
+
+        InvalidNickName(input: DefaultAtom) {
+            description("encountered invalid IRC nickname"),
+            display("Invalid IRC nickname: {:?}", input.as_ref()),
+        }
     }
 }
 
@@ -37,8 +45,34 @@ lazy_static! {
     pub static ref CHANNEL_NAME_REGEX: Regex = Regex::from_str(
         r"(?:[#&+]|![[:upper:][:digit:]]{5})[^\x00\a\r\n ,:]{0,49}(?::[^\x00\a\r\n ,:]{0,49})?"
     ).expect(STATIC_REGEX_PARSE_ERR_MSG);
+
+    /// This is a [`lazy_static`] item containing a non-anchored regex that matches an IRC nickname
+    /// as specified in [IETF RFC 2812, section 2.3.1], except that nicknames of any length are
+    /// accepted, as most IRC servers allow nicknames longer than the nine characters that the
+    /// standard specifies.
+    ///
+    /// [IETF RFC 2812, section 2.3.1]: <https://tools.ietf.org/html/rfc2812#section-2.3.1>
+    /// [`lazy_static`]: <https://docs.rs/lazy_static/*/lazy_static/>
+    pub static ref NICKNAME_REGEX: Regex = Regex::from_str(
+        r"[[:alpha:]\[\]\\`_^{|}][[:alnum:]\[\]\\`_^{|}-]*"
+    ).expect(STATIC_REGEX_PARSE_ERR_MSG);
 }
 
+/// The greatest length, in bytes, of an IRC message, counting its prefix (if any) and its
+/// terminating CR-LF sequence, but not counting any IRCv3 message tags, per [IETF RFC 2812,
+/// section 2.3]. As a server prepends the sender's prefix to a message when relaying it, a client
+/// must leave room for that prefix within this limit.
+///
+/// [IETF RFC 2812, section 2.3]: <https://tools.ietf.org/html/rfc2812#section-2.3>
+pub const IRC_MESSAGE_MAX_LEN: usize = 512;
+
+/// The length, in bytes, of the CR-LF sequence that terminates each IRC message
+pub const IRC_LINE_TERMINATOR_LEN: usize = 2;
+
+/// The greatest length of a hostname that the bot expects an IRC server to report in a message
+/// prefix; 63 is the `HOSTLEN` of many common IRC server implementations.
+pub const IRC_WORST_CASE_HOST_LEN: usize = 63;
+
 /// Compares two strings case-insensitively, using the IRC rules for case-folding.
 ///
 /// This function optimizes for comparing short strings such as nicknames and channel names.
@@ -76,6 +110,20 @@ where
     x.cmp(&y)
 }
 
+/// Converts a string to lowercase, using the IRC rules for case-folding, such that two strings are
+/// converted to the same string if and only if `case_insensitive_str_cmp` considers them equal.
+pub fn irc_to_lowercase(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '[' => '{',
+            ']' => '}',
+            '\\' => '|',
+            '~' => '^',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
 /// A string type representing the name of an IRC channel.
 ///
 /// This wrapper around an interned string (specifically, a Servo [`Atom`]) ensures that the string
@@ -160,6 +208,15 @@ impl<'de> Deserialize<'de> for ChannelName {
     }
 }
 
+impl Serialize for ChannelName {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_ref())
+    }
+}
+
 struct ChannelNameDeserializationVisitor;
 
 impl<'de> serde::de::Visitor<'de> for ChannelNameDeserializationVisitor {
@@ -187,9 +244,170 @@ impl<'de> serde::de::Visitor<'de> for ChannelNameDeserializationVisitor {
     }
 }
 
+/// A string type representing an IRC nickname.
+///
+/// Like [`ChannelName`], this wrapper around an interned string (specifically, a Servo [`Atom`])
+/// ensures that the string is a valid IRC nickname and implements comparison operations as
+/// appropriate for IRC nicknames, comparing them case-insensitively per IRC's particular rules for
+/// such comparisons.
+///
+/// [`Atom`]: <https://docs.rs/string_cache/*/string_cache/atom/struct.Atom.html>
+/// [`ChannelName`]: <struct.ChannelName.html>
+#[derive(Clone, Debug)]
+pub struct NickName(DefaultAtom);
+
+impl NickName {
+    /// Constructs a new `NickName` from a string, verifying that the whole string is a single
+    /// match of [`NICKNAME_REGEX`].
+    ///
+    /// An `Err` will be returned if [`NICKNAME_REGEX`] does not match against the whole given
+    /// string.
+    ///
+    /// [`NICKNAME_REGEX`]: <struct.NICKNAME_REGEX.html>
+    pub fn new<S>(name: S) -> Result<Self>
+    where
+        S: Into<DefaultAtom>,
+    {
+        let name = name.into();
+
+        match NICKNAME_REGEX.find(&name) {
+            Some(m) if m.start() == 0 && m.end() == name.len() => Ok(NickName(name)),
+            _ => Err(ErrorKind::InvalidNickName(name).into()),
+        }
+    }
+}
+
+impl Deref for NickName {
+    type Target = DefaultAtom;
+
+    fn deref(&self) -> &Self::Target {
+        let NickName(inner) = self;
+        inner
+    }
+}
+
+impl Ord for NickName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        case_insensitive_str_cmp(self.as_ref(), other.as_ref())
+    }
+}
+
+impl PartialOrd for NickName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for NickName {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for NickName {}
+
+impl fmt::Display for NickName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl<'de> Deserialize<'de> for NickName {
+    fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_string(NickNameDeserializationVisitor)
+    }
+}
+
+impl Serialize for NickName {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_ref())
+    }
+}
+
+struct NickNameDeserializationVisitor;
+
+impl<'de> serde::de::Visitor<'de> for NickNameDeserializationVisitor {
+    type Value = NickName;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a string that can be parsed as an IRC nickname")
+    }
+
+    fn visit_str<E>(self, input: &str) -> StdResult<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Self::Value::new(input).map_err(serde::de::Error::custom)
+    }
+
+    fn visit_string<E>(self, input: String) -> StdResult<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Self::Value::new(input).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A map keyed by IRC channel names, in which names that differ only in case, per IRC's rules for
+/// case-folding, refer to the same entry; e.g., `#Rust` and `#rust` are the same key.
+///
+/// This should be used for any per-channel state, rather than a map keyed by plain strings, so
+/// that channels are looked up consistently however their names happen to be capitalized.
+pub type ChannelMap<V> = BTreeMap<ChannelName, V>;
+
+/// An identifier for an IRC channel that is unique across all the IRC servers to which the bot
+/// may be connected, consisting of the name of a server, as given in the bot's configuration, and
+/// the name of a channel on that server.
+///
+/// The `Display` implementation of this type produces the identifier's canonical string form,
+/// `<server>/<channel>` (e.g., `freenode/##rust`), which is the form that the per-channel settings
+/// `can see` and `seen by` in the bot's configuration are documented to match against.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChannelId {
+    server: DefaultAtom,
+    channel: ChannelName,
+}
+
+impl ChannelId {
+    /// Constructs a new `ChannelId` from the given server name and channel name.
+    pub fn new<S>(server: S, channel: ChannelName) -> Self
+    where
+        S: Into<DefaultAtom>,
+    {
+        ChannelId {
+            server: server.into(),
+            channel,
+        }
+    }
+
+    /// Returns the name of the server on which the identified channel is to be found.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// Returns the name of the identified channel.
+    pub fn channel(&self) -> &ChannelName {
+        &self.channel
+    }
+}
+
+impl fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.server(), self.channel)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_yaml;
 
     /// Constructs `ChannelName`s from strings by using the data constructor directly, bypassing
     /// the check that the strings are valid channel names.
@@ -237,4 +455,48 @@ mod tests {
             ChannelName::to_string(&cn) == ToString::to_string(&cn)
         }
     }
+
+    #[test]
+    fn nickname_validity() {
+        for valid in &["a", "Bot[1]", "under_score", "dash-", "`^{|}\\"] {
+            assert!(NickName::new(*valid).is_ok(), "{:?}", valid);
+        }
+
+        for invalid in &["", "1bot", "-bot", "two words", "bot,", "#chan", "bot!user@host"] {
+            assert!(NickName::new(*invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn nickname_casefolding() {
+        let nick = |s: &str| NickName::new(s).unwrap();
+
+        assert_eq!(nick("Bot[away]"), nick("bot{AWAY}"));
+        assert_eq!(nick("a\\b"), nick("A|B"));
+        assert_ne!(nick("bot"), nick("bot_"));
+        assert_eq!(
+            serde_yaml::from_str::<NickName>("Bot[1]").unwrap(),
+            nick("bot{1}")
+        );
+        assert!(serde_yaml::from_str::<NickName>("'#chan'").is_err());
+    }
+
+    #[test]
+    fn channel_map_casefolding() {
+        let mut map = ChannelMap::new();
+
+        map.insert(ChannelName::new("#Rust[]").unwrap(), 1);
+        map.insert(ChannelName::new("#rust{}").unwrap(), 2);
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&ChannelName::new("#RUST[]").unwrap()], 2);
+        assert!(!map.contains_key(&ChannelName::new("#rust").unwrap()));
+    }
+
+    #[test]
+    fn channel_id_display() {
+        let id = ChannelId::new("freenode", ChannelName::new("##rust").unwrap());
+
+        assert_eq!(id.to_string(), "freenode/##rust");
+    }
 }
@@ -1,6 +1,7 @@
 use core::ErrorKind;
 use core::Result;
 use std::borrow::Cow;
+use std::mem;
 use std::sync::LockResult;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
@@ -88,6 +89,19 @@ impl<T> RoLock<T> {
     pub fn read(&self) -> LockResult<RwLockReadGuard<T>> {
         self.0.read()
     }
+
+    /// Replaces the contained value with `value`, returning the old value.
+    ///
+    /// The write lock that this acquires is held only while the two values are swapped, during
+    /// which no other code is run, so this cannot poison the lock; readers will see either the old
+    /// value or the new value, never anything in between.
+    pub fn replace<Desc>(&self, value: T, description: Desc) -> Result<T>
+    where
+        Desc: Into<Cow<'static, str>>,
+    {
+        let mut guard = self.0.write_clean(description)?;
+        Ok(mem::replace(&mut *guard, value))
+    }
 }
 
 impl<T> ReadLockExt<T> for RoLock<T> {
@@ -1,6 +1,7 @@
 use core::ErrorKind;
 use core::Result;
 use smallvec::SmallVec;
+use std::any::Any;
 use std::borrow::Cow;
 use std::panic;
 
@@ -28,10 +29,29 @@ where
     F: FnOnce() -> R + panic::UnwindSafe,
 {
     panic::catch_unwind(handler_invocation).map_err(|panic_payload| {
-        ErrorKind::HandlerPanic(feature_kind.into(), feature_name.into(), panic_payload).into()
+        ErrorKind::HandlerPanic(
+            feature_kind.into(),
+            feature_name.into(),
+            panic_payload_to_msg(panic_payload),
+        )
+        .into()
     })
 }
 
+/// Extracts the message from the payload of a panic, which is a `&'static str` or a `String` for
+/// panics raised with `panic!`.
+fn panic_payload_to_msg(payload: Box<Any + Send + 'static>) -> Cow<'static, str> {
+    let payload = match payload.downcast::<&'static str>() {
+        Ok(msg) => return Cow::Borrowed(*msg),
+        Err(payload) => payload,
+    };
+
+    match payload.downcast::<String>() {
+        Ok(msg) => Cow::Owned(*msg),
+        Err(payload) => Cow::Owned(fmt::FmtAny(payload.as_ref()).to_string()),
+    }
+}
+
 /// Calls `ToOwned::to_owned` on the argument and wraps the result in `Cow::Owned`.
 pub fn to_cow_owned<T>(x: &T) -> Cow<'static, T>
 where
@@ -150,6 +170,17 @@ impl<'a> ExactSizeIterator for Munge<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn handler_panic_msg() {
+        let display = |result: Result<()>| result.unwrap_err().to_string();
+
+        assert!(display(run_handler("command", "test", || panic!("oh no"))).ends_with(": oh no"));
+        assert!(
+            display(run_handler("command", "test", || panic!("oh {}", "dear")))
+                .ends_with(": oh dear")
+        );
+    }
+
     #[test]
     fn zwsp_munge_examples() {
         let no_strs: &[&'static str] = &[];
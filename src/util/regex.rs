@@ -5,6 +5,8 @@ use regex::RegexBuilder;
 use serde;
 use serde::Deserialize;
 use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
 use std::fmt;
 use std::marker::PhantomData;
 use std::ops::Deref;
@@ -43,7 +45,7 @@ impl IntoRegexCI for regex::Regex {
     }
 }
 
-impl<'a> IntoRegexCI for &'a str {
+impl IntoRegexCI for &str {
     fn into_regex_ci(self) -> RegexBuildResult {
         mk_case_insensitive_regex(self)
     }
@@ -236,6 +238,17 @@ pub trait RegexConfig {
     fn builder_from_string(input: String) -> RegexBuilder {
         Self::builder_from_str(&input)
     }
+
+    /// Given the pattern of a regex built by this configuration, returns the string from which
+    /// the regex was built, undoing any changes that `builder_from_str` made to that string.
+    ///
+    /// This is used to serialize a [`Regex`] such that deserializing it yields an equivalent
+    /// regex. The default implementation returns the pattern unchanged.
+    ///
+    /// [`Regex`]: <struct.Regex.html>
+    fn source_from_pattern(pattern: &str) -> &str {
+        pattern
+    }
 }
 
 pub mod config {
@@ -298,6 +311,16 @@ where
         }
     }
 
+    fn source_from_pattern(pattern: &str) -> &str {
+        let pattern = Base::source_from_pattern(pattern);
+
+        if pattern.starts_with(REGEX_ANCHOR_START) && pattern.ends_with(REGEX_ANCHOR_END) {
+            &pattern[REGEX_ANCHOR_START.len()..pattern.len() - REGEX_ANCHOR_END.len()]
+        } else {
+            pattern
+        }
+    }
+
     // TODO: implement optimized methods too.
 }
 
@@ -310,6 +333,10 @@ where
         rxb.case_insensitive(true);
         rxb
     }
+
+    fn source_from_pattern(pattern: &str) -> &str {
+        Base::source_from_pattern(pattern)
+    }
 }
 
 impl<Base> RegexConfig for config::SizeLimit<Base>
@@ -322,6 +349,10 @@ where
         rxb.dfa_size_limit(REGEX_SIZE_LIMIT);
         rxb
     }
+
+    fn source_from_pattern(pattern: &str) -> &str {
+        Base::source_from_pattern(pattern)
+    }
 }
 
 impl<'de, Cfg> Deserialize<'de> for Regex<Cfg>
@@ -336,6 +367,18 @@ where
     }
 }
 
+impl<Cfg> Serialize for Regex<Cfg>
+where
+    Cfg: RegexConfig,
+{
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(Cfg::source_from_pattern(self.as_str()))
+    }
+}
+
 struct RegexDeserializationVisitor<Cfg>(PhantomData<Cfg>)
 where
     Cfg: RegexConfig;
@@ -601,5 +644,16 @@ mod tests {
 
             test_regex_equivalence_for_input(true, unchanged, unanchored_anchored, &haystack)
         }
+
+        fn anchored_source_round_trips(pattern: String) -> TestResult {
+            let rx = match Regex::<config::SizeLimit<config::Anchored>>::from_str(&pattern) {
+                Ok(rx) => rx,
+                Err(_) => return TestResult::discard(),
+            };
+
+            let source = config::SizeLimit::<config::Anchored>::source_from_pattern(rx.as_str());
+
+            TestResult::from_bool(source == pattern)
+        }
     }
 }
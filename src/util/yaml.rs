@@ -1,4 +1,6 @@
 use ref_slice::ref_slice;
+use serde::de::DeserializeOwned;
+use serde_yaml;
 use smallvec;
 use smallvec::SmallVec;
 use std;
@@ -21,22 +23,32 @@ error_chain! {
             description("wanted a single YAML node but found zero or multiple nodes")
             display("While parsing YAML: Wanted a single node, but found {} nodes.", node_qty)
         }
-        RequiredFieldMissing(name: Cow<'static, str>) {
+        RequiredFieldMissing(name: Cow<'static, str>, object: String) {
             description("a YAML object is missing a required field")
-            display("While handling YAML: An object is missing the required field {:?}.", name)
+            display("While handling YAML: An object is missing the required field {:?}:\n{}",
+                    name,
+                    object)
+        }
+        InvalidNode(path: Cow<'static, str>) {
+            description("encountered an invalid YAML node")
+            display("While handling YAML: {} is invalid. An alias may not refer to a node that \
+                     contains it, and a scalar with a tag such as `!!int` must be a valid value \
+                     of the tagged type.",
+                    path)
         }
         AliasesNotSupported {
             description("encountered a YAML alias (which is not supported by `yaml_rust`)")
             display("While handling YAML: Encountered a YAML alias, which is not supported by \
                      `yaml_rust`.")
         }
-        TypeMismatch(path: Cow<'static, str>, expected_ty: Kind, actual_ty: Kind) {
+        TypeMismatch(path: Cow<'static, str>, expected_ty: Kind, actual_ty: Kind, actual: String) {
             description("encountered a type error while handling YAML")
             display("While handling YAML: Expected {path} to be of type {expected_ty:?}, but it \
-                     is of type {actual_ty:?}.",
+                     is of type {actual_ty:?}:\n{actual}",
                      path = path,
                      expected_ty = expected_ty,
-                     actual_ty = actual_ty)
+                     actual_ty = actual_ty,
+                     actual = actual)
         }
         ExpectedNonEmptyStream {
             description("expected non-empty YAML stream but found empty stream")
@@ -46,6 +58,10 @@ error_chain! {
             description("expected empty YAML stream but found non-empty stream")
             display("While handling YAML: Expected an empty stream, but found a non-empty stream.")
         }
+        ArgInvalid(key: Cow<'static, str>, problem: String) {
+            description("an argument could not be converted to the type that was wanted")
+            display("While handling YAML: The argument {:?} is invalid: {}.", key, problem)
+        }
         ArgGivenByBothLongAndShortKey(long_key: Cow<'static, str>, short_key: Cow<'static, str>) {
             description("wanted one but not both of an argument's full and abbreviated names")
             display("While handling YAML: An argument was given by both its full key {full:?} and \
@@ -62,18 +78,30 @@ pub mod str {
     use yaml_rust::Yaml;
 
     lazy_static! {
+        pub static ref YAML_STR_BY: Yaml = mk_str("by");
         pub static ref YAML_STR_CHAN: Yaml = mk_str("chan");
+        pub static ref YAML_STR_CHANNELS: Yaml = mk_str("channels");
         pub static ref YAML_STR_CMD: Yaml = mk_str("cmd");
         pub static ref YAML_STR_ELLIPSIS: Yaml = mk_str("...");
         pub static ref YAML_STR_ELLIPSIS_IN_SQUARE_BRACKETS: Yaml = mk_str("[...]");
+        pub static ref YAML_STR_FILE: Yaml = mk_str("file");
+        pub static ref YAML_STR_FORMAT: Yaml = mk_str("format");
+        pub static ref YAML_STR_FUZZY: Yaml = mk_str("fuzzy");
+        pub static ref YAML_STR_HOST: Yaml = mk_str("host");
         pub static ref YAML_STR_ID: Yaml = mk_str("id");
+        pub static ref YAML_STR_IN: Yaml = mk_str("in");
         pub static ref YAML_STR_LIST: Yaml = mk_str("list");
         pub static ref YAML_STR_MSG: Yaml = mk_str("msg");
+        pub static ref YAML_STR_NICK: Yaml = mk_str("nick");
         pub static ref YAML_STR_R: Yaml = mk_str("r");
         pub static ref YAML_STR_REGEX: Yaml = mk_str("regex");
         pub static ref YAML_STR_S: Yaml = mk_str("s");
+        pub static ref YAML_STR_SEARCH: Yaml = mk_str("search");
         pub static ref YAML_STR_STRING: Yaml = mk_str("string");
         pub static ref YAML_STR_TAG: Yaml = mk_str("tag");
+        pub static ref YAML_STR_TAGS: Yaml = mk_str("tags");
+        pub static ref YAML_STR_TEXT: Yaml = mk_str("text");
+        pub static ref YAML_STR_USER: Yaml = mk_str("user");
     }
 }
 
@@ -170,6 +198,29 @@ where
     }
 }
 
+/// Converts any type of YAML node to a string, as `any_to_str` does, except that a sequence or
+/// mapping is written in an indented, multi-line form that is easier to read, e.g., in error
+/// messages.
+pub fn pretty_to_str<'a, 'b, F>(node: &'a Yaml, lt_map: F) -> Result<Cow<'b, str>>
+where
+    F: Fn(&'a str) -> Cow<'b, str>,
+{
+    match node.as_str() {
+        Some(s) => Ok(lt_map(s)),
+        None => {
+            let mut s = String::new();
+
+            {
+                let mut emitter = YamlEmitter::new(&mut s);
+                emitter.compact(false);
+                emitter.dump(node)?;
+            }
+
+            Ok(to_cow_owned(s.trim_start_matches("---\n")))
+        }
+    }
+}
+
 /// Converts a scalar YAML node to a string.
 ///
 /// If the `node` is scalar, returns the same value as `any_to_str(node, lt_map)`. If the `node` is
@@ -189,9 +240,13 @@ where
 {
     match Kind::of(node) {
         Kind::Scalar => any_to_str(node, lt_map),
-        wrong_kind => {
-            Err(ErrorKind::TypeMismatch(subject_label.into(), Kind::Scalar, wrong_kind).into())
-        }
+        wrong_kind => Err(ErrorKind::TypeMismatch(
+            subject_label.into(),
+            Kind::Scalar,
+            wrong_kind,
+            pretty_to_str(node, to_cow_owned)?.into_owned(),
+        )
+        .into()),
     }
 }
 
@@ -250,6 +305,66 @@ pub fn get_arg_by_short_or_long_key<'a>(
     }
 }
 
+/// Gets an argument from a hash-map of arguments by its key, and deserializes it into a `T`.
+///
+/// If the argument is not found, `Ok(None)` is returned. If it cannot be deserialized into a `T`,
+/// an `Err` naming the argument is returned.
+///
+/// ```
+/// # extern crate irc_bot;
+/// # extern crate yaml_rust;
+/// # use irc_bot::util::yaml::get_arg_as;
+/// # use irc_bot::util::yaml::mk_str;
+/// # use irc_bot::util::yaml::parse_node;
+/// # use yaml_rust::Yaml;
+/// # fn main() {
+/// let arg = parse_node("{n: 3, s: x}").unwrap().unwrap();
+/// let arg = arg.as_hash().unwrap();
+///
+/// assert_eq!(get_arg_as::<u32>(arg, &mk_str("n")).unwrap(), Some(3));
+/// assert_eq!(get_arg_as::<u32>(arg, &mk_str("m")).unwrap(), None);
+/// assert!(get_arg_as::<u32>(arg, &mk_str("s")).is_err());
+/// # }
+/// ```
+pub fn get_arg_as<T>(args: &yaml::Hash, key: &Yaml) -> Result<Option<T>>
+where
+    T: DeserializeOwned,
+{
+    let node = match args.get(key) {
+        Some(node) => node,
+        None => return Ok(None),
+    };
+
+    match serde_yaml::from_value(to_serde_value(node)?) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => Err(ErrorKind::ArgInvalid(any_to_str(key, to_cow_owned)?, e.to_string()).into()),
+    }
+}
+
+/// Converts a `yaml_rust` YAML node into the equivalent `serde_yaml` YAML node.
+fn to_serde_value(node: &Yaml) -> Result<serde_yaml::Value> {
+    Ok(match *node {
+        Yaml::Real(ref s) => match node.as_f64() {
+            Some(f) => f.into(),
+            None => s.clone().into(),
+        },
+        Yaml::Integer(i) => i.into(),
+        Yaml::String(ref s) => s.clone().into(),
+        Yaml::Boolean(b) => b.into(),
+        Yaml::Array(ref seq) => {
+            serde_yaml::Value::Sequence(seq.iter().map(to_serde_value).collect::<Result<_>>()?)
+        }
+        Yaml::Hash(ref map) => serde_yaml::Value::Mapping(
+            map.iter()
+                .map(|(k, v)| Ok((to_serde_value(k)?, to_serde_value(v)?)))
+                .collect::<Result<_>>()?,
+        ),
+        Yaml::Null => serde_yaml::Value::Null,
+        Yaml::Alias(_) => bail!(ErrorKind::AliasesNotSupported),
+        Yaml::BadValue => bail!(ErrorKind::InvalidNode("the value".into())),
+    })
+}
+
 /// Parses a lone YAML node.
 ///
 /// Wraps `yaml_rust::YamlLoader::load_from_str` to parse a single YAML node.
@@ -284,12 +399,46 @@ where
     S1: Into<Cow<'s, str>>,
 {
     let node = parse_node(src)?.unwrap_or_else(default);
+    let subject_label = subject_label.into();
+
+    check_validity(&node, &mut vec![subject_label.clone().into_owned()])?;
 
     check_type(expected_syntax, &node, subject_label)?;
 
     Ok(node)
 }
 
+/// Checks that a YAML node contains no `Yaml::BadValue`, which `yaml_rust` produces in place of an
+/// alias to a node that contains the alias, or of a scalar whose value does not match its tag.
+///
+/// `yaml_rust` expands other aliases as it parses, so that they need no further handling.
+///
+/// `path_buf` should contain a label identifying the node, for use in error messages.
+fn check_validity(node: &Yaml, path_buf: &mut Vec<String>) -> Result<()> {
+    match *node {
+        Yaml::BadValue => bail!(ErrorKind::InvalidNode(path_buf.join(".").into())),
+        Yaml::Alias(_) => bail!(ErrorKind::AliasesNotSupported),
+        Yaml::Array(ref seq) => {
+            for (idx, item) in seq.iter().enumerate() {
+                path_buf.push(idx.to_string());
+                check_validity(item, path_buf)?;
+                path_buf.pop();
+            }
+        }
+        Yaml::Hash(ref map) => {
+            for (key, value) in map {
+                check_validity(key, path_buf)?;
+                path_buf.push(any_to_str(key, to_cow_owned)?.into_owned());
+                check_validity(value, path_buf)?;
+                path_buf.pop();
+            }
+        }
+        Yaml::Real(_) | Yaml::Integer(_) | Yaml::String(_) | Yaml::Boolean(_) | Yaml::Null => {}
+    }
+
+    Ok(())
+}
+
 /// Checks that a YAML object has a given type and structure.
 ///
 /// Checks that the `actual` YAML object matches the type and structure of the `expected` YAML
@@ -360,6 +509,7 @@ where
             path_buf.join(".").into(),
             Kind::from_aug_ty(&expected_ty),
             Kind::from_aug_ty(&actual_ty),
+            pretty_to_str(actual, to_cow_owned)?.into_owned(),
         )),
         (_, &Ty::Other) | (&Ty::Other, _) => bail!(ErrorKind::AliasesNotSupported),
     }
@@ -400,9 +550,10 @@ where
                     any_to_str(key, Cow::Borrowed)?,
                 )?
             }
-            (_, None) => bail!(ErrorKind::RequiredFieldMissing(any_to_str(key, |s| s
-                .to_owned()
-                .into())?)),
+            (_, None) => bail!(ErrorKind::RequiredFieldMissing(
+                any_to_str(key, to_cow_owned)?,
+                pretty_to_str(&Yaml::Hash(actual_fields.clone()), to_cow_owned)?.into_owned(),
+            )),
         }
     }
 